@@ -0,0 +1,140 @@
+//! Benchmarks of parsing and looking up symbols in large `.dbg` files
+//!
+//! A kernel sized file is generated with `DbgWriter`. Real files can be
+//! benchmarked too by listing them in `DBGPARSE_BENCH_FILES`, separated like
+//! `PATH`, eg. `DBGPARSE_BENCH_FILES=ntoskrnl.dbg:win32k.dbg cargo bench`.
+
+use std::io::{BufReader, Cursor};
+use std::path::{Path, PathBuf};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use criterion::Throughput;
+use dbgparse::{DbgFile, DbgWriter, Header, LoadOptions, Machine, Section};
+
+/// Number of functions in the generated file, about as many as a kernel
+const FUNCTIONS: u32 = 20_000;
+
+/// Number of globals in the generated file
+const GLOBALS: u32 = 10_000;
+
+/// Number of source lines in each generated function
+const LINES_PER_FUNCTION: u32 = 16;
+
+/// Number of source files the generated lines are spread over
+const SOURCES: u32 = 500;
+
+/// Build a large `.dbg` file with code and data sections, functions with
+/// sizes and lines, and globals
+fn generate() -> Vec<u8> {
+    let mut writer = DbgWriter::new(Header {
+        machine:       Machine::I386,
+        image_base:    0x80100000,
+        size_of_image: 0x800000,
+        section_align: 0x1000,
+        ..Default::default()
+    });
+    writer.section(Section {
+        name:            ".text".into(),
+        vsize:           0x400000,
+        vaddr:           0x1000,
+        raw_data_sz:     0x400000,
+        ptr_raw_data:    0x400,
+        characteristics: 0x60000020,
+    });
+    writer.section(Section {
+        name:            ".data".into(),
+        vsize:           0x100000,
+        vaddr:           0x401000,
+        raw_data_sz:     0x100000,
+        ptr_raw_data:    0x400400,
+        characteristics: 0xc0000040,
+    });
+
+    for idx in 0..FUNCTIONS {
+        let rva = 0x1000 + idx * 0x100;
+        writer.function(rva, &format!("_Function{}@{}", idx, idx % 8 * 4));
+        writer.function_size(rva, 0xf0);
+
+        let source = format!("d:\\nt\\private\\ntos\\src{}.c", idx % SOURCES);
+        for line in 0..LINES_PER_FUNCTION {
+            writer.line(rva + line * 0xf, &source, idx * 20 + line + 1);
+        }
+    }
+    for idx in 0..GLOBALS {
+        writer.global(0x401000 + idx * 0x10, &format!("_Global{}", idx));
+    }
+    writer.to_bytes()
+}
+
+/// Write `data` to a file in the temporary directory for the benchmarks
+/// which read from a file, returning its path
+fn write_temp(data: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir()
+        .join(format!("dbgparse-bench-{}.dbg", std::process::id()));
+    std::fs::write(&path, data).expect("failed to write benchmark file");
+    path
+}
+
+/// Benchmark each way of loading the file `name` at `path`, holding `data`
+fn bench_file(c: &mut Criterion, name: &str, path: &Path, data: &[u8]) {
+    let mut group = c.benchmark_group("load");
+    group.throughput(Throughput::Bytes(data.len() as u64));
+
+    group.bench_with_input(BenchmarkId::new("parse", name), data, |b, x| {
+        b.iter(|| DbgFile::parse(x).unwrap());
+    });
+    group.bench_with_input(BenchmarkId::new("open", name), path, |b, x| {
+        b.iter(|| DbgFile::open(x).unwrap());
+    });
+    group.bench_with_input(BenchmarkId::new("bufreader", name), path,
+            |b, x| {
+        b.iter(|| {
+            let fd = std::fs::File::open(x).unwrap();
+            DbgFile::load(BufReader::new(fd)).unwrap()
+        });
+    });
+    group.bench_with_input(BenchmarkId::new("lazy", name), data, |b, x| {
+        let opts = LoadOptions::default().lazy(true).clone();
+        b.iter(|| DbgFile::load_with(Cursor::new(x), &opts).unwrap());
+    });
+    group.finish();
+
+    // Look up an address in every function, as symbolizing a trace does
+    let dbg = DbgFile::parse(data).unwrap();
+    let rvas = dbg.functions().keys().map(|x| x + 1).collect::<Vec<_>>();
+    let mut group = c.benchmark_group("lookup");
+    group.throughput(Throughput::Elements(rvas.len() as u64));
+    group.bench_with_input(BenchmarkId::new("nearest_symbol", name), &rvas,
+            |b, rvas| {
+        b.iter(|| {
+            rvas.iter().filter(|&&x| dbg.nearest_symbol(x).is_some()).count()
+        });
+    });
+    group.bench_with_input(BenchmarkId::new("lines_in_range", name), &rvas,
+            |b, rvas| {
+        b.iter(|| {
+            rvas.iter().map(|&x| dbg.lines_in_range(x..x + 0x10).count())
+                .sum::<usize>()
+        });
+    });
+    group.finish();
+}
+
+/// Benchmark the generated file and any listed in `DBGPARSE_BENCH_FILES`
+fn benches(c: &mut Criterion) {
+    let data = generate();
+    let path = write_temp(&data);
+    bench_file(c, "generated", &path, &data);
+    let _ = std::fs::remove_file(&path);
+
+    let files = std::env::var_os("DBGPARSE_BENCH_FILES").unwrap_or_default();
+    let files = std::env::split_paths(&files)
+        .filter(|x| !x.as_os_str().is_empty());
+    for path in files {
+        let data = std::fs::read(&path).expect("failed to read bench file");
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        bench_file(c, &name, &path, &data);
+    }
+}
+
+criterion_group!(parse, benches);
+criterion_main!(parse);
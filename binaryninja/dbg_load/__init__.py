@@ -13,7 +13,12 @@ def load_dbg_file(bv, function):
         # Parse the debug file
         output = subprocess.check_output(["dbgparse", dbg_file]).decode()
         for line in output.splitlines():
-            (typ, addr, name) = rex.match(line).groups()
+            # Skip record types we don't handle, such as aliases
+            match = rex.match(line)
+            if match is None:
+                continue
+
+            (typ, addr, name) = match.groups()
             addr = bv.start + int(addr, 16)
 
             (mangle_typ, mangle_name) = demangle.demangle_ms(bv.arch, name)
@@ -0,0 +1,30 @@
+//! Checks shared by the fuzz targets
+
+use std::io::Cursor;
+use dbgparse::{DbgFile, LoadOptions};
+
+/// Load `data` with `opts` and walk everything which was parsed, which
+/// covers the lazily parsed symbols too
+pub fn load(data: &[u8], opts: &LoadOptions) {
+    let Ok(dbg) = DbgFile::load_with(Cursor::new(data), opts) else {
+        return;
+    };
+
+    for &rva in dbg.functions().keys() {
+        let _ = dbg.function_size(rva);
+        let _ = dbg.nearest_symbol(rva);
+        let _ = dbg.rva_to_file_offset(rva);
+    }
+    for &rva in dbg.globals().keys() {
+        let _ = dbg.nearest_symbol(rva);
+    }
+    for name in dbg.aliases().keys() {
+        let _ = dbg.find_symbol(name);
+    }
+    let _ = dbg.symbols_in_range(..).count();
+    let _ = dbg.lines_by_file();
+    let _ = dbg.diagnostics().count();
+    for section in dbg.sections() {
+        let _ = dbg.file_offset_to_rva(section.ptr_raw_data);
+    }
+}
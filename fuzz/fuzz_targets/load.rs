@@ -0,0 +1,14 @@
+//! Load arbitrary bytes with the default options, and again leniently
+
+#![no_main]
+
+use dbgparse::LoadOptions;
+use libfuzzer_sys::fuzz_target;
+
+#[path = "common.rs"]
+mod common;
+
+fuzz_target!(|data: &[u8]| {
+    common::load(data, &LoadOptions::default());
+    common::load(data, LoadOptions::default().lenient(true).partial(true));
+});
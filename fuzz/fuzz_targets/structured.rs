@@ -0,0 +1,105 @@
+//! Build a file with `DbgWriter` from arbitrary symbols, lines, and sections,
+//! flip some of its bytes, and load it with arbitrary options
+//!
+//! Arbitrary bytes rarely get past the header, files which start out valid
+//! reach the COFF symbols, line numbers, and function table.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use dbgparse::{DbgWriter, Header, LoadOptions, Machine, Section};
+use libfuzzer_sys::fuzz_target;
+
+#[path = "common.rs"]
+mod common;
+
+/// A section, as (name, RVA, size, characteristics)
+type SectionInput = (String, u32, u32, u32);
+
+/// Everything needed to build and load a file
+#[derive(Arbitrary, Debug)]
+struct Input {
+    /// `IMAGE_FILE_MACHINE_*` of the header
+    machine: u16,
+
+    /// Size of the image in the header
+    size_of_image: u32,
+
+    /// Sections of the image
+    sections: Vec<SectionInput>,
+
+    /// Names exported by the image
+    exported_names: Vec<String>,
+
+    /// File name of the image
+    image_name: Option<String>,
+
+    /// Functions, as (RVA, name, size)
+    functions: Vec<(u32, String, Option<u32>)>,
+
+    /// Globals, as (RVA, name)
+    globals: Vec<(u32, String)>,
+
+    /// Source lines, as (RVA, file name, line)
+    lines: Vec<(u32, String, u32)>,
+
+    /// Bytes to corrupt, as (offset modulo the file size, value to XOR in)
+    corruption: Vec<(u32, u8)>,
+
+    /// Bytes to cut off the end of the file
+    truncate: u16,
+
+    /// Options to load with
+    lenient: bool,
+    lazy:    bool,
+    partial: bool,
+    strict:  bool,
+}
+
+fuzz_target!(|input: Input| {
+    let mut writer = DbgWriter::new(Header {
+        machine:       Machine::from(input.machine),
+        size_of_image: input.size_of_image,
+        ..Default::default()
+    });
+    for (name, vaddr, vsize, characteristics) in input.sections {
+        writer.section(Section {
+            name,
+            vsize,
+            vaddr,
+            raw_data_sz: vsize,
+            ptr_raw_data: vaddr,
+            characteristics,
+        });
+    }
+    for name in &input.exported_names {
+        writer.exported_name(name);
+    }
+    writer.image_name = input.image_name;
+    for (rva, name, size) in &input.functions {
+        writer.function(*rva, name);
+        if let Some(size) = size {
+            writer.function_size(*rva, *size);
+        }
+    }
+    for (rva, name) in &input.globals {
+        writer.global(*rva, name);
+    }
+    for (rva, filename, line) in &input.lines {
+        writer.line(*rva, filename, *line);
+    }
+
+    let mut data = writer.to_bytes();
+    if !data.is_empty() {
+        let len = data.len();
+        for (offset, xor) in input.corruption {
+            data[offset as usize % len] ^= xor;
+        }
+    }
+    data.truncate(data.len().saturating_sub(input.truncate as usize));
+
+    let mut opts = LoadOptions::default();
+    opts.lenient(input.lenient).lazy(input.lazy).partial(input.partial)
+        .strict(input.strict);
+    common::load(&data, &opts);
+});
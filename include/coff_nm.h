@@ -0,0 +1,52 @@
+/* C API of dbgparse, built with `--features capi` */
+
+#ifndef COFF_NM_H
+#define COFF_NM_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Kinds passed to symbol callbacks */
+#define COFF_NM_FUNCTION 0
+#define COFF_NM_GLOBAL   1
+
+/* A loaded debug file */
+typedef struct CoffNm CoffNm;
+
+/* Callbacks for iteration, return false to stop */
+typedef bool (*coff_nm_symbol_cb)(void *ctx, uint32_t rva, uint32_t kind,
+    const char *name);
+typedef bool (*coff_nm_line_cb)(void *ctx, uint32_t rva, const char *filename,
+    uint32_t line);
+
+/* Load a debug file from a path or a buffer, NULL on failure */
+CoffNm *coff_nm_load(const char *path);
+CoffNm *coff_nm_load_buffer(const uint8_t *data, size_t len);
+
+uint32_t coff_nm_image_base(const CoffNm *handle);
+
+/* Find the closest symbol or source line at or before `rva`. Strings live as
+ * long as the handle. */
+bool coff_nm_lookup(const CoffNm *handle, uint32_t rva, const char **name,
+    uint32_t *offset);
+bool coff_nm_lookup_line(const CoffNm *handle, uint32_t rva,
+    const char **filename, uint32_t *line);
+
+/* Call `callback` on each symbol or source line in address order */
+void coff_nm_iter_symbols(const CoffNm *handle, coff_nm_symbol_cb callback,
+    void *ctx);
+void coff_nm_iter_lines(const CoffNm *handle, coff_nm_line_cb callback,
+    void *ctx);
+
+void coff_nm_free(CoffNm *handle);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
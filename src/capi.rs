@@ -0,0 +1,355 @@
+//! C API so debuggers and emulators written in C or C++ can link against the
+//! parser, declared in `include/coff_nm.h`
+//!
+//! A file is loaded into an opaque handle with [`coff_nm_load`] or
+//! [`coff_nm_load_buffer`], queried, then released with [`coff_nm_free`].
+//! Strings handed out are null terminated and live as long as the handle.
+
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufReader;
+use crate::{DbgFile, SymbolKind};
+
+/// Kind passed to symbol callbacks for functions
+pub const COFF_NM_FUNCTION: u32 = 0;
+
+/// Kind passed to symbol callbacks for globals
+pub const COFF_NM_GLOBAL: u32 = 1;
+
+/// Callback for each symbol, given the context pointer, RVA, kind, and name.
+/// Returning `false` stops the iteration.
+pub type SymbolCallback =
+    extern "C" fn(*mut c_void, u32, u32, *const c_char) -> bool;
+
+/// Callback for each source line, given the context pointer, RVA, filename,
+/// and line number. Returning `false` stops the iteration.
+pub type LineCallback =
+    extern "C" fn(*mut c_void, u32, *const c_char, u32) -> bool;
+
+/// A loaded debug file, opaque to C
+pub struct CoffNm {
+    /// Image base of the file
+    image_base: u32,
+
+    /// Functions and globals in address order as (RVA, kind, name), with the
+    /// function first if a function and a global share an address
+    symbols: Vec<(u32, u32, CString)>,
+
+    /// Source filenames, referenced by index from `lines`
+    filenames: Vec<CString>,
+
+    /// Source lines in address order as (RVA, filename index, line)
+    lines: Vec<(u32, usize, u32)>,
+}
+
+impl CoffNm {
+    /// Copy everything C can ask about out of `dbg`
+    fn new(dbg: &DbgFile) -> Self {
+        // Names come from null terminated strings, so they can't have nulls
+        // in them
+        let cstring = |x: &str| CString::new(x).unwrap_or_default();
+
+        let symbols = dbg.symbols_in_range(..).map(|(rva, kind, name)| {
+            let kind = match kind {
+                SymbolKind::Function => COFF_NM_FUNCTION,
+                SymbolKind::Global   => COFF_NM_GLOBAL,
+            };
+            (rva, kind, cstring(name))
+        }).collect();
+
+        let mut index = BTreeMap::new();
+        let mut filenames = Vec::new();
+        let lines = dbg.lines().iter().map(|(&rva, (filename, line))| {
+            let idx = *index.entry(filename.clone()).or_insert_with(|| {
+                filenames.push(cstring(filename));
+                filenames.len() - 1
+            });
+            (rva, idx, *line)
+        }).collect();
+
+        Self {
+            image_base: dbg.header().image_base,
+            symbols,
+            filenames,
+            lines,
+        }
+    }
+}
+
+/// Load the debug file at the null terminated UTF-8 `path`, returning null if
+/// it couldn't be opened or parsed
+///
+/// # Safety
+///
+/// `path` must be a valid null terminated string
+#[no_mangle]
+pub unsafe extern "C" fn coff_nm_load(path: *const c_char) -> *mut CoffNm {
+    if path.is_null() {
+        return std::ptr::null_mut();
+    }
+
+    let Ok(path) = CStr::from_ptr(path).to_str() else {
+        return std::ptr::null_mut();
+    };
+    File::open(path).ok()
+        .and_then(|fd| DbgFile::load(BufReader::new(fd)).ok())
+        .map(|dbg| Box::into_raw(Box::new(CoffNm::new(&dbg))))
+        .unwrap_or(std::ptr::null_mut())
+}
+
+/// Parse the debug file in the `len` bytes at `data`, returning null if it
+/// couldn't be parsed. The buffer isn't referenced after this returns.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes
+#[no_mangle]
+pub unsafe extern "C" fn coff_nm_load_buffer(data: *const u8, len: usize)
+        -> *mut CoffNm {
+    if data.is_null() {
+        return std::ptr::null_mut();
+    }
+
+    DbgFile::parse(std::slice::from_raw_parts(data, len)).ok()
+        .map(|dbg| Box::into_raw(Box::new(CoffNm::new(&dbg))))
+        .unwrap_or(std::ptr::null_mut())
+}
+
+/// Get the image base of a loaded file
+///
+/// # Safety
+///
+/// `handle` must be from [`coff_nm_load`] or [`coff_nm_load_buffer`] and not
+/// yet freed
+#[no_mangle]
+pub unsafe extern "C" fn coff_nm_image_base(handle: *const CoffNm) -> u32 {
+    (*handle).image_base
+}
+
+/// Find the closest function or global at or before `rva`
+///
+/// Returns `false` if there is none, otherwise sets `*name` to the name of
+/// the symbol and `*offset` to the offset of `rva` from its start. If a
+/// function and a global share an address, the function is preferred.
+///
+/// # Safety
+///
+/// `handle` must be from [`coff_nm_load`] or [`coff_nm_load_buffer`] and not
+/// yet freed, and `name` and `offset` must be valid to write to or null
+#[no_mangle]
+pub unsafe extern "C" fn coff_nm_lookup(handle: *const CoffNm, rva: u32,
+        name: *mut *const c_char, offset: *mut u32) -> bool {
+    let symbols = &(*handle).symbols;
+
+    // Find the last symbol at or before `rva`, then the first symbol at
+    // that address so functions win over globals
+    let Some(idx) = symbols.partition_point(|x| x.0 <= rva).checked_sub(1)
+    else {
+        return false;
+    };
+    let addr = symbols[idx].0;
+    let (addr, _, symbol) = &symbols[symbols.partition_point(|x| x.0 < addr)];
+
+    if !name.is_null() {
+        *name = symbol.as_ptr();
+    }
+    if !offset.is_null() {
+        *offset = rva - addr;
+    }
+    true
+}
+
+/// Find the source line at or before `rva`
+///
+/// Returns `false` if there is none, otherwise sets `*filename` and `*line`
+/// to where it is.
+///
+/// # Safety
+///
+/// `handle` must be from [`coff_nm_load`] or [`coff_nm_load_buffer`] and not
+/// yet freed, and `filename` and `line` must be valid to write to or null
+#[no_mangle]
+pub unsafe extern "C" fn coff_nm_lookup_line(handle: *const CoffNm, rva: u32,
+        filename: *mut *const c_char, line: *mut u32) -> bool {
+    let handle = &*handle;
+    let Some(idx) = handle.lines.partition_point(|x| x.0 <= rva)
+        .checked_sub(1) else {
+        return false;
+    };
+    let (_, source, number) = handle.lines[idx];
+
+    if !filename.is_null() {
+        *filename = handle.filenames[source].as_ptr();
+    }
+    if !line.is_null() {
+        *line = number;
+    }
+    true
+}
+
+/// Call `callback` with `ctx` on every function and global in address
+/// order, until it returns `false`
+///
+/// # Safety
+///
+/// `handle` must be from [`coff_nm_load`] or [`coff_nm_load_buffer`] and not
+/// yet freed
+#[no_mangle]
+pub unsafe extern "C" fn coff_nm_iter_symbols(handle: *const CoffNm,
+        callback: SymbolCallback, ctx: *mut c_void) {
+    for (rva, kind, name) in &(*handle).symbols {
+        if !callback(ctx, *rva, *kind, name.as_ptr()) {
+            break;
+        }
+    }
+}
+
+/// Call `callback` with `ctx` on every source line in address order, until
+/// it returns `false`
+///
+/// # Safety
+///
+/// `handle` must be from [`coff_nm_load`] or [`coff_nm_load_buffer`] and not
+/// yet freed
+#[no_mangle]
+pub unsafe extern "C" fn coff_nm_iter_lines(handle: *const CoffNm,
+        callback: LineCallback, ctx: *mut c_void) {
+    let handle = &*handle;
+    for (rva, source, line) in &handle.lines {
+        if !callback(ctx, *rva, handle.filenames[*source].as_ptr(), *line) {
+            break;
+        }
+    }
+}
+
+/// Free a loaded file, invalidating all strings handed out for it. Freeing
+/// null does nothing.
+///
+/// # Safety
+///
+/// `handle` must be null or from [`coff_nm_load`] or [`coff_nm_load_buffer`]
+/// and not yet freed
+#[no_mangle]
+pub unsafe extern "C" fn coff_nm_free(handle: *mut CoffNm) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DbgWriter, Header, Machine, Section};
+
+    /// Build a file with two functions, a global sharing the address of the
+    /// first, and lines from two source files
+    fn file() -> Vec<u8> {
+        let mut writer = DbgWriter::new(Header {
+            machine:    Machine::I386,
+            image_base: 0x400000,
+            ..Default::default()
+        });
+        writer.section(Section {
+            name:            ".text".into(),
+            vsize:           0x1000,
+            vaddr:           0x1000,
+            raw_data_sz:     0x1000,
+            ptr_raw_data:    0x400,
+            characteristics: 0x60000020,
+        });
+        writer.function(0x1000, "_main")
+            .function(0x1100, "_helper")
+            .global(0x1000, "_main_alias")
+            .line(0x1000, "main.c", 3)
+            .line(0x1100, "helper.c", 10);
+        writer.to_bytes()
+    }
+
+    /// Symbol callback collecting (RVA, kind, name) into the `Vec` at `ctx`
+    extern "C" fn collect(ctx: *mut c_void, rva: u32, kind: u32,
+            name: *const c_char) -> bool {
+        let symbols = unsafe { &mut *(ctx as *mut Vec<(u32, u32, String)>) };
+        let name = unsafe { CStr::from_ptr(name) };
+        symbols.push((rva, kind, name.to_str().unwrap().to_string()));
+        true
+    }
+
+    /// Line callback collecting (RVA, filename, line) into the `Vec` at
+    /// `ctx`, stopping after the first
+    extern "C" fn first_line(ctx: *mut c_void, rva: u32,
+            filename: *const c_char, line: u32) -> bool {
+        let lines = unsafe { &mut *(ctx as *mut Vec<(u32, String, u32)>) };
+        let filename = unsafe { CStr::from_ptr(filename) };
+        lines.push((rva, filename.to_str().unwrap().to_string(), line));
+        false
+    }
+
+    #[test]
+    fn lookups() {
+        let data = file();
+        unsafe {
+            let handle = coff_nm_load_buffer(data.as_ptr(), data.len());
+            assert!(!handle.is_null());
+            assert_eq!(coff_nm_image_base(handle), 0x400000);
+
+            // The function wins over the global at the same address
+            let mut name = std::ptr::null();
+            let mut offset = 0;
+            assert!(coff_nm_lookup(handle, 0x1010, &mut name, &mut offset));
+            assert_eq!(CStr::from_ptr(name).to_str(), Ok("_main"));
+            assert_eq!(offset, 0x10);
+            assert!(coff_nm_lookup(handle, 0x1100, &mut name,
+                std::ptr::null_mut()));
+            assert_eq!(CStr::from_ptr(name).to_str(), Ok("_helper"));
+            assert!(!coff_nm_lookup(handle, 0xfff, &mut name, &mut offset));
+
+            let mut filename = std::ptr::null();
+            let mut line = 0;
+            assert!(coff_nm_lookup_line(handle, 0x1104, &mut filename,
+                &mut line));
+            assert_eq!(CStr::from_ptr(filename).to_str(), Ok("helper.c"));
+            assert_eq!(line, 10);
+            assert!(!coff_nm_lookup_line(handle, 0xfff, &mut filename,
+                &mut line));
+
+            coff_nm_free(handle);
+        }
+    }
+
+    #[test]
+    fn iteration() {
+        let data = file();
+        unsafe {
+            let handle = coff_nm_load_buffer(data.as_ptr(), data.len());
+
+            let mut symbols: Vec<(u32, u32, String)> = Vec::new();
+            coff_nm_iter_symbols(handle, collect,
+                &mut symbols as *mut _ as *mut c_void);
+            assert_eq!(symbols, [
+                (0x1000, COFF_NM_FUNCTION, "_main".to_string()),
+                (0x1000, COFF_NM_GLOBAL, "_main_alias".to_string()),
+                (0x1100, COFF_NM_FUNCTION, "_helper".to_string()),
+            ]);
+
+            let mut lines: Vec<(u32, String, u32)> = Vec::new();
+            coff_nm_iter_lines(handle, first_line,
+                &mut lines as *mut _ as *mut c_void);
+            assert_eq!(lines, [(0x1000, "main.c".to_string(), 3)]);
+
+            coff_nm_free(handle);
+        }
+    }
+
+    #[test]
+    fn bad_input() {
+        unsafe {
+            assert!(coff_nm_load(std::ptr::null()).is_null());
+            assert!(coff_nm_load(c"/nonexistent/file.dbg".as_ptr())
+                .is_null());
+            assert!(coff_nm_load_buffer(std::ptr::null(), 0).is_null());
+            assert!(coff_nm_load_buffer(b"MZ".as_ptr(), 2).is_null());
+            coff_nm_free(std::ptr::null_mut());
+        }
+    }
+}
@@ -0,0 +1,338 @@
+//! Coverage reports, mapping a trace of hit addresses onto the functions and
+//! source lines of a debug file
+//!
+//! Traces are either text, one hex RVA per line, or `drcov` logs as written
+//! by DynamoRIO's `drcov` and compatible tracers.
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use crate::{DbgFile, Error, Result};
+
+/// A range of code which was executed, from a trace
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Hit {
+    /// RVA of the start of the range
+    pub rva: u32,
+
+    /// Size of the range in bytes, 1 for traces of single addresses
+    pub size: u32,
+}
+
+/// Parse the trace `data`, a `drcov` log or a text file of hex RVAs
+///
+/// Blank lines and lines starting with `#` are skipped in text traces. For
+/// `drcov` logs only the blocks of the module whose file name (without the
+/// extension) matches `module` case-insensitively are returned. The hits are
+/// sorted by address.
+pub fn parse_trace(data: &[u8], module: &str) -> Result<Vec<Hit>> {
+    let mut ret = if data.starts_with(b"DRCOV VERSION") {
+        parse_drcov(data, module)?
+    } else {
+        let text = String::from_utf8_lossy(data);
+        let mut ret = Vec::new();
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let line = line.strip_prefix("0x").unwrap_or(line);
+            let rva = u32::from_str_radix(line, 16)
+                .map_err(|_| Error::Trace(idx + 1, "bad address"))?;
+            ret.push(Hit { rva, size: 1 });
+        }
+        ret
+    };
+
+    ret.sort();
+    Ok(ret)
+}
+
+/// Check if the module `path` from a trace is `module`, comparing the file
+/// names without extensions
+fn same_module(path: &str, module: &str) -> bool {
+    let stem = |x: &str| {
+        let name = x.rsplit(['\\', '/']).next().unwrap_or(x);
+        name.split('.').next().unwrap_or(name).to_ascii_lowercase()
+    };
+    stem(path) == stem(module)
+}
+
+/// Parse a `drcov` log, keeping the blocks of `module`
+///
+/// The header is text: the version and flavor, the module table (a
+/// `Columns:` line naming the columns, then one line per module), and a
+/// `BB Table: <n> bbs` line. The blocks follow, either as binary
+/// `(u32 offset, u16 size, u16 module id)` entries or as text lines
+/// `module[ id]: 0xoffset, size`.
+fn parse_drcov(data: &[u8], module: &str) -> Result<Vec<Hit>> {
+    let mut ids = Vec::new();
+    let mut columns: Vec<String> = Vec::new();
+    let mut pos = 0;
+    let mut line_num = 0;
+
+    // Walk the text header a line at a time
+    let (blocks, text) = loop {
+        let end = data[pos..].iter().position(|&x| x == b'\n')
+            .ok_or(Error::Trace(line_num, "no BB table"))?;
+        let line = String::from_utf8_lossy(&data[pos..pos + end]);
+        let line = line.trim();
+        pos += end + 1;
+        line_num += 1;
+
+        // Version 1 logs have no `Columns:` line
+        if line.starts_with("Module Table:") {
+            columns = vec!["id".into(), "size".into(), "path".into()];
+        } else if let Some(names) = line.strip_prefix("Columns:") {
+            columns = names.split(',').map(|x| x.trim().to_string())
+                .collect();
+        } else if let Some(count) = line.strip_prefix("BB Table:") {
+            let count = count.split_whitespace().next().unwrap_or("")
+                .parse::<usize>()
+                .map_err(|_| Error::Trace(line_num, "bad BB count"))?;
+            break (count, data[pos..].starts_with(b"module["));
+        } else if !columns.is_empty() &&
+                line.splitn(columns.len(), ',').count() == columns.len() {
+            // A module, with its fields in the order of the columns. The
+            // path comes last and may have commas of its own.
+            let fields = line.splitn(columns.len(), ',').map(str::trim)
+                .collect::<Vec<_>>();
+            let field = |name: &str| {
+                columns.iter().position(|x| x == name).map(|x| fields[x])
+            };
+            let id = field("id").and_then(|x| x.parse::<u16>().ok())
+                .ok_or(Error::Trace(line_num, "bad module id"))?;
+            if field("path").is_some_and(|x| same_module(x, module)) {
+                ids.push(id);
+            }
+        }
+    };
+
+    let mut ret = Vec::new();
+    if text {
+        let rest = String::from_utf8_lossy(&data[pos..]);
+        for line in rest.lines().take(blocks) {
+            line_num += 1;
+            let err = || Error::Trace(line_num, "bad BB");
+            let (id, block) = line.strip_prefix("module[")
+                .and_then(|x| x.split_once("]:")).ok_or_else(err)?;
+            let (offset, size) = block.split_once(',').ok_or_else(err)?;
+            let id = id.trim().parse::<u16>().map_err(|_| err())?;
+            let offset = offset.trim();
+            let offset = u32::from_str_radix(
+                offset.strip_prefix("0x").unwrap_or(offset), 16)
+                .map_err(|_| err())?;
+            let size = size.trim().parse::<u32>().map_err(|_| err())?;
+            if ids.contains(&id) {
+                ret.push(Hit { rva: offset, size });
+            }
+        }
+    } else {
+        let table = blocks.checked_mul(8).and_then(|x| pos.checked_add(x))
+            .and_then(|end| data.get(pos..end))
+            .ok_or(Error::Trace(line_num, "BB table is truncated"))?;
+
+        // Unwraps are fine as the slice sizes are constant
+        for entry in table.chunks_exact(8) {
+            let rva  = u32::from_le_bytes(entry[0..4].try_into().unwrap());
+            let size = u16::from_le_bytes(entry[4..6].try_into().unwrap());
+            let id   = u16::from_le_bytes(entry[6..8].try_into().unwrap());
+            if ids.contains(&id) {
+                ret.push(Hit { rva, size: size as u32 });
+            }
+        }
+    }
+
+    Ok(ret)
+}
+
+/// Count the hits overlapping the range `start..end` of `hits`, which must be
+/// sorted
+fn count_hits(hits: &[Hit], max_size: u32, start: u32, end: u32) -> usize {
+    // Only hits starting at most `max_size` before the range can reach it
+    let first = hits.partition_point(|x| x.rva < start.saturating_sub(
+        max_size));
+    let last = hits.partition_point(|x| x.rva < end);
+    hits[first..last.max(first)].iter()
+        .filter(|x| x.rva as u64 + x.size as u64 > start as u64)
+        .count()
+}
+
+/// Count the hits in each function of `dbg`, keyed by the RVA of the
+/// function. `hits` must be sorted, as returned by [`parse_trace`].
+///
+/// A function covers its size as given by [`DbgFile::function_size`], or
+/// just its first byte if the size isn't known.
+pub fn function_hits(dbg: &DbgFile, hits: &[Hit]) -> BTreeMap<u32, usize> {
+    let max_size = hits.iter().map(|x| x.size).max().unwrap_or(0);
+    dbg.functions().keys().map(|&rva| {
+        let end = rva.saturating_add(dbg.function_size(rva).unwrap_or(1));
+        (rva, count_hits(hits, max_size, rva, end))
+    }).collect()
+}
+
+/// Count the hits in each source line of `dbg`, keyed by the RVA of the
+/// line. `hits` must be sorted, as returned by [`parse_trace`].
+///
+/// A line covers the code up to the next line or the end of its function,
+/// whichever comes first.
+pub fn line_hits(dbg: &DbgFile, hits: &[Hit]) -> BTreeMap<u32, usize> {
+    let max_size = hits.iter().map(|x| x.size).max().unwrap_or(0);
+    let mut lines = dbg.lines().keys().copied().peekable();
+    let mut ret = BTreeMap::new();
+    while let Some(rva) = lines.next() {
+        let next = lines.peek().copied().unwrap_or(u32::MAX);
+        let function_end = dbg.functions().range(..=rva).next_back()
+            .and_then(|(&start, _)| {
+                dbg.function_size(start).map(|x| start.saturating_add(x))
+            })
+            .filter(|&end| end > rva)
+            .unwrap_or(u32::MAX);
+        let end = next.min(function_end).max(rva.saturating_add(1));
+        ret.insert(rva, count_hits(hits, max_size, rva, end));
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DbgWriter, Header, Machine, Section};
+
+    /// Header of a version 2 `drcov` log of `ntoskrnl.exe` and a module with
+    /// a comma in its path, for `blocks` blocks
+    fn drcov_header(blocks: usize) -> Vec<u8> {
+        format!("DRCOV VERSION: 2
+DRCOV FLAVOR: drcov
+Module Table: version 2, count 2
+Columns: id, base, end, entry, checksum, timestamp, path
+ 0, 0x80100000, 0x80300000, 0x0000000000000000, 0x00000000, 0x00000000, \
+C:\\WINNT\\system32\\NTOSKRNL.EXE
+ 1, 0x80010000, 0x80030000, 0x0000000000000000, 0x00000000, 0x00000000, \
+C:\\a,b\\hal.dll
+BB Table: {} bbs
+", blocks).into_bytes()
+    }
+
+    /// Blocks of the logs as (offset, size, module id)
+    const BLOCKS: [(u32, u16, u16); 3] =
+        [(0x1010, 8, 0), (0x1100, 4, 1), (0x1000, 16, 0)];
+
+    #[test]
+    fn text_trace() {
+        let hits = parse_trace(b"0x1010\n\n# comment\n  1000\n", "nt")
+            .unwrap();
+        assert_eq!(hits, [Hit { rva: 0x1000, size: 1 },
+            Hit { rva: 0x1010, size: 1 }]);
+
+        assert!(matches!(parse_trace(b"1000\n\n1000g\n", "nt"),
+            Err(Error::Trace(3, "bad address"))));
+    }
+
+    #[test]
+    fn drcov_binary() {
+        let mut data = drcov_header(BLOCKS.len());
+        for (offset, size, id) in BLOCKS {
+            data.extend_from_slice(&offset.to_le_bytes());
+            data.extend_from_slice(&size.to_le_bytes());
+            data.extend_from_slice(&id.to_le_bytes());
+        }
+
+        let hits = parse_trace(&data, "ntoskrnl.dbg").unwrap();
+        assert_eq!(hits, [Hit { rva: 0x1000, size: 16 },
+            Hit { rva: 0x1010, size: 8 }]);
+        let hits = parse_trace(&data, "hal").unwrap();
+        assert_eq!(hits, [Hit { rva: 0x1100, size: 4 }]);
+
+        // The table is cut short
+        data.truncate(data.len() - 1);
+        assert!(matches!(parse_trace(&data, "ntoskrnl"),
+            Err(Error::Trace(7, "BB table is truncated"))));
+    }
+
+    /// Build a text `drcov` log of `BLOCKS` followed by `extra` blocks
+    fn drcov_text_log(extra: &[&str]) -> Vec<u8> {
+        let mut ret = drcov_header(BLOCKS.len() + extra.len());
+        for (offset, size, id) in BLOCKS {
+            ret.extend_from_slice(format!("module[{:3}]: {:#x}, {}\n", id,
+                offset, size).as_bytes());
+        }
+        for block in extra {
+            ret.extend_from_slice(block.as_bytes());
+            ret.push(b'\n');
+        }
+        ret
+    }
+
+    #[test]
+    fn drcov_text() {
+        let hits = parse_trace(&drcov_text_log(&[]), "NTOSKRNL").unwrap();
+        assert_eq!(hits, [Hit { rva: 0x1000, size: 16 },
+            Hit { rva: 0x1010, size: 8 }]);
+
+        let data = drcov_text_log(&["module[  0]: 0x1000"]);
+        assert!(matches!(parse_trace(&data, "ntoskrnl"),
+            Err(Error::Trace(11, "bad BB"))));
+    }
+
+    #[test]
+    fn drcov_version_1() {
+        // No `Columns:` line, modules are the id, size, and path
+        let mut data = b"DRCOV VERSION: 1
+DRCOV FLAVOR: drcov
+Module Table: 1
+ 0, 2097152, C:\\WINNT\\system32\\ntoskrnl.exe
+BB Table: 1 bbs
+".to_vec();
+        data.extend_from_slice(&[0x00, 0x10, 0, 0, 5, 0, 0, 0]);
+        assert_eq!(parse_trace(&data, "ntoskrnl").unwrap(),
+            [Hit { rva: 0x1000, size: 5 }]);
+
+        assert!(matches!(parse_trace(b"DRCOV VERSION: 2\n", "ntoskrnl"),
+            Err(Error::Trace(1, "no BB table"))));
+    }
+
+    #[test]
+    fn hits() {
+        let mut writer = DbgWriter::new(Header {
+            machine: Machine::I386,
+            ..Default::default()
+        });
+        writer.section(Section {
+            name:            ".text".into(),
+            vsize:           0x1000,
+            vaddr:           0x1000,
+            raw_data_sz:     0x1000,
+            ptr_raw_data:    0x400,
+            characteristics: 0x60000020,
+        });
+        writer.function(0x1000, "_main")
+            .function(0x1100, "_helper")
+            .function_size(0x1000, 0x40)
+            .line(0x1000, "main.c", 3)
+            .line(0x1010, "main.c", 4)
+            .line(0x1100, "helper.c", 10);
+        let dbg = DbgFile::parse(&writer.to_bytes()).unwrap();
+
+        // The last hit is past the end of `_main`, and `_helper` has no
+        // size so only covers its first byte
+        let hits = parse_trace(b"1004\n1012\n1100\n1101\n1040\n", "nt")
+            .unwrap();
+        let functions = function_hits(&dbg, &hits).into_iter()
+            .collect::<Vec<_>>();
+        assert_eq!(functions, [(0x1000, 2), (0x1100, 1)]);
+
+        // The second line ends with `_main`, but the last one goes on as the
+        // size of `_helper` isn't known
+        let lines = line_hits(&dbg, &hits).into_iter().collect::<Vec<_>>();
+        assert_eq!(lines, [(0x1000, 1), (0x1010, 1), (0x1100, 2)]);
+
+        // A block counts for every function and line it overlaps
+        let blocks = [Hit { rva: 0x1008, size: 0x100 }];
+        assert_eq!(function_hits(&dbg, &blocks)[&0x1100], 1);
+        assert_eq!(line_hits(&dbg, &blocks)[&0x1000], 1);
+        assert_eq!(line_hits(&dbg, &blocks)[&0x1010], 1);
+    }
+}
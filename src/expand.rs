@@ -0,0 +1,269 @@
+//! Decompression of files compressed with the legacy `compress.exe` tool, as
+//! unpacked by `expand.exe`. Symbol files on old CDs are often shipped like
+//! this, eg. `ntdll.db_`.
+
+use alloc::vec::Vec;
+use crate::{Error, Result};
+
+/// Magic of an SZDD file
+const SZDD_MAGIC: &[u8; 8] = b"SZDD\x88\xf0\x27\x33";
+
+/// Magic of a KWAJ file
+const KWAJ_MAGIC: &[u8; 8] = b"KWAJ\x88\xf0\x27\xd1";
+
+/// Size of the LZSS sliding window
+const WINDOW_SIZE: usize = 4096;
+
+/// Check if `data` starts with the magic of an SZDD or KWAJ file
+pub fn is_compressed(data: &[u8]) -> bool {
+    data.starts_with(SZDD_MAGIC) || data.starts_with(KWAJ_MAGIC)
+}
+
+/// Decompress the SZDD or KWAJ file `data`
+///
+/// KWAJ files may be stored, XORed, or LZSS compressed. The LZ+Huffman and
+/// MSZIP methods are reported as [`Error::Expand`].
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>> {
+    if data.starts_with(SZDD_MAGIC) {
+        decompress_szdd(data)
+    } else if data.starts_with(KWAJ_MAGIC) {
+        decompress_kwaj(data)
+    } else {
+        Err(Error::Expand("not an SZDD or KWAJ file"))
+    }
+}
+
+/// Read a little endian `u16` at `offset` of `data`
+fn read_u16(data: &[u8], offset: usize) -> Result<u16> {
+    // Unwrap is fine as the slice size is checked by `get`
+    data.get(offset..offset + 2)
+        .map(|x| u16::from_le_bytes(x.try_into().unwrap()))
+        .ok_or(Error::Expand("header is truncated"))
+}
+
+/// Read a little endian `u32` at `offset` of `data`
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    // Unwrap is fine as the slice size is checked by `get`
+    data.get(offset..offset + 4)
+        .map(|x| u32::from_le_bytes(x.try_into().unwrap()))
+        .ok_or(Error::Expand("header is truncated"))
+}
+
+/// Decompress an SZDD file
+///
+/// The header is the magic, the compression mode (always `A`), the last
+/// character of the original file name which was replaced by `_`, and the
+/// size of the uncompressed data
+fn decompress_szdd(data: &[u8]) -> Result<Vec<u8>> {
+    if data.get(8) != Some(&b'A') {
+        return Err(Error::Expand("unknown SZDD compression mode"));
+    }
+    let size = read_u32(data, 10)?;
+
+    lzss(&data[14..], Some(size as usize))
+}
+
+/// Decompress a KWAJ file
+///
+/// The header is the magic, the compression method, the offset of the
+/// compressed data, and flags saying which optional headers follow. The
+/// only optional header we care about is the uncompressed size.
+fn decompress_kwaj(data: &[u8]) -> Result<Vec<u8>> {
+    let method      = read_u16(data, 8)?;
+    let data_offset = read_u16(data, 10)? as usize;
+    let flags       = read_u16(data, 12)?;
+
+    // The uncompressed size comes first if it's there at all
+    let size = if flags & 1 != 0 {
+        Some(read_u32(data, 14)? as usize)
+    } else {
+        None
+    };
+
+    let compressed = data.get(data_offset..)
+        .ok_or(Error::Expand("data offset is past the end of the file"))?;
+    let mut ret = match method {
+        0 => compressed.to_vec(),
+        1 => compressed.iter().map(|x| x ^ 0xff).collect(),
+        2 => lzss(compressed, size)?,
+        3 => return Err(Error::Expand("KWAJ LZ+Huffman is not supported")),
+        4 => return Err(Error::Expand("KWAJ MSZIP is not supported")),
+        _ => return Err(Error::Expand("unknown KWAJ compression method")),
+    };
+
+    if let Some(size) = size {
+        ret.truncate(size);
+    }
+    Ok(ret)
+}
+
+/// Decompress the LZSS stream `data`, stopping at `size` bytes if given
+///
+/// Each control byte is followed by 8 items, one for each bit starting from
+/// the lowest. A set bit is a literal byte, a clear bit is a 12-bit window
+/// position and a 4-bit length (minus 3) to copy from the window. The window
+/// starts out filled with spaces.
+fn lzss(mut data: &[u8], size: Option<usize>) -> Result<Vec<u8>> {
+    let mut window = [b' '; WINDOW_SIZE];
+    let mut pos = WINDOW_SIZE - 16;
+
+    // Don't trust the size for the allocation, LZSS can't expand by more
+    // than 9 times
+    let limit = size.unwrap_or(usize::MAX);
+    let mut ret = Vec::with_capacity(limit.min(data.len().saturating_mul(9)));
+
+    while let Some((&control, rest)) = data.split_first() {
+        data = rest;
+        for bit in 0..8 {
+            // A copy from the window may have gone past the end
+            if ret.len() >= limit {
+                ret.truncate(limit);
+                return Ok(ret);
+            }
+
+            if control & (1 << bit) != 0 {
+                // Literal byte
+                let Some((&byte, rest)) = data.split_first() else {
+                    break;
+                };
+                data = rest;
+
+                ret.push(byte);
+                window[pos] = byte;
+                pos = (pos + 1) % WINDOW_SIZE;
+            } else {
+                // Copy from the window
+                let [lo, hi, rest @ ..] = data else { break; };
+                data = rest;
+
+                let mut from = *lo as usize | (*hi as usize & 0xf0) << 4;
+                let len = (*hi as usize & 0x0f) + 3;
+                for _ in 0..len {
+                    let byte = window[from];
+                    ret.push(byte);
+                    window[pos] = byte;
+                    pos  = (pos  + 1) % WINDOW_SIZE;
+                    from = (from + 1) % WINDOW_SIZE;
+                }
+            }
+        }
+    }
+
+    if ret.len() < size.unwrap_or(0) {
+        return Err(Error::Expand("compressed data is truncated"));
+    }
+    ret.truncate(limit);
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    /// Compress `data` as LZSS without using the window, all literals
+    fn literals(data: &[u8]) -> Vec<u8> {
+        let mut ret = Vec::new();
+        for chunk in data.chunks(8) {
+            ret.push(0xff);
+            ret.extend_from_slice(chunk);
+        }
+        ret
+    }
+
+    /// Build an SZDD file of the uncompressed `size` and LZSS `compressed`
+    fn szdd(size: u32, compressed: &[u8]) -> Vec<u8> {
+        let mut ret = SZDD_MAGIC.to_vec();
+        ret.extend_from_slice(b"A_");
+        ret.extend_from_slice(&size.to_le_bytes());
+        ret.extend_from_slice(compressed);
+        ret
+    }
+
+    /// Build a KWAJ file of `method` and `data`, with the uncompressed
+    /// `size` if given
+    fn kwaj(method: u16, size: Option<u32>, data: &[u8]) -> Vec<u8> {
+        let offset = if size.is_some() { 18u16 } else { 14 };
+        let mut ret = KWAJ_MAGIC.to_vec();
+        ret.extend_from_slice(&method.to_le_bytes());
+        ret.extend_from_slice(&offset.to_le_bytes());
+        ret.extend_from_slice(&(size.is_some() as u16).to_le_bytes());
+        if let Some(size) = size {
+            ret.extend_from_slice(&size.to_le_bytes());
+        }
+        ret.extend_from_slice(data);
+        ret
+    }
+
+    #[test]
+    fn szdd_literals() {
+        let data = b"DI\0\0 ntoskrnl.dbg, uncompressed";
+        let file = szdd(data.len() as u32, &literals(data));
+        assert!(is_compressed(&file));
+        assert_eq!(decompress(&file).unwrap(), data);
+    }
+
+    #[test]
+    fn szdd_window() {
+        // `abc`, then 6 bytes from the window where it was written, then 3
+        // of the spaces the window starts out with
+        let compressed = [0x07, b'a', b'b', b'c', 0xf0, 0xf3, 0x00, 0x00];
+        let file = szdd(12, &compressed);
+        assert_eq!(decompress(&file).unwrap(), b"abcabcabc   ");
+
+        // The size stops the output in the middle of a copy
+        assert_eq!(decompress(&szdd(5, &compressed)).unwrap(), b"abcab");
+    }
+
+    #[test]
+    fn truncated() {
+        let data = b"hello world";
+        let compressed = literals(data);
+        let file = szdd(data.len() as u32, &compressed[..6]);
+        assert!(matches!(decompress(&file),
+            Err(Error::Expand("compressed data is truncated"))));
+        assert!(matches!(decompress(&file[..12]),
+            Err(Error::Expand("header is truncated"))));
+        assert!(matches!(decompress(&kwaj(2, Some(4), b"")[..16]),
+            Err(Error::Expand("header is truncated"))));
+
+        let mut file = szdd(0, b"");
+        file[8] = b'B';
+        assert!(matches!(decompress(&file),
+            Err(Error::Expand("unknown SZDD compression mode"))));
+    }
+
+    #[test]
+    fn kwaj_methods() {
+        let data = b"DI\0\0 hal.dbg";
+        assert_eq!(decompress(&kwaj(0, None, data)).unwrap(), data);
+
+        let xored = data.iter().map(|x| x ^ 0xff).collect::<Vec<_>>();
+        assert_eq!(decompress(&kwaj(1, None, &xored)).unwrap(), data);
+
+        let lzss = literals(data);
+        assert_eq!(decompress(&kwaj(2, None, &lzss)).unwrap(), data);
+        assert_eq!(decompress(&kwaj(2, Some(4), &lzss)).unwrap(), b"DI\0\0");
+
+        // The size also cuts short stored data
+        assert_eq!(decompress(&kwaj(0, Some(2), data)).unwrap(), b"DI");
+
+        assert!(matches!(decompress(&kwaj(3, None, data)),
+            Err(Error::Expand("KWAJ LZ+Huffman is not supported"))));
+        assert!(matches!(decompress(&kwaj(9, None, data)),
+            Err(Error::Expand("unknown KWAJ compression method"))));
+
+        let mut file = kwaj(0, None, b"");
+        file[10] = 0x40;
+        assert!(matches!(decompress(&file),
+            Err(Error::Expand("data offset is past the end of the file"))));
+    }
+
+    #[test]
+    fn not_compressed() {
+        let data = vec![0u8; 32];
+        assert!(!is_compressed(&data));
+        assert!(matches!(decompress(&data),
+            Err(Error::Expand("not an SZDD or KWAJ file"))));
+    }
+}
@@ -0,0 +1,183 @@
+//! Approximate memory used by a parsed file, for sizing processes which keep
+//! hundreds of modules loaded at once
+//!
+//! Sizes are estimates of the heap memory of each table. Maps are assumed to
+//! be about two thirds full, and strings and vectors to use their whole
+//! capacity. Allocator overhead isn't counted.
+
+use core::mem::size_of;
+use core::ops::AddAssign;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use crate::{DbgFile, SymbolKind};
+
+/// Number of entries in a table and the approximate memory they use
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TableFootprint {
+    /// Number of entries
+    pub entries: usize,
+
+    /// Approximate bytes of heap memory used by the entries, including the
+    /// strings they own
+    pub bytes: usize,
+}
+
+impl AddAssign for TableFootprint {
+    fn add_assign(&mut self, other: Self) {
+        self.entries += other.entries;
+        self.bytes += other.bytes;
+    }
+}
+
+/// Approximate memory used by each table of a [`DbgFile`], see
+/// [`DbgFile::memory_footprint`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryFootprint {
+    /// Function names by RVA
+    pub functions: TableFootprint,
+
+    /// Global names by RVA
+    pub globals: TableFootprint,
+
+    /// Source lines by RVA, not counting the file names they share
+    pub lines: TableFootprint,
+
+    /// Source file names, each stored once however many lines refer to it
+    pub filenames: TableFootprint,
+
+    /// Aliases (weak externals) and the names they resolve to
+    pub aliases: TableFootprint,
+
+    /// Function extents from `.bf` and `.ef` records
+    pub function_info: TableFootprint,
+
+    /// Function extents from the exception information
+    pub exception_ranges: TableFootprint,
+
+    /// Sections, exported names, debug directories, and diagnostics
+    pub other: TableFootprint,
+
+    /// Raw COFF debug information held by a lazy load until it's parsed
+    pub pending: TableFootprint,
+
+    /// Index of symbols by name, built by the first
+    /// [`DbgFile::find_symbol`]
+    pub name_index: TableFootprint,
+}
+
+impl MemoryFootprint {
+    /// Get the name and footprint of each table, in the order of the fields
+    pub fn tables(&self) -> [(&'static str, TableFootprint); 10] {
+        [
+            ("functions",        self.functions),
+            ("globals",          self.globals),
+            ("lines",            self.lines),
+            ("filenames",        self.filenames),
+            ("aliases",          self.aliases),
+            ("function_info",    self.function_info),
+            ("exception_ranges", self.exception_ranges),
+            ("other",            self.other),
+            ("pending",          self.pending),
+            ("name_index",       self.name_index),
+        ]
+    }
+
+    /// Get the approximate bytes used by every table together
+    pub fn total(&self) -> usize {
+        self.tables().iter().map(|(_, x)| x.bytes).sum()
+    }
+}
+
+impl AddAssign<&MemoryFootprint> for MemoryFootprint {
+    fn add_assign(&mut self, other: &Self) {
+        self.functions        += other.functions;
+        self.globals          += other.globals;
+        self.lines            += other.lines;
+        self.filenames        += other.filenames;
+        self.aliases          += other.aliases;
+        self.function_info    += other.function_info;
+        self.exception_ranges += other.exception_ranges;
+        self.other            += other.other;
+        self.pending          += other.pending;
+        self.name_index       += other.name_index;
+    }
+}
+
+/// Get the footprint of the map `map`, adding `owned` bytes for what each
+/// entry owns, eg. the contents of a `String`
+fn map<K, V>(map: &BTreeMap<K, V>, owned: impl Fn(&K, &V) -> usize)
+        -> TableFootprint {
+    TableFootprint {
+        entries: map.len(),
+        bytes:   map.len() * (size_of::<K>() + size_of::<V>()) * 3 / 2 +
+            map.iter().map(|(k, v)| owned(k, v)).sum::<usize>(),
+    }
+}
+
+/// Get the footprint of the vector `vec`, adding `owned` bytes for what each
+/// element owns
+fn vec<T>(vec: &Vec<T>, owned: impl Fn(&T) -> usize) -> TableFootprint {
+    TableFootprint {
+        entries: vec.len(),
+        bytes:   vec.capacity() * size_of::<T>() +
+            vec.iter().map(owned).sum::<usize>(),
+    }
+}
+
+/// Get the bytes owned by `string`
+fn string(string: &String) -> usize {
+    string.capacity()
+}
+
+impl DbgFile {
+    /// Estimate the memory used by each table of the file
+    ///
+    /// This doesn't parse the symbols of a lazy load, whose raw data is
+    /// counted as `pending` until something else parses them.
+    pub fn memory_footprint(&self) -> MemoryFootprint {
+        let mut ret = MemoryFootprint {
+            exception_ranges: map(&self.exception_ranges, |_, _| 0),
+            pending: vec(&self.pending_coff, |(_, _, data)| data.capacity()),
+            ..Default::default()
+        };
+
+        ret.other += vec(&self.sections, |x| string(&x.name));
+        ret.other += vec(&self.exported_names, string);
+        ret.other += vec(&self.debug_directories, |_| 0);
+        ret.other += vec(&self.load_diagnostics, |_| 0);
+        let names = [&self.image_name, &self.module_name];
+        for name in names.into_iter().flatten() {
+            ret.other.bytes += string(name);
+        }
+
+        if let Some(coff) = self.coff.get() {
+            let tables = &coff.tables;
+            ret.functions = map(&tables.functions, |_, x| string(x));
+            ret.globals = map(&tables.globals, |_, x| string(x));
+            ret.lines = map(&tables.addr_to_line, |_, _| 0);
+            ret.filenames = TableFootprint {
+                entries: tables.filenames.len(),
+                bytes:   tables.filenames.iter().map(|x| {
+                    size_of::<Arc<str>>() * 3 / 2 + x.len() +
+                        2 * size_of::<usize>()
+                }).sum(),
+            };
+            ret.aliases = map(&coff.aliases, |alias, (target, _)| {
+                string(alias) + string(target)
+            });
+            ret.function_info = map(&coff.function_info, |_, _| 0);
+            ret.other += vec(&coff.diagnostics, |_| 0);
+            ret.other += map(&tables.origins, |_, _| 0);
+        }
+
+        if let Some(index) = self.name_to_addr.get() {
+            ret.name_index = map(index, |name, rvas| {
+                string(name) + rvas.capacity() * size_of::<(u32, SymbolKind)>()
+            });
+        }
+
+        ret
+    }
+}
@@ -0,0 +1,218 @@
+//! The parts of `std::io` used by the parser, so it can be built without
+//! `std`
+//!
+//! With the `std` feature these are just the ones from `std::io`. Without it
+//! they're small stand-ins with the same names and behavior, and the only
+//! reader is a [`Cursor`] over bytes in memory, which is all that parsing
+//! from a slice needs.
+
+#[cfg(feature = "std")]
+pub use std::io::{Cursor, Error, ErrorKind, Read, Result, Seek, SeekFrom};
+
+#[cfg(not(feature = "std"))]
+pub use self::core_io::*;
+
+#[cfg(not(feature = "std"))]
+mod core_io {
+    use alloc::vec::Vec;
+
+    /// Kind of an [`Error`], the subset of the `std` kinds a [`Cursor`] can
+    /// report
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ErrorKind {
+        /// A read needed more bytes than were left
+        UnexpectedEof,
+
+        /// A seek went before the start of the data
+        InvalidInput,
+    }
+
+    /// An I/O error, given as its kind and a description
+    #[derive(Debug)]
+    pub struct Error {
+        /// Kind of the error
+        kind: ErrorKind,
+
+        /// What went wrong
+        message: &'static str,
+    }
+
+    impl Error {
+        /// Create an error of `kind` described by `message`
+        pub fn new(kind: ErrorKind, message: &'static str) -> Self {
+            Self { kind, message }
+        }
+
+        /// Get the kind of the error
+        pub fn kind(&self) -> ErrorKind {
+            self.kind
+        }
+    }
+
+    impl core::fmt::Display for Error {
+        fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+            f.write_str(self.message)
+        }
+    }
+
+    impl core::error::Error for Error {}
+
+    /// Wrapper type for `Result` with an I/O [`Error`]
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    /// Position to seek to, relative to the start, end, or current position
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum SeekFrom {
+        /// Offset from the start
+        Start(u64),
+
+        /// Offset from the end
+        End(i64),
+
+        /// Offset from the current position
+        Current(i64),
+    }
+
+    /// Source of bytes, as `std::io::Read`
+    pub trait Read {
+        /// Read into `buf`, returning the number of bytes read, 0 at the end
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+        /// Fill all of `buf`, failing with [`ErrorKind::UnexpectedEof`] if
+        /// the bytes run out first
+        fn read_exact(&mut self, mut buf: &mut [u8]) -> Result<()> {
+            while !buf.is_empty() {
+                match self.read(buf)? {
+                    0 => return Err(Error::new(ErrorKind::UnexpectedEof,
+                        "failed to fill whole buffer")),
+                    read => buf = &mut buf[read..],
+                }
+            }
+            Ok(())
+        }
+
+        /// Append everything up to the end to `buf`, returning the number of
+        /// bytes read
+        fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
+            let start = buf.len();
+            let mut chunk = [0u8; 4096];
+            loop {
+                match self.read(&mut chunk)? {
+                    0 => return Ok(buf.len() - start),
+                    read => buf.extend_from_slice(&chunk[..read]),
+                }
+            }
+        }
+
+        /// Borrow the reader, to use it through adapters without giving it up
+        fn by_ref(&mut self) -> &mut Self where Self: Sized {
+            self
+        }
+
+        /// Create an adapter reading at most `limit` bytes
+        fn take(self, limit: u64) -> Take<Self> where Self: Sized {
+            Take { inner: self, limit }
+        }
+    }
+
+    /// Something with a position which can be moved, as `std::io::Seek`
+    pub trait Seek {
+        /// Move to `pos`, returning the new offset from the start
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64>;
+
+        /// Get the offset from the start
+        fn stream_position(&mut self) -> Result<u64> {
+            self.seek(SeekFrom::Current(0))
+        }
+    }
+
+    impl<R: Read + ?Sized> Read for &mut R {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            (**self).read(buf)
+        }
+    }
+
+    impl<S: Seek + ?Sized> Seek for &mut S {
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+            (**self).seek(pos)
+        }
+    }
+
+    /// Reader of at most a fixed number of bytes, see [`Read::take`]
+    pub struct Take<R> {
+        /// Reader being limited
+        inner: R,
+
+        /// Number of bytes left to read
+        limit: u64,
+    }
+
+    impl<R: Read> Read for Take<R> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let max = usize::try_from(self.limit).unwrap_or(usize::MAX)
+                .min(buf.len());
+            let read = self.inner.read(&mut buf[..max])?;
+            self.limit -= read as u64;
+            Ok(read)
+        }
+    }
+
+    /// Reader over bytes in memory, as `std::io::Cursor`
+    #[derive(Debug, Clone, Default)]
+    pub struct Cursor<T> {
+        /// Bytes being read
+        inner: T,
+
+        /// Offset of the next byte to read, may be past the end
+        pos: u64,
+    }
+
+    impl<T> Cursor<T> {
+        /// Create a reader starting at the first byte of `inner`
+        pub fn new(inner: T) -> Self {
+            Self { inner, pos: 0 }
+        }
+
+        /// Get the offset of the next byte to read
+        pub fn position(&self) -> u64 {
+            self.pos
+        }
+
+        /// Get the bytes being read
+        pub fn get_ref(&self) -> &T {
+            &self.inner
+        }
+
+        /// Get back the bytes being read
+        pub fn into_inner(self) -> T {
+            self.inner
+        }
+    }
+
+    impl<T: AsRef<[u8]>> Read for Cursor<T> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let data = self.inner.as_ref();
+            let start = usize::try_from(self.pos).unwrap_or(usize::MAX)
+                .min(data.len());
+            let read = buf.len().min(data.len() - start);
+            buf[..read].copy_from_slice(&data[start..start + read]);
+            self.pos += read as u64;
+            Ok(read)
+        }
+    }
+
+    impl<T: AsRef<[u8]>> Seek for Cursor<T> {
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+            let (base, offset) = match pos {
+                SeekFrom::Start(offset) => (offset, 0),
+                SeekFrom::End(offset) =>
+                    (self.inner.as_ref().len() as u64, offset),
+                SeekFrom::Current(offset) => (self.pos, offset),
+            };
+            self.pos = base.checked_add_signed(offset).ok_or(Error::new(
+                ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position"))?;
+            Ok(self.pos)
+        }
+    }
+}
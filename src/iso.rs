@@ -0,0 +1,343 @@
+//! Reading files out of ISO9660 CD images, so archived NT CDs can be
+//! processed without mounting them
+
+use alloc::collections::BTreeSet;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use crate::io::{Read, Seek, SeekFrom};
+use crate::{check_size, read_field, Diagnostic, Error, Result};
+
+/// Size of a logical sector
+const SECTOR_SIZE: u64 = 2048;
+
+/// Sector of the first volume descriptor, everything before it is reserved
+const FIRST_DESCRIPTOR: u64 = 16;
+
+/// Directory nesting limit. ISO9660 only allows 8 levels, but Joliet and
+/// Rock Ridge images can go deeper, so this just stops runaway nesting.
+const MAX_DEPTH: usize = 64;
+
+/// A file in an ISO9660 image
+#[derive(Debug, Clone)]
+pub struct IsoFile {
+    /// Path of the file from the root of the image, with `/` separators and
+    /// without the `;1` version suffix
+    pub path: String,
+
+    /// Size of the file in bytes
+    pub size: u32,
+
+    /// Sector the contents of the file start at
+    extent: u32,
+}
+
+/// An ISO9660 image, using the names from the primary volume descriptor
+pub struct IsoImage<R> {
+    /// Reader for the image
+    reader: R,
+
+    /// Every file in the image, in directory order
+    files: Vec<IsoFile>,
+
+    /// Directories which were skipped over
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Check if `reader` holds an ISO9660 image, by the `CD001` magic of its
+/// first volume descriptor. The position of `reader` is left unspecified.
+pub fn is_iso(reader: &mut (impl Read + Seek)) -> bool {
+    let mut magic = [0u8; 6];
+    reader.seek(SeekFrom::Start(FIRST_DESCRIPTOR * SECTOR_SIZE)).is_ok() &&
+        reader.read_exact(&mut magic).is_ok() &&
+        &magic[1..] == b"CD001"
+}
+
+impl<R: Read + Seek> IsoImage<R> {
+    /// Open the ISO9660 image in `reader` and list all files in it
+    pub fn new(mut reader: R) -> Result<Self> {
+        // Find the primary volume descriptor, stopping at the terminator
+        let mut sector = FIRST_DESCRIPTOR;
+        let root = loop {
+            let descriptor = read_sector(&mut reader, sector)?;
+            if &descriptor[1..6] != b"CD001" {
+                return Err(Error::Iso("bad volume descriptor"));
+            }
+
+            match descriptor[0] {
+                // The root directory record is at offset 156
+                1 => break DirRecord::parse(&descriptor[156..])
+                    .ok_or(Error::Iso("bad root directory record"))?,
+                255 => return Err(Error::Iso("no primary volume descriptor")),
+                _ => sector += 1,
+            }
+        };
+
+        let mut ret = Self {
+            reader,
+            files:       Vec::new(),
+            diagnostics: Vec::new(),
+        };
+        let mut visited = BTreeSet::new();
+        ret.walk(&root, "", 0, &mut visited)?;
+        Ok(ret)
+    }
+
+    /// Get every file in the image, in directory order
+    pub fn files(&self) -> &[IsoFile] {
+        &self.files
+    }
+
+    /// Get the problems with directories which were skipped over, such as
+    /// ones nested too deeply or listed more than once
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Read the contents of `file`
+    pub fn read(&mut self, file: &IsoFile) -> Result<Vec<u8>> {
+        let offset = file.extent as u64 * SECTOR_SIZE;
+        self.reader.seek(SeekFrom::Start(offset))
+            .map_err(|x| Error::Consume("file", offset, x))?;
+        check_size(&mut self.reader, "file size", file.size as u64,
+            u64::MAX)?;
+
+        let mut data = vec![0u8; file.size as usize];
+        read_field(&mut self.reader, &mut data, "file")?;
+        Ok(data)
+    }
+
+    /// Add the files in the directory `dir`, found at `path`, and everything
+    /// below it to `files`. `visited` holds the extents of the directories
+    /// walked so far.
+    fn walk(&mut self, dir: &DirRecord, path: &str, depth: usize,
+            visited: &mut BTreeSet<u32>) -> Result<()> {
+        let offset = dir.extent as u64 * SECTOR_SIZE;
+        if depth > MAX_DEPTH {
+            self.diagnostics.push(Diagnostic {
+                offset,
+                error: Error::Iso("directory nested too deeply, skipped"),
+            });
+            return Ok(());
+        }
+
+        // Each directory is only walked once. A corrupt image may link a
+        // directory back to one of its parents, or a crafted one link the
+        // same directory from many places to blow up the walk.
+        if !visited.insert(dir.extent) {
+            self.diagnostics.push(Diagnostic {
+                offset,
+                error: Error::Iso("directory listed more than once, skipped"),
+            });
+            return Ok(());
+        }
+
+        // Read the whole directory
+        self.reader.seek(SeekFrom::Start(offset))
+            .map_err(|x| Error::Consume("directory", offset, x))?;
+        check_size(&mut self.reader, "directory size", dir.size as u64,
+            u64::MAX)?;
+        let mut data = vec![0u8; dir.size as usize];
+        read_field(&mut self.reader, &mut data, "directory")?;
+
+        // Records don't cross sectors, the rest of a sector is zero padding
+        let mut entries = Vec::new();
+        for sector in data.chunks(SECTOR_SIZE as usize) {
+            let mut records = sector;
+            while let Some(record) = DirRecord::parse(records) {
+                records = &records[record.len..];
+
+                // Skip the `.` and `..` entries
+                if record.name != "\0" && record.name != "\x01" {
+                    entries.push(record);
+                }
+            }
+        }
+
+        for entry in entries {
+            let path = if path.is_empty() {
+                entry.name.clone()
+            } else {
+                format!("{}/{}", path, entry.name)
+            };
+
+            if entry.is_dir {
+                self.walk(&entry, &path, depth + 1, visited)?;
+            } else {
+                self.files.push(IsoFile {
+                    path,
+                    size:   entry.size,
+                    extent: entry.extent,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A directory record, used internally
+struct DirRecord {
+    /// Length of the record in bytes
+    len: usize,
+
+    /// Sector the contents start at
+    extent: u32,
+
+    /// Size of the contents in bytes
+    size: u32,
+
+    /// Set if this is a directory
+    is_dir: bool,
+
+    /// Name, without the `;1` version suffix or a trailing `.`
+    name: String,
+}
+
+impl DirRecord {
+    /// Parse the directory record at the start of `data`, returning `None`
+    /// if there isn't a valid one
+    fn parse(data: &[u8]) -> Option<Self> {
+        let len = *data.first()? as usize;
+        let record = data.get(..len).filter(|_| len >= 33)?;
+        let name_len = record[32] as usize;
+        let name = record.get(33..33 + name_len)?;
+
+        // Unwraps are fine as the slice sizes are constant
+        let extent = u32::from_le_bytes(record[2..6].try_into().unwrap());
+        let size   = u32::from_le_bytes(record[10..14].try_into().unwrap());
+
+        let name = String::from_utf8_lossy(name);
+        let name = name.split(';').next().unwrap_or_default();
+        let name = name.strip_suffix('.').unwrap_or(name);
+        Some(Self {
+            len,
+            extent,
+            size,
+            is_dir: record[25] & 2 != 0,
+            name:   name.to_string(),
+        })
+    }
+}
+
+/// Read the sector `sector` of `reader`
+fn read_sector(reader: &mut (impl Read + Seek), sector: u64)
+        -> Result<[u8; SECTOR_SIZE as usize]> {
+    let offset = sector * SECTOR_SIZE;
+    reader.seek(SeekFrom::Start(offset))
+        .map_err(|x| Error::Consume("volume descriptor", offset, x))?;
+
+    let mut ret = [0u8; SECTOR_SIZE as usize];
+    read_field(reader, &mut ret, "volume descriptor")?;
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::Cursor;
+
+    /// Build a directory record for `name` at `extent`
+    fn record(name: &str, extent: u32, size: u32, is_dir: bool) -> Vec<u8> {
+        // Records are padded to an even length
+        let len = (33 + name.len()).next_multiple_of(2);
+        let mut ret = vec![0u8; len];
+        ret[0] = len as u8;
+        ret[2..6].copy_from_slice(&extent.to_le_bytes());
+        ret[10..14].copy_from_slice(&size.to_le_bytes());
+        ret[25] = if is_dir { 2 } else { 0 };
+        ret[32] = name.len() as u8;
+        ret[33..33 + name.len()].copy_from_slice(name.as_bytes());
+        ret
+    }
+
+    /// Build a directory at `extent` holding `entries`, with its `.` and
+    /// `..` entries
+    fn directory(extent: u32, entries: &[Vec<u8>]) -> Vec<u8> {
+        let mut ret = record("\0", extent, SECTOR_SIZE as u32, true);
+        ret.extend_from_slice(&record("\x01", 18, SECTOR_SIZE as u32, true));
+        ret.extend_from_slice(&entries.concat());
+        ret
+    }
+
+    /// Build an image with the root directory at sector 18 and `sectors`
+    /// after it, each padded out to a whole sector
+    fn image(sectors: &[Vec<u8>]) -> Vec<u8> {
+        let sector = SECTOR_SIZE as usize;
+        let mut ret = vec![0u8; FIRST_DESCRIPTOR as usize * sector];
+
+        // Primary volume descriptor, then the terminator
+        let mut primary = vec![0u8; sector];
+        primary[..6].copy_from_slice(b"\x01CD001");
+        let root = record("\0", 18, SECTOR_SIZE as u32, true);
+        primary[156..156 + root.len()].copy_from_slice(&root);
+        ret.extend_from_slice(&primary);
+        let mut terminator = vec![0u8; sector];
+        terminator[..6].copy_from_slice(b"\xffCD001");
+        ret.extend_from_slice(&terminator);
+
+        for data in sectors {
+            let start = ret.len();
+            ret.extend_from_slice(data);
+            ret.resize(start + sector, 0);
+        }
+        ret
+    }
+
+    #[test]
+    fn reads_files() {
+        let data = image(&[
+            directory(18, &[
+                record("SYMBOLS", 19, SECTOR_SIZE as u32, true),
+                record("README.TXT;1", 21, 5, false),
+            ]),
+            directory(19, &[record("NTOSKRNL.DBG;1", 20, 2, false)]),
+            b"DI".to_vec(),
+            b"hello".to_vec(),
+        ]);
+        assert!(is_iso(&mut Cursor::new(&data[..])));
+
+        let mut iso = IsoImage::new(Cursor::new(&data[..])).unwrap();
+        let files = iso.files().to_vec();
+        let paths = files.iter().map(|x| x.path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, ["SYMBOLS/NTOSKRNL.DBG", "README.TXT"]);
+        assert_eq!(iso.read(&files[0]).unwrap(), b"DI");
+        assert_eq!(iso.read(&files[1]).unwrap(), b"hello");
+        assert!(iso.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn directories_are_walked_once() {
+        // `A` links back to the root, and `B` is the same directory as `A`
+        let data = image(&[
+            directory(18, &[
+                record("A", 19, SECTOR_SIZE as u32, true),
+                record("B", 19, SECTOR_SIZE as u32, true),
+                record("C.DBG;1", 20, 0, false),
+            ]),
+            directory(19, &[
+                record("LOOP", 18, SECTOR_SIZE as u32, true),
+                record("D.DBG;1", 20, 0, false),
+            ]),
+        ]);
+
+        let iso = IsoImage::new(Cursor::new(&data[..])).unwrap();
+        let paths = iso.files().iter().map(|x| x.path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, ["A/D.DBG", "C.DBG"]);
+
+        let offsets = iso.diagnostics().iter().map(|x| x.offset)
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, [18 * SECTOR_SIZE, 19 * SECTOR_SIZE]);
+    }
+
+    #[test]
+    fn not_an_image() {
+        let data = vec![0u8; 18 * SECTOR_SIZE as usize];
+        assert!(!is_iso(&mut Cursor::new(&data[..])));
+        assert!(matches!(IsoImage::new(Cursor::new(&data[..])),
+            Err(Error::Iso("bad volume descriptor"))));
+    }
+}
@@ -0,0 +1,3367 @@
+//! Parser for `DI` debug info files. We specfically just parse the COFF
+//! data from them to get globals, functions, and line numbers
+//!
+//! The parser itself only needs `core` and `alloc`. Without the default-on
+//! `std` feature the crate is `no_std`, files are parsed from byte slices
+//! with [`DbgFile::parse`], and the features which need an OS are left out.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::borrow::Cow;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use alloc::vec;
+use core::mem::size_of;
+use core::ops::{Range, RangeBounds, RangeInclusive};
+use crate::io::{Cursor, Read, Seek, SeekFrom};
+
+#[cfg(feature = "std")]
+use std::path::PathBuf;
+
+#[cfg(feature = "std")]
+use std::sync::OnceLock;
+
+// Without `std` nothing is shared between threads, so a cell does
+#[cfg(not(feature = "std"))]
+use core::cell::OnceCell as OnceLock;
+
+#[cfg(feature = "capi")]
+pub mod capi;
+
+pub mod coverage;
+pub mod expand;
+mod footprint;
+pub mod io;
+
+#[cfg(feature = "iso")]
+pub mod iso;
+
+mod map;
+pub mod meso;
+pub mod overlap;
+mod session;
+#[cfg(feature = "std")]
+mod sink;
+pub mod split;
+mod symbol_map;
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+mod writer;
+#[cfg(feature = "std")]
+pub use sink::{SymbolSink, TextSink};
+pub use footprint::{MemoryFootprint, TableFootprint};
+pub use session::{Module, Resolved, Session};
+pub use symbol_map::SymbolMap;
+pub use writer::DbgWriter;
+
+/// Size of the largest file which is read into memory at once to be parsed,
+/// rather than read a field at a time as it's parsed, which takes thousands
+/// of small reads
+#[cfg(feature = "std")]
+pub const MAX_READ_WHOLE: u64 = 256 * 1024 * 1024;
+
+/// Wrapper type for `Result`
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Error types
+#[derive(Debug)]
+pub enum Error {
+    /// Failed to open input file
+    #[cfg(feature = "std")]
+    Open(PathBuf, io::Error),
+
+    /// File was not a debug info file
+    NotDebugInfo,
+
+    /// Failed to consume a field from the file, given as (field, offset of
+    /// the field in the stream, error)
+    Consume(&'static str, u64, io::Error),
+
+    /// Exported name was not valid UTF-8
+    ExportedNameUtf8(core::str::Utf8Error),
+    
+    /// Symbol table string name was not valid UTF-8
+    StringNameUtf8(core::str::Utf8Error),
+    
+    /// A source filename had an invalid UTF-8 character
+    FilenameUtf8(core::str::Utf8Error),
+
+    /// Section name was not valid UTF-8
+    SectionNameUtf8(core::str::Utf8Error),
+
+    /// Image name in the miscellaneous debug information was not valid
+    /// UTF-8
+    ImageNameUtf8(core::str::Utf8Error),
+
+    /// Failed to seek to the COFF section
+    SeekCoff(io::Error),
+
+    /// Failed to seek to the exception information
+    SeekException(io::Error),
+
+    /// COFF debug referenced out-of-bounds string for symbol name
+    SymbolNameOob,
+
+    /// Got a symbol class that was unknown
+    UnknownSymbolClass(u8),
+
+    /// Failed to extract a file from the CAB
+    ExtractCab(io::Error),
+
+    /// Failed to extract a file from the ZIP
+    ExtractZip(io::Error),
+
+    /// An ISO9660 image was malformed, given as (reason)
+    Iso(&'static str),
+
+    /// A linker map file was malformed, given as (line number, reason)
+    Map(usize, &'static str),
+
+    /// A coverage trace was malformed, given as (line number, reason)
+    Trace(usize, &'static str),
+
+    /// A PE image was malformed or had nothing to split, given as (reason)
+    Pe(&'static str),
+
+    /// A size or count field was over its limit in [`LoadOptions`], given as
+    /// (field, value)
+    TooLarge(&'static str, u64),
+
+    /// A size or count field was larger than the rest of the file, given as
+    /// (field, value)
+    Truncated(&'static str, u64),
+
+    /// A size or count field had an odd value which was worked around, given
+    /// as (field, value)
+    Suspicious(&'static str, u64),
+
+    /// Failed to decompress an SZDD or KWAJ file, given as (reason)
+    Expand(&'static str),
+
+    /// A symbol or line was past the end of the image as given by the size
+    /// of image in the header, given as (kind of record, RVA)
+    OutsideImage(&'static str, u32),
+}
+
+impl Error {
+    /// Check if this error is from the file ending before the data it
+    /// describes
+    pub fn is_truncation(&self) -> bool {
+        match self {
+            Self::Consume(_, _, err) =>
+                err.kind() == io::ErrorKind::UnexpectedEof,
+            Self::Truncated(..) => true,
+            _ => false,
+        }
+    }
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            #[cfg(feature = "std")]
+            Self::Open(path, err) =>
+                write!(f, "failed to open {}: {}", path.display(), err),
+            Self::NotDebugInfo => write!(f, "not a DI debug info file"),
+            Self::Consume(field, offset, err) =>
+                write!(f, "failed to read {} at offset {:#x}: {}",
+                    field, offset, err),
+            Self::ExportedNameUtf8(err) =>
+                write!(f, "exported name is not valid UTF-8: {}", err),
+            Self::StringNameUtf8(err) =>
+                write!(f, "symbol name is not valid UTF-8: {}", err),
+            Self::FilenameUtf8(err) =>
+                write!(f, "source filename is not valid UTF-8: {}", err),
+            Self::SectionNameUtf8(err) =>
+                write!(f, "section name is not valid UTF-8: {}", err),
+            Self::ImageNameUtf8(err) =>
+                write!(f, "image name is not valid UTF-8: {}", err),
+            Self::SeekCoff(err) =>
+                write!(f, "failed to seek to the COFF symbols: {}", err),
+            Self::SeekException(err) =>
+                write!(f, "failed to seek to the function table: {}", err),
+            Self::SymbolNameOob =>
+                write!(f, "symbol name is outside of the string table"),
+            Self::UnknownSymbolClass(class) =>
+                write!(f, "unknown symbol class {}", class),
+            Self::ExtractCab(err) =>
+                write!(f, "failed to extract from the CAB: {}", err),
+            Self::ExtractZip(err) =>
+                write!(f, "failed to extract from the ZIP: {}", err),
+            Self::Iso(reason) => write!(f, "bad ISO9660 image: {}", reason),
+            Self::Map(line, reason) =>
+                write!(f, "bad map file at line {}: {}", line, reason),
+            Self::Trace(line, reason) =>
+                write!(f, "bad trace at line {}: {}", line, reason),
+            Self::Pe(reason) => write!(f, "bad PE image: {}", reason),
+            Self::TooLarge(field, value) =>
+                write!(f, "{} {:#x} is over the limit", field, value),
+            Self::Truncated(field, value) =>
+                write!(f, "{} {:#x} is past the end of the file",
+                    field, value),
+            Self::Suspicious(field, value) =>
+                write!(f, "{} {:#x} is suspicious", field, value),
+            Self::Expand(reason) =>
+                write!(f, "failed to expand compressed file: {}", reason),
+            Self::OutsideImage(kind, rva) =>
+                write!(f, "{} at RVA {:#x} is outside of the image",
+                    kind, rva),
+        }
+    }
+}
+
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            Self::Open(_, err) => Some(err),
+            Self::Consume(_, _, err) |
+                    Self::SeekCoff(err) | Self::SeekException(err) |
+                    Self::ExtractCab(err) | Self::ExtractZip(err) => Some(err),
+            Self::ExportedNameUtf8(err) | Self::StringNameUtf8(err) |
+                    Self::FilenameUtf8(err) | Self::SectionNameUtf8(err) |
+                    Self::ImageNameUtf8(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// A non-fatal problem found while parsing
+#[derive(Debug)]
+pub struct Diagnostic {
+    /// Offset in the file of the data with the problem
+    pub offset: u64,
+
+    /// What the problem was
+    pub error: Error,
+}
+
+impl core::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "offset {:#x}: {}", self.offset, self.error)
+    }
+}
+
+/// Consume bytes from a reader
+macro_rules! consume {
+    ($reader:expr, $ty:ty, $field:expr) => {{
+        // Create buffer for type
+        let mut tmp = [0u8; size_of::<$ty>()];
+
+        // Read the bytes and convert
+        read_field($reader.by_ref(), &mut tmp, $field).map(|_| {
+            <$ty>::from_le_bytes(tmp)
+        })
+    }};
+
+    ($reader:expr, $size:expr, $field:expr) => {{
+        // Create buffer for type
+        let mut tmp = [0u8; $size];
+
+        // Read the bytes and convert
+        read_field($reader.by_ref(), &mut tmp, $field).map(|_| {
+            tmp
+        })
+    }};
+}
+
+/// Fill `buf` from `reader`, reporting the offset `field` was at if it
+/// couldn't be read
+fn read_field(reader: &mut (impl Read + Seek), buf: &mut [u8],
+        field: &'static str) -> Result<()> {
+    let offset = reader.stream_position()
+        .map_err(|x| Error::Consume(field, 0, x))?;
+    reader.read_exact(buf).map_err(|x| Error::Consume(field, offset, x))
+}
+
+/// Enter a `tracing` debug span, which lasts until the returned guard is
+/// dropped. Does nothing without the `tracing` feature.
+macro_rules! trace_span {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "tracing")]
+        let guard = tracing::debug_span!($($arg)*).entered();
+
+        #[cfg(not(feature = "tracing"))]
+        let guard = NoSpan;
+
+        guard
+    }};
+}
+
+/// Stand-in for a span guard when the `tracing` feature is disabled
+#[cfg(not(feature = "tracing"))]
+struct NoSpan;
+
+/// Emit a `tracing` debug event. Does nothing without the `tracing` feature.
+macro_rules! trace_event {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+    };
+}
+
+/// Debug directory types
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum DebugType {
+    ///  Unknown value, ignored by all tools. 
+    Unknown = 0,
+
+    /// COFF debugging information (line numbers, symbol table, and
+    /// string table). This type of debugging information is also
+    /// pointed to by fields in the file headers. 
+    Coff = 1,
+
+    /// CodeView debugging information. The format of the data block is
+    /// described by the CodeView 4.0 specification. 
+    CodeView = 2,
+
+    /// Frame pointer omission (FPO) information. This information
+    /// tells the debugger how to interpret nonstandard stack frames,
+    /// which use the EBP register for a purpose other than as a frame
+    /// pointer. 
+    FramePointerOmission = 3,
+
+    /// Miscellaneous information. 
+    Misc = 4,
+
+    /// Exception information. 
+    Exception = 5,
+
+    /// Fixup information. 
+    Fixup = 6,
+
+    /// Omap to source
+    OmapToSrc = 7,
+
+    /// Omap from source
+    OmapFromSrc = 8,
+
+    /// Borland debugging information. 
+    Borland = 9,
+
+    /// Reserved for future use.
+    Reserved10 = 10,
+
+    /// Reserved for future use.
+    Clsid = 11,
+
+    /// Visual C++ feature information.
+    VcFeature = 12,
+
+    /// Profile guided optimization information.
+    Pogo = 13,
+
+    /// Incremental link-time code generation information.
+    Iltcg = 14,
+
+    /// Intel Memory Protection Extensions information.
+    Mpx = 15,
+
+    /// PE determinism or reproducibility.
+    Repro = 16,
+
+    /// Extended DLL characteristics bits.
+    ExDllCharacteristics = 20,
+
+    /// A debug type we don't know about, these are skipped
+    Other(u32),
+}
+
+impl From<u32> for DebugType {
+    fn from(val: u32) -> Self {
+        match val {
+            0 => Self::Unknown,
+            1 => Self::Coff,
+            2 => Self::CodeView,
+            3 => Self::FramePointerOmission,
+            4 => Self::Misc,
+            5 => Self::Exception,
+            6 => Self::Fixup,
+            7 => Self::OmapToSrc,
+            8 => Self::OmapFromSrc,
+            9 => Self::Borland,
+            10 => Self::Reserved10,
+            11 => Self::Clsid,
+            12 => Self::VcFeature,
+            13 => Self::Pogo,
+            14 => Self::Iltcg,
+            15 => Self::Mpx,
+            16 => Self::Repro,
+            20 => Self::ExDllCharacteristics,
+            _  => Self::Other(val),
+        }
+    }
+}
+
+impl From<DebugType> for u32 {
+    fn from(val: DebugType) -> Self {
+        match val {
+            DebugType::Unknown              => 0,
+            DebugType::Coff                 => 1,
+            DebugType::CodeView             => 2,
+            DebugType::FramePointerOmission => 3,
+            DebugType::Misc                 => 4,
+            DebugType::Exception            => 5,
+            DebugType::Fixup                => 6,
+            DebugType::OmapToSrc            => 7,
+            DebugType::OmapFromSrc          => 8,
+            DebugType::Borland              => 9,
+            DebugType::Reserved10           => 10,
+            DebugType::Clsid                => 11,
+            DebugType::VcFeature            => 12,
+            DebugType::Pogo                 => 13,
+            DebugType::Iltcg                => 14,
+            DebugType::Mpx                  => 15,
+            DebugType::Repro                => 16,
+            DebugType::ExDllCharacteristics => 20,
+            DebugType::Other(x)             => x,
+        }
+    }
+}
+
+/// COFF symbol storage classes (`IMAGE_SYM_CLASS_*`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StorageClass {
+    /// A special symbol that represents the end of function, for debugging
+    /// purposes.
+    EndOfFunction,
+
+    /// No assigned storage class.
+    Null,
+
+    /// The automatic (stack) variable. The Value field specifies the stack
+    /// frame offset.
+    Automatic,
+
+    /// A value that Microsoft tools use for external symbols. The Value
+    /// field indicates the size if the section number is
+    /// `IMAGE_SYM_UNDEFINED` (0). If the section number is not zero, then
+    /// the Value field specifies the offset within the section.
+    External,
+
+    /// The offset of the symbol within the section. If the Value field is
+    /// zero, then the symbol represents a section name.
+    Static,
+
+    /// A register variable. The Value field specifies the register number.
+    Register,
+
+    /// A symbol that is defined externally.
+    ExternalDef,
+
+    /// A code label that is defined within the module. The Value field
+    /// specifies the offset of the symbol within the section.
+    Label,
+
+    /// A reference to a code label that is not defined.
+    UndefinedLabel,
+
+    /// The structure member. The Value field specifies the n th member.
+    MemberOfStruct,
+
+    /// A formal argument (parameter) of a function. The Value field
+    /// specifies the n th argument.
+    Argument,
+
+    /// The structure tag-name entry.
+    StructTag,
+
+    /// A union member. The Value field specifies the n th member.
+    MemberOfUnion,
+
+    /// The Union tag-name entry.
+    UnionTag,
+
+    /// A Typedef entry.
+    TypeDefinition,
+
+    /// A static data declaration.
+    UndefinedStatic,
+
+    /// An enumerated type tagname entry.
+    EnumTag,
+
+    /// A member of an enumeration. The Value field specifies the n th
+    /// member.
+    MemberOfEnum,
+
+    /// A register parameter.
+    RegisterParam,
+
+    /// A bit-field reference. The Value field specifies the n th bit in the
+    /// bit field.
+    BitField,
+
+    /// A .bb (beginning of block) or .eb (end of block) record. The Value
+    /// field is the relocatable address of the code location.
+    Block,
+
+    /// A value that Microsoft tools use for symbol records that define the
+    /// extent of a function: begin function (.bf), end function (.ef), and
+    /// lines in function (.lf).
+    Function,
+
+    /// An end-of-structure entry.
+    EndOfStruct,
+
+    /// A value that Microsoft tools, as well as traditional COFF format,
+    /// use for the source-file symbol record. The symbol is followed by
+    /// auxiliary records that name the file.
+    File,
+
+    /// A definition of a section (Microsoft tools use STATIC storage class
+    /// instead).
+    Section,
+
+    /// A weak external.
+    WeakExternal,
+
+    /// A CLR token symbol. The name is an ASCII string that consists of the
+    /// hexadecimal value of the token.
+    ClrToken,
+
+    /// A storage class we don't know about
+    Unknown(u8),
+}
+
+impl From<u8> for StorageClass {
+    fn from(val: u8) -> Self {
+        match val {
+            0xff => Self::EndOfFunction,
+            0    => Self::Null,
+            1    => Self::Automatic,
+            2    => Self::External,
+            3    => Self::Static,
+            4    => Self::Register,
+            5    => Self::ExternalDef,
+            6    => Self::Label,
+            7    => Self::UndefinedLabel,
+            8    => Self::MemberOfStruct,
+            9    => Self::Argument,
+            10   => Self::StructTag,
+            11   => Self::MemberOfUnion,
+            12   => Self::UnionTag,
+            13   => Self::TypeDefinition,
+            14   => Self::UndefinedStatic,
+            15   => Self::EnumTag,
+            16   => Self::MemberOfEnum,
+            17   => Self::RegisterParam,
+            18   => Self::BitField,
+            100  => Self::Block,
+            101  => Self::Function,
+            102  => Self::EndOfStruct,
+            103  => Self::File,
+            104  => Self::Section,
+            105  => Self::WeakExternal,
+            107  => Self::ClrToken,
+            _    => Self::Unknown(val),
+        }
+    }
+}
+
+/// `IMAGE_SECTION_HEADER`
+#[derive(Debug)]
+#[repr(C)]
+struct SectionHeader {
+    /// An 8-byte, null-padded UTF-8 string. There is no
+    /// terminating null character if the string is exactly eight
+    /// characters long. For longer names, this member contains a
+    /// forward slash (/) followed by an ASCII representation of a
+    /// decimal number that is an offset into the string table.
+    /// Executable images do not use a string table and do not
+    /// support section names longer than eight characters.
+    name: [u8; 8],
+
+    /// The total size of the section when loaded into memory, in
+    /// bytes. If this value is greater than the SizeOfRawData
+    /// member, the section is filled with zeroes. This field is
+    /// valid only for executable images and should be set to 0 for
+    /// object files.
+    vsize: u32,
+
+    /// The address of the first byte of the section when loaded
+    /// into memory, relative to the image base. For object files,
+    /// this is the address of the first byte before relocation is
+    /// applied.
+    vaddr: u32,
+
+    /// The size of the initialized data on disk, in bytes. This
+    /// value must be a multiple of the `FileAlignment` member of
+    /// the `IMAGE_OPTIONAL_HEADER` structure. If this value is
+    /// less than the VirtualSize member, the remainder of the
+    /// section is filled with zeroes. If the section contains only
+    /// uninitialized data, the member is zero.
+    raw_data_sz: u32,
+
+    /// A file pointer to the first page within the COFF file. This
+    /// value must be a multiple of the `FileAlignment` member of
+    /// the `IMAGE_OPTIONAL_HEADER` structure. If a section
+    /// contains only uninitialized data, set this member is zero.
+    ptr_raw_data: u32,
+
+    /// A file pointer to the beginning of the relocation entries
+    /// for the section. If there are no relocations, this value is
+    /// zero.
+    ptr_relocation: u32,
+
+    /// A file pointer to the beginning of the line-number entries
+    /// for the section. If there are no COFF line numbers, this
+    /// value is zero.
+    ptr_line_num: u32,
+
+    /// The number of relocation entries for the section. This
+    /// value is zero for executable images.
+    num_relocs: u16,
+
+    /// The number of line-number entries for the section.
+    num_line_num: u16,
+
+    /// The characteristics of the image. The following values are
+    /// defined.
+    characteristics: u32,
+}
+
+/// A section of the image, from its `IMAGE_SECTION_HEADER`
+#[derive(Debug, Clone)]
+pub struct Section {
+    /// Name of the section. Long names (`/NNN`) are resolved through the
+    /// COFF string table.
+    pub name: String,
+
+    /// The total size of the section when loaded into memory, in bytes
+    pub vsize: u32,
+
+    /// The RVA of the first byte of the section when loaded into memory
+    pub vaddr: u32,
+
+    /// The size of the initialized data on disk, in bytes
+    pub raw_data_sz: u32,
+
+    /// A file pointer to the first page of the section within the image
+    pub ptr_raw_data: u32,
+
+    /// The `IMAGE_SCN_*` characteristics of the section
+    pub characteristics: u32,
+}
+
+/// Category of the contents of a section, from its characteristics
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectionCategory {
+    /// Executable code
+    Code,
+
+    /// Writable initialized data
+    Data,
+
+    /// Uninitialized data
+    Bss,
+
+    /// Read-only initialized data
+    RData,
+}
+
+impl SectionCategory {
+    /// Get a short lowercase name for the category, eg. `rdata`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Code  => "code",
+            Self::Data  => "data",
+            Self::Bss   => "bss",
+            Self::RData => "rdata",
+        }
+    }
+}
+
+/// `IMAGE_SCN_CNT_CODE`, the section contains executable code
+const IMAGE_SCN_CNT_CODE: u32 = 0x00000020;
+
+/// `IMAGE_SCN_CNT_INITIALIZED_DATA`, the section contains initialized data
+const IMAGE_SCN_CNT_INITIALIZED_DATA: u32 = 0x00000040;
+
+/// `IMAGE_SCN_CNT_UNINITIALIZED_DATA`, the section contains uninitialized
+/// data
+const IMAGE_SCN_CNT_UNINITIALIZED_DATA: u32 = 0x00000080;
+
+/// `IMAGE_SCN_MEM_EXECUTE`, the section can be executed as code
+const IMAGE_SCN_MEM_EXECUTE: u32 = 0x20000000;
+
+/// `IMAGE_SCN_MEM_WRITE`, the section can be written to
+const IMAGE_SCN_MEM_WRITE: u32 = 0x80000000;
+
+impl Section {
+    /// Categorize the contents of this section from its characteristics
+    pub fn category(&self) -> SectionCategory {
+        let ch = self.characteristics;
+        if ch & (IMAGE_SCN_CNT_CODE | IMAGE_SCN_MEM_EXECUTE) != 0 {
+            SectionCategory::Code
+        } else if ch & IMAGE_SCN_CNT_UNINITIALIZED_DATA != 0 &&
+                ch & IMAGE_SCN_CNT_INITIALIZED_DATA == 0 {
+            SectionCategory::Bss
+        } else if ch & IMAGE_SCN_MEM_WRITE != 0 {
+            SectionCategory::Data
+        } else {
+            SectionCategory::RData
+        }
+    }
+
+    /// Check if `rva` falls inside of this section
+    ///
+    /// The extent of the section is the larger of its virtual and raw sizes,
+    /// as some files leave the virtual size as zero.
+    pub fn contains(&self, rva: u32) -> bool {
+        let size = self.vsize.max(self.raw_data_sz);
+        rva.wrapping_sub(self.vaddr) < size
+    }
+}
+
+impl Section {
+    /// Create a section from its header, decoding the name with `names`
+    fn from_header(sh: &SectionHeader, names: NameDecoding) -> Result<Self> {
+        Ok(Self {
+            // Inside unwrap is fine, `split` always returns at least one
+            // iterated value
+            name: names.decode(sh.name.split(|x| *x == 0).next().unwrap(),
+                Error::SectionNameUtf8)?.into_owned(),
+            vsize:           sh.vsize,
+            vaddr:           sh.vaddr,
+            raw_data_sz:     sh.raw_data_sz,
+            ptr_raw_data:    sh.ptr_raw_data,
+            characteristics: sh.characteristics,
+        })
+    }
+}
+
+/// Target architecture of an image (`IMAGE_FILE_MACHINE_*`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Machine {
+    /// The content of this field is assumed to be applicable to any machine
+    /// type
+    #[default]
+    Unknown,
+
+    /// Intel 386 or later processors and compatible processors
+    I386,
+
+    /// MIPS R3000 little endian
+    R3000,
+
+    /// MIPS R4000 little endian
+    R4000,
+
+    /// MIPS R10000 little endian
+    R10000,
+
+    /// MIPS little-endian WCE v2
+    WceMipsV2,
+
+    /// DEC Alpha AXP
+    Alpha,
+
+    /// Hitachi SH3
+    Sh3,
+
+    /// Hitachi SH3 DSP
+    Sh3Dsp,
+
+    /// Hitachi SH4
+    Sh4,
+
+    /// Hitachi SH5
+    Sh5,
+
+    /// ARM little endian
+    Arm,
+
+    /// Thumb
+    Thumb,
+
+    /// ARM Thumb-2 little endian
+    ArmNt,
+
+    /// Matsushita AM33
+    Am33,
+
+    /// Power PC little endian
+    PowerPc,
+
+    /// Power PC with floating point support
+    PowerPcFp,
+
+    /// Intel Itanium processor family
+    Ia64,
+
+    /// MIPS16
+    Mips16,
+
+    /// DEC Alpha AXP 64-bit
+    Alpha64,
+
+    /// MIPS with FPU
+    MipsFpu,
+
+    /// MIPS16 with FPU
+    MipsFpu16,
+
+    /// x64
+    Amd64,
+
+    /// Mitsubishi M32R little endian
+    M32R,
+
+    /// ARM64 little endian
+    Arm64,
+
+    /// A machine type we don't know about
+    Other(u16),
+}
+
+impl From<u16> for Machine {
+    fn from(val: u16) -> Self {
+        match val {
+            0x0000 => Self::Unknown,
+            0x014c => Self::I386,
+            0x0162 => Self::R3000,
+            0x0166 => Self::R4000,
+            0x0168 => Self::R10000,
+            0x0169 => Self::WceMipsV2,
+            0x0184 => Self::Alpha,
+            0x01a2 => Self::Sh3,
+            0x01a3 => Self::Sh3Dsp,
+            0x01a6 => Self::Sh4,
+            0x01a8 => Self::Sh5,
+            0x01c0 => Self::Arm,
+            0x01c2 => Self::Thumb,
+            0x01c4 => Self::ArmNt,
+            0x01d3 => Self::Am33,
+            0x01f0 => Self::PowerPc,
+            0x01f1 => Self::PowerPcFp,
+            0x0200 => Self::Ia64,
+            0x0266 => Self::Mips16,
+            0x0284 => Self::Alpha64,
+            0x0366 => Self::MipsFpu,
+            0x0466 => Self::MipsFpu16,
+            0x8664 => Self::Amd64,
+            0x9041 => Self::M32R,
+            0xaa64 => Self::Arm64,
+            _      => Self::Other(val),
+        }
+    }
+}
+
+impl From<Machine> for u16 {
+    fn from(val: Machine) -> Self {
+        match val {
+            Machine::Unknown   => 0x0000,
+            Machine::I386      => 0x014c,
+            Machine::R3000     => 0x0162,
+            Machine::R4000     => 0x0166,
+            Machine::R10000    => 0x0168,
+            Machine::WceMipsV2 => 0x0169,
+            Machine::Alpha     => 0x0184,
+            Machine::Sh3       => 0x01a2,
+            Machine::Sh3Dsp    => 0x01a3,
+            Machine::Sh4       => 0x01a6,
+            Machine::Sh5       => 0x01a8,
+            Machine::Arm       => 0x01c0,
+            Machine::Thumb     => 0x01c2,
+            Machine::ArmNt     => 0x01c4,
+            Machine::Am33      => 0x01d3,
+            Machine::PowerPc   => 0x01f0,
+            Machine::PowerPcFp => 0x01f1,
+            Machine::Ia64      => 0x0200,
+            Machine::Mips16    => 0x0266,
+            Machine::Alpha64   => 0x0284,
+            Machine::MipsFpu   => 0x0366,
+            Machine::MipsFpu16 => 0x0466,
+            Machine::Amd64     => 0x8664,
+            Machine::M32R      => 0x9041,
+            Machine::Arm64     => 0xaa64,
+            Machine::Other(x)  => x,
+        }
+    }
+}
+
+/// Layout of the entries in an `IMAGE_DEBUG_TYPE_EXCEPTION` table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FunctionEntryLayout {
+    /// `IMAGE_FUNCTION_ENTRY`, start, end, and end of prologue
+    Entry,
+
+    /// `IMAGE_FUNCTION_ENTRY64`, the 64-bit Alpha version of `Entry`
+    Entry64,
+
+    /// `IMAGE_RUNTIME_FUNCTION_ENTRY` as found in the `.pdata` of Alpha,
+    /// MIPS, and PowerPC images: start, end, exception handler, handler data,
+    /// and end of prologue
+    Runtime,
+}
+
+impl FunctionEntryLayout {
+    /// Pick the layout for a function table of `machine`
+    ///
+    /// RISC images may carry either a stripped down `IMAGE_FUNCTION_ENTRY`
+    /// table or a raw copy of `.pdata`, so for those we check which one the
+    /// table is consistent with
+    fn detect(machine: Machine, table: &[u8]) -> Self {
+        match machine {
+            Machine::Alpha64 => Self::Entry64,
+            Machine::R3000 | Machine::R4000 | Machine::R10000 |
+            Machine::WceMipsV2 | Machine::Mips16 | Machine::MipsFpu |
+            Machine::MipsFpu16 | Machine::Alpha | Machine::PowerPc |
+            Machine::PowerPcFp => {
+                if !Self::Entry.is_consistent(table) &&
+                        Self::Runtime.is_consistent(table) {
+                    Self::Runtime
+                } else {
+                    Self::Entry
+                }
+            }
+            _ => Self::Entry,
+        }
+    }
+
+    /// Size of a single entry in bytes
+    fn size(self) -> usize {
+        match self {
+            Self::Entry   => 12,
+            Self::Entry64 => 24,
+            Self::Runtime => 20,
+        }
+    }
+
+    /// Parse the start, end, and end of prologue addresses from an entry
+    fn parse(self, entry: &[u8]) -> Result<(u64, u64, u64)> {
+        let mut entry = Cursor::new(entry);
+        Ok(match self {
+            Self::Entry => {
+                let begin       = consume!(entry, u32, "starting address")?;
+                let end         = consume!(entry, u32, "ending address")?;
+                let end_prolog  = consume!(entry, u32, "end of prologue")?;
+                (begin as u64, end as u64, end_prolog as u64)
+            }
+            Self::Entry64 => {
+                let begin       = consume!(entry, u64, "starting address")?;
+                let end         = consume!(entry, u64, "ending address")?;
+                let end_prolog  = consume!(entry, u64, "end of prologue")?;
+                (begin, end, end_prolog)
+            }
+            Self::Runtime => {
+                let begin       = consume!(entry, u32, "starting address")?;
+                let end         = consume!(entry, u32, "ending address")?;
+                let _handler    = consume!(entry, u32, "exception handler")?;
+                let _data       = consume!(entry, u32, "handler data")?;
+                let end_prolog  = consume!(entry, u32, "end of prologue")?;
+                (begin as u64, end as u64, end_prolog as u64)
+            }
+        })
+    }
+
+    /// Check whether `table` parses as a sorted list of sane entries in this
+    /// layout
+    fn is_consistent(self, table: &[u8]) -> bool {
+        if !table.len().is_multiple_of(self.size()) {
+            return false;
+        }
+
+        let mut prev_end = 0;
+        for entry in table.chunks_exact(self.size()) {
+            let Ok((begin, end, prolog)) = self.parse(entry) else {
+                return false;
+            };
+
+            if begin < prev_end || end <= begin ||
+                    !(begin..=end).contains(&prolog) {
+                return false;
+            }
+            prev_end = end;
+        }
+
+        true
+    }
+}
+
+/// Fields of the `IMAGE_SEPARATE_DEBUG_HEADER` describing the image
+#[derive(Debug, Clone, Default)]
+pub struct Header {
+    /// Flags
+    pub flags: u16,
+
+    /// The architecture of the image
+    pub machine: Machine,
+
+    /// The `IMAGE_FILE_*` characteristics of the image
+    pub characteristics: u16,
+
+    /// The time and date the image was created, in seconds since the Unix
+    /// epoch
+    pub timedatestamp: u32,
+
+    /// The checksum of the image
+    pub checksum: u32,
+
+    /// The preferred base address of the image
+    pub image_base: u32,
+
+    /// The size of the image when loaded into memory, in bytes
+    pub size_of_image: u32,
+
+    /// The alignment of sections when loaded into memory, in bytes
+    pub section_align: u32,
+}
+
+/// `IMAGE_DEBUG_DIRECTORY`
+#[derive(Debug, Clone)]
+#[repr(C)]
+pub struct DebugDirectory {
+    /// Reserved
+    pub characteristics: u32,
+
+    /// The time and date the debugging information was created.
+    pub timedatestamp: u32,
+
+    /// The major version number of the debugging information format.
+    pub major_version: u16,
+
+    /// The minor version number of the debugging information format.
+    pub minor_version: u16,
+
+    /// The format of the debugging information.
+    pub typ: DebugType,
+
+    /// The size of the debugging information, in bytes. This value
+    /// does not include the debug directory itself.
+    pub size_of_data: u32,
+
+    /// The address of the debugging information when the image is
+    /// loaded, relative to the image base.
+    pub addr_raw_data: u32,
+
+    /// A file pointer to the debugging information.
+    pub ptr_raw_data: u32,
+}
+
+impl DebugDirectory {
+    /// Size of an `IMAGE_DEBUG_DIRECTORY` in the file, in bytes. This differs
+    /// from the in-memory size as `typ` is parsed into a [`DebugType`].
+    const SIZE: usize = 28;
+
+    /// Get the data this directory describes from `file`, the bytes of the
+    /// file it was loaded from. Returns `None` if the data runs past the end
+    /// of `file`.
+    pub fn data<'a>(&self, file: &'a [u8]) -> Option<&'a [u8]> {
+        let start = self.ptr_raw_data as usize;
+        file.get(start..start.checked_add(self.size_of_data as usize)?)
+    }
+}
+
+/// `IMAGE_COFF_SYMBOLS_HEADER`
+#[derive(Debug)]
+#[repr(C)]
+struct CoffSymbolsHeader {
+    /// The number of symbols.
+    num_symbols: u32,
+
+    /// The virtual address of the first symbol.
+    lva_first_symbol: u32,
+
+    /// The number of line-number entries.
+    num_line_nums: u32,
+
+    /// The virtual address of the first line-number entry.
+    lva_first_line: u32,
+
+    /// The relative virtual address of the first byte of code.
+    rva_first_code: u32,
+
+    /// The relative virtual address of the last byte of code.
+    rva_last_code: u32,
+
+    /// The relative virtual address of the first byte of data.
+    rva_first_data: u32,
+
+    /// The relative virtual address of the last byte of data.
+    rva_last_data: u32,
+}
+
+impl CoffSymbolsHeader {
+    /// Parse the header at the current position of `reader`
+    fn parse(reader: &mut (impl Read + Seek)) -> Result<Self> {
+        Ok(Self {
+            num_symbols:      consume!(reader, u32, "num_symbols")?,
+            lva_first_symbol:
+                consume!(reader, u32, "lva_first_symbol")?,
+            num_line_nums:    consume!(reader, u32, "num_line_nums")?,
+            lva_first_line:   consume!(reader, u32, "lva_first_line")?,
+            rva_first_code:   consume!(reader, u32, "rva_first_code")?,
+            rva_last_code:    consume!(reader, u32, "rva_last_code")?,
+            rva_first_data:   consume!(reader, u32, "rva_first_data")?,
+            rva_last_data:    consume!(reader, u32, "rva_last_data")?,
+        })
+    }
+
+    /// Get the RVAs of the first and last bytes of code, if there are any
+    fn code_range(&self) -> Option<RangeInclusive<u32>> {
+        Self::range(self.rva_first_code, self.rva_last_code)
+    }
+
+    /// Get the RVAs of the first and last bytes of data, if there are any
+    fn data_range(&self) -> Option<RangeInclusive<u32>> {
+        Self::range(self.rva_first_data, self.rva_last_data)
+    }
+
+    /// Get the range `first..=last`, linkers with nothing to put there write
+    /// zeros
+    fn range(first: u32, last: u32) -> Option<RangeInclusive<u32>> {
+        (first <= last && last != 0).then_some(first..=last)
+    }
+}
+
+/// Get the name of a COFF symbol from its 8-byte `name` field, which either
+/// holds the name inline or points into `string_table`, decoded with `names`
+fn symbol_name<'a>(name: &'a [u8; 8], string_table: &'a [u8],
+        names: NameDecoding) -> Result<Cow<'a, str>> {
+    // Check if the symbol name is a pointer
+    let name_is_ptr = &name[..4] == b"\0\0\0\0";
+    Ok(if name_is_ptr {
+        // Unwrap is fine because the size is constant
+        let ptr = u32::from_le_bytes(name[4..].try_into().unwrap());
+
+        // Inside unwrap is fine, `split` always returns at least one
+        // iterated value. A pointer at the very end of the table is out of
+        // bounds too.
+        let name = string_table.get(ptr as usize..)
+            .filter(|x| !x.is_empty())
+            .ok_or(Error::SymbolNameOob)?;
+        names.decode(name.split(|x| *x == 0).next().unwrap(),
+            Error::StringNameUtf8)?
+    } else {
+        // Inside unwrap is fine, `split` always returns at least one
+        // iterated value
+        names.decode(name.split(|x| *x == 0).next().unwrap(),
+            Error::StringNameUtf8)?
+    })
+}
+
+/// Get the path of a `.file` symbol from its `aux` data, which spans as many
+/// 18-byte aux records as the path needs
+///
+/// The path normally runs straight through the records and is padded out
+/// with NULs, but some producers pad each record on its own. Each record is
+/// taken up to its first NUL so both give the whole path. The path is
+/// decoded with `names`.
+fn file_name(aux: &[u8], names: NameDecoding) -> Result<String> {
+    // Inside unwrap is fine, `split` always returns at least one iterated
+    // value
+    let name = aux.chunks(18)
+        .flat_map(|x| x.split(|x| *x == 0).next().unwrap())
+        .copied()
+        .collect::<Vec<_>>();
+    Ok(names.decode(&name, Error::FilenameUtf8)?.into_owned())
+}
+
+/// Check if `filename` refers to `source`, either exactly or with `source`
+/// being a trailing path component, ignoring ASCII case
+fn source_matches(filename: &str, source: &str) -> bool {
+    let (filename, source) = (filename.as_bytes(), source.as_bytes());
+
+    // `source` must be a suffix of `filename`
+    let Some(split) = filename.len().checked_sub(source.len()) else {
+        return false;
+    };
+    if !filename[split..].eq_ignore_ascii_case(source) {
+        return false;
+    }
+
+    // And must start at a path component boundary
+    split == 0 || matches!(filename[split - 1], b'\\' | b'/' | b':')
+}
+
+/// Demangle an MSVC C++ decorated name, eg. `?Foo@Bar@@QAEXH@Z`
+///
+/// Returns `None` if `name` is not a decorated name or failed to demangle
+#[cfg(feature = "demangle")]
+pub fn demangle(name: &str) -> Option<String> {
+    // Only C++ decorated names start with a `?`
+    if !name.starts_with('?') {
+        return None;
+    }
+
+    msvc_demangler::demangle(name, msvc_demangler::DemangleFlags::COMPLETE)
+        .ok()
+}
+
+/// Strip i386 C calling convention decoration from `name`
+///
+/// This turns `_func@12` (stdcall), `@func@8` (fastcall), and `_func` (cdecl)
+/// into `func`. C++ decorated names (starting with `?`) and names without a
+/// decoration are returned unchanged.
+pub fn undecorate(name: &str) -> &str {
+    let (prefixed, stripped) = if let Some(name) = name.strip_prefix('_') {
+        (true, name)
+    } else if let Some(name) = name.strip_prefix('@') {
+        (true, name)
+    } else {
+        (false, name)
+    };
+
+    // Strip the `@<argument bytes>` suffix
+    let stripped = match stripped.rsplit_once('@') {
+        Some((base, args)) if !base.is_empty() && !args.is_empty() &&
+                args.bytes().all(|x| x.is_ascii_digit()) => base,
+        _ => stripped,
+    };
+
+    // A decorated name needs a prefix and something left after stripping
+    if prefixed && !stripped.is_empty() { stripped } else { name }
+}
+
+/// Check if `name` is a compiler or linker internal symbol rather than
+/// something from the source, eg. `$$SYMBOLS`, `$LN12`, `@comp.id`, a string
+/// literal like `??_C@_05...`, a floating point constant like `__real@...`, or
+/// the import descriptors and thunk data of an import library
+///
+/// Only external symbols are kept as functions and globals, so statics,
+/// labels, and section symbols are already left out by their storage class.
+/// This catches the internal names which are external anyway.
+pub fn is_internal(name: &str) -> bool {
+    const PREFIXES: &[&str] = &[
+        "$", ".", "@comp.id", "@feat.", "\x7f", "??_C@", "__real@", "__xmm@",
+        "__ymm@", "__mask@", "__IMPORT_DESCRIPTOR_",
+        "__NULL_IMPORT_DESCRIPTOR", "__CT??", "__CTA", "__TI",
+    ];
+
+    PREFIXES.iter().any(|x| name.starts_with(x)) ||
+        name.ends_with("_NULL_THUNK_DATA")
+}
+
+/// Get the module name of the image or file name `name`, by removing the
+/// directory and the extension, eg. `ntoskrnl` from `\nt\ntoskrnl.exe`
+pub fn module_stem(name: &str) -> &str {
+    let name = name.rsplit(['\\', '/', ':']).next().unwrap_or(name);
+    match name.rsplit_once('.') {
+        Some((stem, _)) if !stem.is_empty() => stem,
+        _ => name,
+    }
+}
+
+/// Split a WinDbg style qualified name like `ntoskrnl!KeBugCheckEx` into the
+/// module and symbol names
+///
+/// Returns no module for a name without a `!`, or with nothing before it.
+pub fn split_qualified(name: &str) -> (Option<&str>, &str) {
+    match name.split_once('!') {
+        Some((module, symbol)) if !module.is_empty() =>
+            (Some(module), symbol),
+        _ => (None, name),
+    }
+}
+
+/// Kind of a named symbol
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    /// A function, from the `functions` map
+    Function,
+
+    /// A global, from the `globals` map
+    Global,
+}
+
+/// A single piece of symbol information, as emitted by [`parse_with`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event<'a> {
+    /// A public function at `rva`
+    Function { rva: u32, name: &'a str },
+
+    /// A public global at `rva`
+    Global { rva: u32, name: &'a str },
+
+    /// Source line `line` of `filename` at `rva`
+    Line { rva: u32, filename: &'a str, line: u32 },
+
+    /// The events which follow come from the COFF symbols of the debug
+    /// directory at `index` in [`DbgFile::debug_directories`]
+    Directory { index: usize },
+}
+
+/// Parse a debug file from `reader`, invoking `callback` on each function,
+/// global, and line as it is decoded
+///
+/// Unlike [`DbgFile::load`] this never builds the function, global, and line
+/// maps, so one-pass consumers of huge files don't pay for them. Events come
+/// out in symbol table order, not address order, and repeated addresses are
+/// not deduplicated.
+pub fn parse_with(reader: impl Read + Seek,
+        mut callback: impl FnMut(Event)) -> Result<()> {
+    DbgFile::load_events(reader, &LoadOptions::default(), &mut callback)?;
+    Ok(())
+}
+
+/// Find plausible `DI` files embedded in `data`, such as a raw disk image or
+/// memory dump
+///
+/// Every `DI` signature is checked for a sane `IMAGE_SEPARATE_DEBUG_HEADER`
+/// (a known machine, a reasonable number of sections, a power of two section
+/// alignment) followed by debug directories of known types whose data lies
+/// inside of `data`. Returns the range of `data` covered by each hit, which
+/// may be parsed as a file of its own. Hits don't overlap.
+pub fn carve(data: &[u8]) -> Vec<Range<usize>> {
+    let mut ret = Vec::new();
+    let mut offset = 0;
+    while let Some(pos) = data.get(offset..)
+            .and_then(|x| x.windows(2).position(|x| x == b"DI")) {
+        let start = offset + pos;
+        match carve_at(data, start) {
+            Some(end) => {
+                ret.push(start..end);
+                offset = end;
+            }
+            None => offset = start + 1,
+        }
+    }
+    ret
+}
+
+/// Check for a plausible `DI` file at `start` of `data`, returning the end
+/// of the data it covers, used internally
+fn carve_at(data: &[u8], start: usize) -> Option<usize> {
+    // Unwraps are fine as the slice sizes are checked by `get`
+    let u16_at = |offset: usize| data.get(start + offset..start + offset + 2)
+        .map(|x| u16::from_le_bytes(x.try_into().unwrap()));
+    let u32_at = |offset: usize| data.get(start + offset..start + offset + 4)
+        .map(|x| u32::from_le_bytes(x.try_into().unwrap()));
+
+    // `IMAGE_SEPARATE_DEBUG_HEADER`, only the mismatch flag is defined
+    let flags           = u16_at(2)?;
+    let machine         = Machine::from(u16_at(4)?);
+    let num_sections    = u32_at(24)? as usize;
+    let exported_namesz = u32_at(28)? as usize;
+    let debug_dirsz     = u32_at(32)? as usize;
+    let section_align   = u32_at(36)?;
+    if flags & !0x8000 != 0 ||
+            matches!(machine, Machine::Unknown | Machine::Other(_)) ||
+            !(1..=96).contains(&num_sections) ||
+            !section_align.is_power_of_two() ||
+            debug_dirsz == 0 || debug_dirsz > 16 * DebugDirectory::SIZE ||
+            !debug_dirsz.is_multiple_of(DebugDirectory::SIZE) {
+        return None;
+    }
+
+    // The section headers, exported names, and debug directories follow
+    let dirs = 48usize.checked_add(num_sections * 40)?
+        .checked_add(exported_namesz)?;
+    let mut end = dirs + debug_dirsz;
+    if start.checked_add(end)? > data.len() {
+        return None;
+    }
+
+    // Each debug directory must be of a known type with its data in bounds
+    for dir in (dirs..dirs + debug_dirsz).step_by(DebugDirectory::SIZE) {
+        let typ  = DebugType::from(u32_at(dir + 12)?);
+        let size = u32_at(dir + 16)? as usize;
+        let ptr  = u32_at(dir + 24)? as usize;
+        let dir_end = ptr.checked_add(size)?;
+        if matches!(typ, DebugType::Other(_)) {
+            return None;
+        }
+        if size == 0 {
+            continue;
+        }
+        if ptr < dirs + debug_dirsz ||
+                start.checked_add(dir_end)? > data.len() {
+            return None;
+        }
+        end = end.max(dir_end);
+    }
+
+    Some(start + end)
+}
+
+/// Extent of a function, recovered from its `.bf` and `.ef` records
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FunctionInfo {
+    /// RVA of the first byte of the function (inclusive)
+    pub start: u32,
+
+    /// RVA of the end of the function (exclusive)
+    pub end: u32,
+
+    /// Source line number of the start of the function
+    pub first_line: u32,
+}
+
+/// How names which aren't valid UTF-8 are decoded. Some old tools wrote
+/// names in the OEM or ANSI code page of the build machine.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NameDecoding {
+    /// Replace each invalid sequence with U+FFFD
+    #[default]
+    Lossy,
+
+    /// Replace each invalid byte with `?`, keeping names ASCII where the
+    /// code page bytes were
+    Replace,
+
+    /// Fail with an error naming the kind of name
+    Error,
+}
+
+impl NameDecoding {
+    /// Decode `name`, failing with `err` if it's invalid and decoding is
+    /// [`NameDecoding::Error`]
+    fn decode(self, name: &[u8], err: fn(core::str::Utf8Error) -> Error)
+            -> Result<Cow<'_, str>> {
+        let invalid = match core::str::from_utf8(name) {
+            Ok(name) => return Ok(Cow::Borrowed(name)),
+            Err(invalid) => invalid,
+        };
+        match self {
+            Self::Lossy => Ok(String::from_utf8_lossy(name)),
+            Self::Replace => {
+                let mut ret = String::new();
+                for chunk in name.utf8_chunks() {
+                    ret.push_str(chunk.valid());
+                    ret.extend(chunk.invalid().iter().map(|_| '?'));
+                }
+                Ok(Cow::Owned(ret))
+            }
+            Self::Error => Err(err(invalid)),
+        }
+    }
+}
+
+/// Options controlling how a `.dbg` file is parsed
+#[derive(Debug, Clone)]
+pub struct LoadOptions {
+    /// Record recoverable problems (such as unknown symbol classes and bad
+    /// string table references) as diagnostics and keep parsing, rather than
+    /// failing the whole file
+    pub lenient: bool,
+
+    /// Only parse the header, sections, and debug directories up front. The
+    /// COFF symbols and lines are kept unparsed and decoded on first use by
+    /// any of the symbol or line accessors.
+    ///
+    /// The accessors can't fail, so an error parsing the symbols, including
+    /// one `strict` asks for, is recorded as a diagnostic and the symbols
+    /// parsed before it are kept.
+    pub lazy: bool,
+
+    /// If the file is cut short, keep everything parsed up to that point and
+    /// record the truncation as a diagnostic rather than failing
+    pub partial: bool,
+
+    /// Fail on symbols and lines whose RVAs are outside of the image, rather
+    /// than recording them as diagnostics. These are a common sign of a
+    /// symbol file which doesn't match its header, or is corrupt. A lazy
+    /// load can't fail after the fact, see `lazy`.
+    pub strict: bool,
+
+    /// How symbol, source file, exported, and section names which aren't
+    /// valid UTF-8 are decoded
+    pub names: NameDecoding,
+
+    /// Maximum number of COFF symbol table entries to accept
+    pub max_symbols: u32,
+
+    /// Maximum size of the COFF string table to accept, in bytes
+    pub max_string_table: u32,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self {
+            lenient:          false,
+            lazy:             false,
+            partial:          false,
+            strict:           false,
+            names:            NameDecoding::Lossy,
+            max_symbols:      16 * 1024 * 1024,
+            max_string_table: 256 * 1024 * 1024,
+        }
+    }
+}
+
+/// Chainable setters for the options, eg.
+/// `DbgFile::open_with(path, LoadOptions::default().lenient(true).lazy(true))`
+impl LoadOptions {
+    /// Set [`LoadOptions::lenient`]
+    pub fn lenient(&mut self, lenient: bool) -> &mut Self {
+        self.lenient = lenient;
+        self
+    }
+
+    /// Set [`LoadOptions::lazy`]
+    pub fn lazy(&mut self, lazy: bool) -> &mut Self {
+        self.lazy = lazy;
+        self
+    }
+
+    /// Set [`LoadOptions::partial`]
+    pub fn partial(&mut self, partial: bool) -> &mut Self {
+        self.partial = partial;
+        self
+    }
+
+    /// Set [`LoadOptions::strict`]
+    pub fn strict(&mut self, strict: bool) -> &mut Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Set [`LoadOptions::names`]
+    pub fn names(&mut self, names: NameDecoding) -> &mut Self {
+        self.names = names;
+        self
+    }
+
+    /// Set [`LoadOptions::max_symbols`]
+    pub fn max_symbols(&mut self, max_symbols: u32) -> &mut Self {
+        self.max_symbols = max_symbols;
+        self
+    }
+
+    /// Set [`LoadOptions::max_string_table`]
+    pub fn max_string_table(&mut self, max_string_table: u32) -> &mut Self {
+        self.max_string_table = max_string_table;
+        self
+    }
+}
+
+/// Make sure `size` bytes for `field` are no more than `limit` and fit in the
+/// rest of `reader`, so corrupt sizes don't turn into huge allocations
+fn check_size(reader: &mut (impl Read + Seek), field: &'static str,
+        size: u64, limit: u64) -> Result<()> {
+    let seek_err = |x| Error::Consume(field, 0, x);
+    let pos = reader.stream_position().map_err(seek_err)?;
+    let len = reader.seek(SeekFrom::End(0)).map_err(seek_err)?;
+    reader.seek(SeekFrom::Start(pos)).map_err(seek_err)?;
+
+    if size > limit {
+        return Err(Error::TooLarge(field, size));
+    }
+    if size > len.saturating_sub(pos) {
+        return Err(Error::Truncated(field, size));
+    }
+
+    Ok(())
+}
+
+/// Table of [`SymbolTables`] a record is in, used internally
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Table {
+    /// `functions`
+    Function,
+
+    /// `globals`
+    Global,
+
+    /// `addr_to_line`
+    Line,
+}
+
+/// Functions, globals, and lines collected from [`Event`]s, used internally
+///
+/// When there are several COFF directories, the first one to give a record at
+/// an RVA keeps it. Within a directory the last record at an RVA wins.
+#[derive(Default)]
+struct SymbolTables {
+    /// Mapping from RVA to (filename, line number)
+    addr_to_line: BTreeMap<u32, (Arc<str>, u32)>,
+
+    /// Mapping from RVA to function name
+    functions: BTreeMap<u32, String>,
+
+    /// Mapping from RVA to global name
+    globals: BTreeMap<u32, String>,
+
+    /// Every source filename seen, so each is only stored once no matter how
+    /// many lines refer to it
+    filenames: BTreeSet<Arc<str>>,
+
+    /// Index of the debug directory the events are currently coming from
+    directory: usize,
+
+    /// Index of the first COFF debug directory
+    first_directory: Option<usize>,
+
+    /// Index of the debug directory of each record which didn't come from
+    /// the first COFF directory, so the common single directory file pays
+    /// nothing for it
+    origins: BTreeMap<(Table, u32), usize>,
+}
+
+impl SymbolTables {
+    /// Store a single `event` into the function, global, and line maps
+    fn collect(&mut self, event: Event) {
+        match event {
+            Event::Function { rva, name } => {
+                if self.claim(Table::Function, rva) {
+                    self.functions.insert(rva, name.to_string());
+                }
+            }
+            Event::Global { rva, name } => {
+                if self.claim(Table::Global, rva) {
+                    self.globals.insert(rva, name.to_string());
+                }
+            }
+            Event::Line { rva, filename, line } => {
+                if !self.claim(Table::Line, rva) {
+                    return;
+                }
+
+                let filename = match self.filenames.get(filename) {
+                    Some(filename) => filename.clone(),
+                    None => {
+                        let filename: Arc<str> = filename.into();
+                        self.filenames.insert(filename.clone());
+                        filename
+                    }
+                };
+                self.addr_to_line.insert(rva, (filename, line));
+            }
+            Event::Directory { index } => {
+                self.directory = index;
+                self.first_directory.get_or_insert(index);
+            }
+        }
+    }
+
+    /// Get the index of the debug directory the record in `table` at `rva`
+    /// came from, if there is one
+    fn origin(&self, table: Table, rva: u32) -> Option<usize> {
+        let exists = match table {
+            Table::Function => self.functions.contains_key(&rva),
+            Table::Global   => self.globals.contains_key(&rva),
+            Table::Line     => self.addr_to_line.contains_key(&rva),
+        };
+        if !exists {
+            return None;
+        }
+        self.origins.get(&(table, rva)).copied().or(self.first_directory)
+    }
+
+    /// Check if a record in `table` at `rva` from the current directory
+    /// should be stored, noting where it came from if so
+    fn claim(&mut self, table: Table, rva: u32) -> bool {
+        if self.origin(table, rva).is_some_and(|x| x != self.directory) {
+            return false;
+        }
+        if Some(self.directory) != self.first_directory {
+            self.origins.insert((table, rva), self.directory);
+        }
+        true
+    }
+}
+
+/// Information decoded from the COFF symbol table, used internally
+#[derive(Default)]
+struct Coff {
+    /// Functions, globals, and lines
+    tables: SymbolTables,
+
+    /// Mapping from function start RVA to its extent
+    function_info: BTreeMap<u32, FunctionInfo>,
+
+    /// Mapping from alias (weak external) name to the name and RVA of the
+    /// symbol it resolves to
+    aliases: BTreeMap<String, (String, u32)>,
+
+    /// Recoverable problems which were skipped over during a lenient parse
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Windows NT `.dbg` file parser
+#[derive(Default)]
+pub struct DbgFile {
+    /// Header describing the image
+    header: Header,
+
+    /// Sections of the image
+    sections: Vec<Section>,
+
+    /// Debug directories in the file
+    debug_directories: Vec<DebugDirectory>,
+
+    /// Names exported by the image
+    exported_names: Vec<String>,
+
+    /// Symbols and lines, filled in at load time or on first use for a lazy
+    /// load
+    coff: OnceLock<Coff>,
+
+    /// Problems skipped over while loading everything but the symbols
+    load_diagnostics: Vec<Diagnostic>,
+
+    /// Raw COFF debug information waiting to be parsed by a lazy load, along
+    /// with its offset in the file
+    pending_coff: Vec<(usize, u64, Vec<u8>)>,
+
+    /// Options the file was loaded with
+    options: LoadOptions,
+
+    /// Mapping from function start RVA to end RVA (exclusive) from the
+    /// exception information function table
+    exception_ranges: BTreeMap<u32, u32>,
+
+    /// File name of the image from the first miscellaneous debug
+    /// information
+    image_name: Option<String>,
+
+    /// Name of the module set with [`DbgFile::set_module_name`], overriding
+    /// the one from the image name
+    module_name: Option<String>,
+
+    /// RVAs of the first and last bytes of code from the first COFF symbols
+    /// header, read up front even for a lazy load
+    code_range: Option<RangeInclusive<u32>>,
+
+    /// RVAs of the first and last bytes of data from the first COFF symbols
+    /// header
+    data_range: Option<RangeInclusive<u32>>,
+
+    /// Mapping from symbol name to (RVA, kind), built on first use by
+    /// [`DbgFile::find_symbol`]
+    name_to_addr: OnceLock<BTreeMap<String, Vec<(u32, SymbolKind)>>>,
+}
+
+impl DbgFile {
+    /// Get the header describing the image
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Get the debug directories in the file, in the order of the directory
+    /// table and including those of types which aren't parsed. The data of
+    /// each can be read from the file with [`DebugDirectory::data`].
+    pub fn debug_directories(&self) -> &[DebugDirectory] {
+        &self.debug_directories
+    }
+
+    /// Get the first debug directory of type `typ`, eg. to find the CodeView
+    /// or FPO data of the file
+    pub fn debug_directory(&self, typ: DebugType) -> Option<&DebugDirectory> {
+        self.debug_directories.iter().find(|x| x.typ == typ)
+    }
+
+    /// Get the sections of the image
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    /// Get the names exported by the image
+    pub fn exported_names(&self) -> &[String] {
+        &self.exported_names
+    }
+
+    /// Get the file name of the image, eg. `ntoskrnl.exe`, from the
+    /// miscellaneous debug information. Returns `None` if the file has none.
+    pub fn image_name(&self) -> Option<&str> {
+        self.image_name.as_deref()
+    }
+
+    /// Get the name of the module, as used by debuggers to qualify symbols
+    /// like `ntoskrnl!KeBugCheckEx`
+    ///
+    /// This is the name set with [`DbgFile::set_module_name`], otherwise the
+    /// image name without its directory and extension. Returns `None` if the
+    /// file has neither.
+    pub fn module_name(&self) -> Option<&str> {
+        self.module_name.as_deref()
+            .or_else(|| self.image_name().map(module_stem))
+    }
+
+    /// Set the name of the module, eg. from the name of the `.dbg` file when
+    /// the image name is missing or renamed
+    pub fn set_module_name(&mut self, name: &str) {
+        self.module_name = Some(name.into());
+    }
+
+    /// Get the section containing `rva`
+    pub fn section_for_rva(&self, rva: u32) -> Option<&Section> {
+        self.sections.iter().find(|section| section.contains(rva))
+    }
+
+    /// Translate `rva` into an offset in the original image file
+    ///
+    /// Returns `None` if `rva` isn't backed by raw data in any section
+    pub fn rva_to_file_offset(&self, rva: u32) -> Option<u32> {
+        self.sections.iter().find_map(|section| {
+            let offset = rva.wrapping_sub(section.vaddr);
+            (offset < section.raw_data_sz)
+                .then(|| section.ptr_raw_data.checked_add(offset))?
+        })
+    }
+
+    /// Translate `offset` in the original image file into an RVA
+    ///
+    /// Returns `None` if `offset` isn't in the raw data of any section
+    pub fn file_offset_to_rva(&self, offset: u32) -> Option<u32> {
+        self.sections.iter().find_map(|section| {
+            let delta = offset.wrapping_sub(section.ptr_raw_data);
+            (delta < section.raw_data_sz)
+                .then(|| section.vaddr.checked_add(delta))?
+        })
+    }
+
+    /// Get the category of the section containing `rva`, useful for telling
+    /// apart code, writable data, and read-only globals
+    pub fn category_for_rva(&self, rva: u32) -> Option<SectionCategory> {
+        self.section_for_rva(rva).map(Section::category)
+    }
+
+    /// Get the mapping from RVA to (filename, line number)
+    ///
+    /// Filenames are shared between all lines of the same file
+    pub fn lines(&self) -> &BTreeMap<u32, (Arc<str>, u32)> {
+        &self.coff().tables.addr_to_line
+    }
+
+    /// Get the mapping from RVA to function name
+    pub fn functions(&self) -> &BTreeMap<u32, String> {
+        &self.coff().tables.functions
+    }
+
+    /// Get the mapping from RVA to global name
+    pub fn globals(&self) -> &BTreeMap<u32, String> {
+        &self.coff().tables.globals
+    }
+
+    /// Get the mapping from alias (weak external) name to the name and RVA
+    /// of the symbol it resolves to
+    pub fn aliases(&self) -> &BTreeMap<String, (String, u32)> {
+        &self.coff().aliases
+    }
+
+    /// Get the mapping from function start RVA to its extent
+    ///
+    /// This only contains functions which had `.bf` and `.ef` records
+    pub fn function_info(&self) -> &BTreeMap<u32, FunctionInfo> {
+        &self.coff().function_info
+    }
+
+    /// Get the RVAs of the first and last bytes of code in the image, from
+    /// the COFF symbols header
+    ///
+    /// Returns `None` if there are no COFF symbols or the header gives an
+    /// empty range. With several COFF directories the first one wins.
+    pub fn code_range(&self) -> Option<RangeInclusive<u32>> {
+        self.code_range.clone()
+    }
+
+    /// Get the RVAs of the first and last bytes of data in the image, from
+    /// the COFF symbols header, see [`DbgFile::code_range`]
+    pub fn data_range(&self) -> Option<RangeInclusive<u32>> {
+        self.data_range.clone()
+    }
+
+    /// Get the index into [`DbgFile::debug_directories`] of the COFF
+    /// directory the `kind` symbol at `rva` came from, or `None` if there's
+    /// no such symbol
+    ///
+    /// Files occasionally have more than one COFF directory. The first one
+    /// to give a symbol or line at an RVA keeps it, later ones don't
+    /// overwrite it.
+    pub fn symbol_directory(&self, kind: SymbolKind, rva: u32)
+            -> Option<usize> {
+        let table = match kind {
+            SymbolKind::Function => Table::Function,
+            SymbolKind::Global   => Table::Global,
+        };
+        self.coff().tables.origin(table, rva)
+    }
+
+    /// Get the index into [`DbgFile::debug_directories`] of the COFF
+    /// directory the line at `rva` came from, see
+    /// [`DbgFile::symbol_directory`]
+    pub fn line_directory(&self, rva: u32) -> Option<usize> {
+        self.coff().tables.origin(Table::Line, rva)
+    }
+
+    /// Get the size of the function starting at `rva`, in bytes
+    ///
+    /// The size comes from the `.ef` record of the function if present, then
+    /// from the exception information function table, and finally falls back
+    /// to the distance to the next function or global. Returns `None` if
+    /// there is no function at `rva` or it's the last symbol.
+    pub fn function_size(&self, rva: u32) -> Option<u32> {
+        if !self.functions().contains_key(&rva) {
+            return None;
+        }
+
+        if let Some(info) = self.function_info().get(&rva) {
+            return Some(info.end - info.start);
+        }
+
+        if let Some(&end) = self.exception_ranges.get(&rva) {
+            return Some(end - rva);
+        }
+
+        // Find the next symbol after this one
+        let next = rva.checked_add(1)?;
+        self.symbols_in_range(next..).next().map(|(next, _, _)| next - rva)
+    }
+
+    /// Get the non-fatal problems found while parsing, such as unknown
+    /// symbol classes and bad string table references skipped over by a
+    /// lenient parse, truncation in a partial parse, and odd sizes
+    ///
+    /// For a lazy load this also holds any error hit while parsing the
+    /// symbols on first use
+    pub fn diagnostics(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.load_diagnostics.iter().chain(self.coff().diagnostics.iter())
+    }
+
+    /// Get the non-fatal problems found outside of the symbols and lines,
+    /// which doesn't parse the symbols of a lazy load
+    pub fn load_diagnostics(&self) -> &[Diagnostic] {
+        &self.load_diagnostics
+    }
+
+    /// Turn a truncation error in `result` into a diagnostic for a partial
+    /// load, used internally
+    fn recover(&mut self, reader: &mut impl Seek, opts: &LoadOptions,
+            result: Result<()>) -> Result<()> {
+        match result {
+            Err(error) if opts.partial && error.is_truncation() => {
+                let offset = reader.stream_position().unwrap_or(0);
+                self.load_diagnostics.push(Diagnostic { offset, error });
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// Get the symbols and lines, parsing them first for a lazy load
+    fn coff(&self) -> &Coff {
+        self.coff.get_or_init(|| {
+            let _span = trace_span!("lazy symbols");
+            let mut coff = Coff::default();
+            for (index, offset, data) in self.pending_coff.iter() {
+                coff.tables.collect(Event::Directory { index: *index });
+                let first = coff.diagnostics.len();
+                let mut reader = Cursor::new(&data[..]);
+                if let Err(error) =
+                        coff.parse_collect(&mut reader, 0, &self.options,
+                            &self.header, &self.sections) {
+                    coff.diagnostics.push(Diagnostic {
+                        offset: reader.position(),
+                        error,
+                    });
+                }
+
+                // Offsets are relative to the stashed data
+                for diag in &mut coff.diagnostics[first..] {
+                    diag.offset += offset;
+                    if let Error::Consume(_, pos, _) = &mut diag.error {
+                        *pos += offset;
+                    }
+                }
+            }
+            coff
+        })
+    }
+
+    /// Find the closest function or global at or before `rva`
+    ///
+    /// Returns the name of the symbol and the offset of `rva` from the start
+    /// of it. If a function and a global share an address, the function is
+    /// preferred.
+    pub fn nearest_symbol(&self, rva: u32) -> Option<(&str, u32)> {
+        // Find the closest preceding entry in each map
+        let func = self.functions().range(..=rva).next_back();
+        let glob = self.globals().range(..=rva).next_back();
+
+        // Pick whichever is closer to `rva`
+        let (addr, name) = match (func, glob) {
+            (Some(func), Some(glob)) => {
+                if glob.0 > func.0 { glob } else { func }
+            }
+            (Some(func), None) => func,
+            (None, Some(glob)) => glob,
+            (None, None) => return None,
+        };
+
+        Some((name.as_str(), rva - addr))
+    }
+
+    /// Get all functions and globals with RVAs in `range`
+    ///
+    /// Symbols are yielded in address order as (RVA, kind, name). If a
+    /// function and a global share an address, the function comes first.
+    pub fn symbols_in_range(&self, range: impl RangeBounds<u32> + Clone)
+            -> impl Iterator<Item = (u32, SymbolKind, &str)> {
+        let mut funcs = self.functions().range(range.clone()).peekable();
+        let mut globs = self.globals().range(range).peekable();
+
+        // Merge the two sorted ranges
+        core::iter::from_fn(move || {
+            let take_func = match (funcs.peek(), globs.peek()) {
+                (Some(func), Some(glob)) => func.0 <= glob.0,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => return None,
+            };
+
+            if take_func {
+                funcs.next().map(|(rva, name)| {
+                    (*rva, SymbolKind::Function, name.as_str())
+                })
+            } else {
+                globs.next().map(|(rva, name)| {
+                    (*rva, SymbolKind::Global, name.as_str())
+                })
+            }
+        })
+    }
+
+    /// Build a compact [`SymbolMap`] of all functions and globals. If a
+    /// function and a global share an address, the function comes first.
+    pub fn symbol_map(&self) -> SymbolMap {
+        let mut ret = self.symbols_in_range(..)
+            .map(|(rva, kind, name)| (rva, name, kind))
+            .collect::<SymbolMap>();
+        ret.shrink_to_fit();
+        ret
+    }
+
+    /// Find all functions and globals named exactly `name`
+    ///
+    /// Returns the (RVA, kind) of each match in address order. The name index
+    /// is built on the first call, so later lookups are cheap.
+    pub fn find_symbol(&self, name: &str) -> &[(u32, SymbolKind)] {
+        let index = self.name_to_addr.get_or_init(|| {
+            let mut index: BTreeMap<String, Vec<(u32, SymbolKind)>> =
+                BTreeMap::new();
+
+            for (rva, kind, name) in self.symbols_in_range(..) {
+                index.entry(name.to_string()).or_default().push((rva, kind));
+            }
+
+            index
+        });
+
+        index.get(name).map(|x| x.as_slice()).unwrap_or(&[])
+    }
+
+    /// Get all source lines with RVAs in `range`
+    ///
+    /// Lines are yielded in address order as (RVA, filename, line number)
+    pub fn lines_in_range(&self, range: impl RangeBounds<u32>)
+            -> impl Iterator<Item = (u32, &str, u32)> {
+        self.lines().range(range).map(|(rva, (source, line))| {
+            (*rva, source.as_ref(), *line)
+        })
+    }
+
+    /// Get all source lines grouped by filename
+    ///
+    /// Each filename maps to its (line number, RVA) pairs, sorted by line
+    /// number and then by RVA
+    pub fn lines_by_file(&self) -> BTreeMap<&str, Vec<(u32, u32)>> {
+        let mut ret: BTreeMap<&str, Vec<(u32, u32)>> = BTreeMap::new();
+        for (rva, (source, line)) in self.lines().iter() {
+            ret.entry(source.as_ref()).or_default().push((*line, *rva));
+        }
+
+        // Sort each file by line number
+        for lines in ret.values_mut() {
+            lines.sort_unstable();
+        }
+
+        ret
+    }
+
+    /// Get all source lines in `source` with line numbers in `lines`
+    ///
+    /// `source` matches a filename either exactly or as a trailing path
+    /// component (`write.c` matches `D:\nt\write.c`), ignoring case as DOS
+    /// paths are case-insensitive. Lines are yielded in address order as
+    /// (RVA, filename, line number).
+    pub fn line_addresses<'a>(&'a self, source: &'a str,
+            lines: impl RangeBounds<u32> + 'a)
+            -> impl Iterator<Item = (u32, &'a str, u32)> + 'a {
+        self.lines_in_range(..).filter(move |(_, filename, line)| {
+            lines.contains(line) && source_matches(filename, source)
+        })
+    }
+
+    /// Parse a debug file which is already entirely in memory
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        Self::parse_with_options(bytes, &LoadOptions::default())
+    }
+
+    /// Parse a debug file which is already entirely in memory using `opts`
+    pub fn parse_with_options(bytes: &[u8], opts: &LoadOptions)
+            -> Result<Self> {
+        Self::load_with(Cursor::new(bytes), opts)
+    }
+
+    /// Map the debug file at `path` into memory and parse it using `opts`
+    #[cfg(feature = "mmap")]
+    pub fn load_mmap(path: impl AsRef<std::path::Path>, opts: &LoadOptions)
+            -> Result<Self> {
+        let path = path.as_ref();
+        let fd = std::fs::File::open(path)
+            .map_err(|x| Error::Open(path.to_path_buf(), x))?;
+
+        // Safety: the mapping only lives for the duration of the parse and
+        // nothing borrows from it afterwards. The file must not be truncated
+        // or modified by someone else while we parse it, which is the usual
+        // caveat of mapping files and is fine for read-only symbol stores
+        let map = unsafe { memmap2::Mmap::map(&fd) }
+            .map_err(|x| Error::Open(path.to_path_buf(), x))?;
+
+        Self::parse_with_options(&map, opts)
+    }
+
+    /// Open and parse the debug file at `path`
+    #[cfg(feature = "std")]
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        Self::open_with(path, &LoadOptions::default())
+    }
+
+    /// Open and parse the debug file at `path` using `opts`
+    ///
+    /// Files of up to [`MAX_READ_WHOLE`] bytes are read into memory at once
+    /// and parsed from there, larger ones are read as they're parsed.
+    #[cfg(feature = "std")]
+    pub fn open_with(path: impl AsRef<std::path::Path>, opts: &LoadOptions)
+            -> Result<Self> {
+        let path = path.as_ref();
+        let open_err = |x| Error::Open(path.to_path_buf(), x);
+        let fd = std::fs::File::open(path).map_err(open_err)?;
+        let len = fd.metadata().map_err(open_err)?.len();
+        if len <= MAX_READ_WHOLE {
+            let mut data = Vec::with_capacity(len as usize);
+            std::io::Read::read_to_end(&mut &fd, &mut data)
+                .map_err(open_err)?;
+            return Self::parse_with_options(&data, opts);
+        }
+        Self::load_with(std::io::BufReader::new(fd), opts)
+    }
+
+    /// Parse a debug file from `reader`
+    pub fn load(reader: impl Read + Seek) -> Result<Self> {
+        Self::load_with(reader, &LoadOptions::default())
+    }
+
+    /// Parse a debug file from `reader` using `opts`
+    pub fn load_with(reader: impl Read + Seek, opts: &LoadOptions)
+            -> Result<Self> {
+        let mut tables = SymbolTables::default();
+        let mut ret = Self::load_events(reader, opts, &mut |event| {
+            tables.collect(event);
+        })?;
+
+        // Lazy loads fill these in on first use instead
+        if let Some(coff) = ret.coff.get_mut() {
+            coff.tables = tables;
+        }
+        Ok(ret)
+    }
+
+    /// Parse a debug file, used internally
+    ///
+    /// Functions, globals, and lines are passed to `sink` rather than being
+    /// stored in the returned `Self`
+    fn load_events(mut reader: impl Read + Seek, opts: &LoadOptions,
+            sink: &mut impl FnMut(Event)) -> Result<Self> {
+        let _span = trace_span!("load");
+
+        // Make sure it's a debug info file
+        if &consume!(reader, 2, "header")? != b"DI" {
+            return Err(Error::NotDebugInfo);
+        }
+
+        // `IMAGE_SEPARATE_DEBUG_HEADER`
+        let flags            = consume!(reader, u16, "flags")?;
+        let machine: Machine = consume!(reader, u16, "machine")?.into();
+        let characteristics  = consume!(reader, u16, "characteristics")?;
+        let timedatestamp    = consume!(reader, u32, "timedatestamp")?;
+        let checksum         = consume!(reader, u32, "checksum")?;
+        let image_base       = consume!(reader, u32, "image base")?;
+        let size_of_image    = consume!(reader, u32, "size of image")?;
+        let num_sections     = consume!(reader, u32, "number of sections")?;
+        let exported_namesz  = consume!(reader, u32, "exported names size")?;
+        let debug_dirsz      = consume!(reader, u32, "debug directory size")?;
+        let section_align    = consume!(reader, u32, "section alignment")?;
+        let _reserved        = consume!(reader, 8,   "reserved")?;
+        trace_event!(?machine, timedatestamp, num_sections, exported_namesz,
+            debug_dirsz, "parsed header");
+
+        // Create return `Self`
+        let mut ret = Self {
+            header: Header {
+                flags,
+                machine,
+                characteristics,
+                timedatestamp,
+                checksum,
+                image_base,
+                size_of_image,
+                section_align,
+            },
+            options: opts.clone(),
+            ..Default::default()
+        };
+
+        // COFF information which is parsed up front
+        let mut coff = Coff::default();
+
+        // Read the tables following the header, for a partial load a
+        // truncated file stops us at the point it was cut off
+        let tables = (|| {
+            // Read each `IMAGE_SECTION_HEADER`
+            for _ in 0..num_sections {
+                // Read the section header
+                let sh = SectionHeader {
+                    name:            consume!(reader, 8,   "name")?,
+                    vsize:           consume!(reader, u32, "vsize")?,
+                    vaddr:           consume!(reader, u32, "vaddr")?,
+                    raw_data_sz:     consume!(reader, u32, "raw_data_sz")?,
+                    ptr_raw_data:    consume!(reader, u32, "ptr_raw_data")?,
+                    ptr_relocation:  consume!(reader, u32, "ptr_relocation")?,
+                    ptr_line_num:    consume!(reader, u32, "ptr_line_num")?,
+                    num_relocs:      consume!(reader, u16, "num_relocs")?,
+                    num_line_num:    consume!(reader, u16, "num_line_num")?,
+                    characteristics: consume!(reader, u32, "characteristics")?,
+                };
+
+                ret.sections.push(Section::from_header(&sh, opts.names)?);
+            }
+
+            // Read the exported names, a list of null-terminated strings
+            check_size(&mut reader, "exported names size",
+                exported_namesz as u64, u64::MAX)?;
+            let mut exported_names = vec![0u8; exported_namesz as usize];
+            read_field(&mut reader, &mut exported_names, "exported names")?;
+            for name in exported_names.split(|x| *x == 0) {
+                if !name.is_empty() {
+                    ret.exported_names.push(opts.names.decode(name,
+                        Error::ExportedNameUtf8)?.into_owned());
+                }
+            }
+
+            // Any trailing partial directory is ignored
+            if !(debug_dirsz as usize).is_multiple_of(DebugDirectory::SIZE) {
+                ret.load_diagnostics.push(Diagnostic {
+                    offset: reader.stream_position()
+                        .map_err(|x| Error::Consume("debug directory", 0, x))?,
+                    error:  Error::Suspicious("debug directory size",
+                        debug_dirsz as u64),
+                });
+            }
+
+            // Read each `IMAGE_DEBUG_DIRECTORY`
+            for _ in 0..debug_dirsz as usize / DebugDirectory::SIZE {
+                // Read the section header
+                let dd = DebugDirectory {
+                    characteristics: consume!(reader, u32, "characteristics")?,
+                    timedatestamp:   consume!(reader, u32, "timedatestamp")?,
+                    major_version:   consume!(reader, u16, "major_version")?,
+                    minor_version:   consume!(reader, u16, "minor_version")?,
+                    typ:             consume!(reader, u32, "typ")?.into(),
+                    size_of_data:    consume!(reader, u32, "size_of_data")?,
+                    addr_raw_data:   consume!(reader, u32, "addr_raw_data")?,
+                    ptr_raw_data:    consume!(reader, u32, "ptr_raw_data")?,
+                };
+                let _span = trace_span!("debug directory", typ = ?dd.typ,
+                    size = dd.size_of_data, offset = dd.ptr_raw_data);
+
+                // The parsers below seek to the data of the directory, and
+                // an error may leave the reader anywhere in it
+                let next = reader.stream_position()
+                    .map_err(|x| Error::Consume("debug directory", 0, x))?;
+
+                // Currently we only handle COFF and exception information
+                let parsed = if matches!(dd.typ, DebugType::Coff) {
+                    let names = ret.resolve_section_names(&mut reader,
+                        dd.ptr_raw_data as u64, opts);
+                    ret.recover(&mut reader, opts, names)?;
+
+                    // Problems with the header are reported by the full parse
+                    let _ = ret.read_coff_ranges(&mut reader,
+                        dd.ptr_raw_data as u64);
+
+                    let index = ret.debug_directories.len();
+                    if opts.lazy {
+                        // Stash the COFF debug information for later
+                        read_coff_at(&mut reader, dd.ptr_raw_data as u64,
+                                dd.size_of_data, opts)
+                            .map(|data| ret.pending_coff.push(
+                                (index, dd.ptr_raw_data as u64, data)))
+                    } else {
+                        // Parse COFF debug information
+                        sink(Event::Directory { index });
+                        coff.parse(&mut reader, dd.ptr_raw_data as u64, opts,
+                            &ret.header, &ret.sections, sink)
+                    }
+                } else if matches!(dd.typ, DebugType::Exception) {
+                    // Parse function table
+                    ret.parse_exception(&mut reader, dd.ptr_raw_data as u64,
+                        dd.size_of_data)
+                } else if matches!(dd.typ, DebugType::Misc) {
+                    // Parse the name of the image
+                    ret.parse_misc(&mut reader, dd.ptr_raw_data as u64,
+                        dd.size_of_data, opts)
+                } else {
+                    Ok(())
+                };
+
+                // The data of one directory being cut short doesn't stop us
+                // from looking at the others
+                ret.recover(&mut reader, opts, parsed)?;
+                reader.seek(SeekFrom::Start(next))
+                    .map_err(|x| Error::Consume("debug directory", next, x))?;
+
+                ret.debug_directories.push(dd);
+            }
+
+            Ok(())
+        })();
+        ret.recover(&mut reader, opts, tables)?;
+
+        if !opts.lazy {
+            ret.coff = OnceLock::from(coff);
+        }
+
+        Ok(ret)
+    }
+
+    /// Parse exception information, used internally
+    ///
+    /// This is a table of `IMAGE_FUNCTION_ENTRY` (or `IMAGE_FUNCTION_ENTRY64`
+    /// on 64-bit Alpha), giving the extent of each function. Updates `self`
+    /// in-place with the newly parsed information.
+    fn parse_exception(&mut self, reader: &mut (impl Read + Seek),
+            offset: u64, size: u32) -> Result<()> {
+        let _span = trace_span!("function table", offset, size);
+        let image_base = self.header.image_base as u64;
+
+        // Save current file location
+        let start = reader.stream_position().map_err(Error::SeekException)?;
+
+        // Seek to the function table
+        reader.seek(SeekFrom::Start(offset))
+            .map_err(Error::SeekException)?;
+
+        // Read the whole table, the layout may depend on its contents
+        check_size(reader, "function table size", size as u64, u64::MAX)?;
+        let mut table = vec![0u8; size as usize];
+        read_field(reader, &mut table, "function table")?;
+
+        // Parse each function entry
+        let layout = FunctionEntryLayout::detect(self.header.machine, &table);
+        if !table.len().is_multiple_of(layout.size()) {
+            self.load_diagnostics.push(Diagnostic {
+                offset,
+                error: Error::Suspicious("function table size", size as u64),
+            });
+        }
+        trace_event!(?layout, entries = table.len() / layout.size(),
+            "parsing function table");
+        for entry in table.chunks_exact(layout.size()) {
+            let (begin, end, _) = layout.parse(entry)?;
+
+            // Entries may be virtual addresses rather than RVAs
+            let (begin, end) = if begin >= image_base && image_base != 0 {
+                (begin - image_base, end.wrapping_sub(image_base))
+            } else {
+                (begin, end)
+            };
+
+            // Skip anything which isn't a valid 32-bit range
+            if let (Ok(begin), Ok(end)) =
+                    (u32::try_from(begin), u32::try_from(end)) {
+                if end > begin {
+                    self.exception_ranges.insert(begin, end);
+                }
+            }
+        }
+
+        // Seek back to where we were
+        reader.seek(SeekFrom::Start(start)).map_err(Error::SeekException)?;
+
+        Ok(())
+    }
+
+    /// Parse miscellaneous information, used internally
+    ///
+    /// This is a list of `IMAGE_DEBUG_MISC` records, of which only the first
+    /// name of the image (`IMAGE_DEBUG_MISC_EXENAME`) is kept. Each record
+    /// is the type, the length of the whole record, whether the data is
+    /// UTF-16, 3 reserved bytes, and the data.
+    fn parse_misc(&mut self, reader: &mut (impl Read + Seek), offset: u64,
+            size: u32, opts: &LoadOptions) -> Result<()> {
+        if self.image_name.is_some() {
+            return Ok(());
+        }
+        let _span = trace_span!("misc", offset, size);
+        let seek_err = |x| Error::Consume("misc data", offset, x);
+
+        // Save current file location
+        let start = reader.stream_position().map_err(seek_err)?;
+
+        reader.seek(SeekFrom::Start(offset)).map_err(seek_err)?;
+        check_size(reader, "misc data size", size as u64, u64::MAX)?;
+        let mut data = vec![0u8; size as usize];
+        read_field(reader, &mut data, "misc data")?;
+
+        // Unwraps are fine as the slice sizes are constant
+        let mut records = &data[..];
+        while records.len() >= 12 {
+            let typ = u32::from_le_bytes(records[0..4].try_into().unwrap());
+            let len = u32::from_le_bytes(records[4..8].try_into().unwrap())
+                as usize;
+            let Some(record) = records.get(12..len) else { break; };
+
+            if typ == 1 {
+                let name = if records[8] != 0 {
+                    let name = record.chunks_exact(2)
+                        .map(|x| u16::from_le_bytes(x.try_into().unwrap()))
+                        .take_while(|x| *x != 0)
+                        .collect::<Vec<_>>();
+                    String::from_utf16_lossy(&name)
+                } else {
+                    // Inside unwrap is fine, `split` always returns at least
+                    // one iterated value
+                    opts.names.decode(record.split(|x| *x == 0).next()
+                        .unwrap(), Error::ImageNameUtf8)?.into_owned()
+                };
+                self.image_name = Some(name);
+                break;
+            }
+            records = &records[len..];
+        }
+
+        // Seek back to where we were
+        reader.seek(SeekFrom::Start(start)).map_err(seek_err)?;
+
+        Ok(())
+    }
+
+    /// Read the code and data ranges from the COFF symbols header at
+    /// `coff_offset`, unless an earlier one gave them, used internally
+    fn read_coff_ranges(&mut self, reader: &mut (impl Read + Seek),
+            coff_offset: u64) -> Result<()> {
+        if self.code_range.is_some() || self.data_range.is_some() {
+            return Ok(());
+        }
+
+        // Save current file location
+        let start = reader.stream_position().map_err(Error::SeekCoff)?;
+
+        let ch = (|| {
+            reader.seek(SeekFrom::Start(coff_offset))
+                .map_err(Error::SeekCoff)?;
+            CoffSymbolsHeader::parse(reader)
+        })();
+
+        // Seek back to where we were, even if the header couldn't be read
+        reader.seek(SeekFrom::Start(start)).map_err(Error::SeekCoff)?;
+
+        let ch = ch?;
+        self.code_range = ch.code_range();
+        self.data_range = ch.data_range();
+        Ok(())
+    }
+
+    /// Resolve long section names, used internally
+    ///
+    /// These are a `/` followed by the decimal offset of the name in the
+    /// string table of the COFF debug information at `coff_offset`
+    fn resolve_section_names(&mut self, reader: &mut (impl Read + Seek),
+            coff_offset: u64, opts: &LoadOptions) -> Result<()> {
+        if !self.sections.iter().any(|x| x.name.starts_with('/')) {
+            return Ok(());
+        }
+
+        // Save current file location
+        let start = reader.stream_position().map_err(Error::SeekCoff)?;
+
+        let names = (|| {
+            // The string table follows the symbols, see `Coff::parse` for
+            // where those are
+            reader.seek(SeekFrom::Start(coff_offset))
+                .map_err(Error::SeekCoff)?;
+            let ch = CoffSymbolsHeader::parse(reader)?;
+            let header_size = size_of::<CoffSymbolsHeader>() as u64;
+            let symbols_offset = if ch.lva_first_symbol as u64 >=
+                    header_size {
+                ch.lva_first_symbol as u64
+            } else {
+                header_size + ch.num_line_nums as u64 * 6
+            };
+            let string_table_offset = coff_offset + symbols_offset +
+                ch.num_symbols as u64 * 18;
+            reader.seek(SeekFrom::Start(string_table_offset))
+                .map_err(Error::SeekCoff)?;
+
+            // Read the string table, see `Coff::parse`
+            let string_table_sz =
+                consume!(reader, u32, "string table size")?;
+            check_size(reader, "string table size", string_table_sz as u64,
+                opts.max_string_table as u64)?;
+            let mut string_table = vec![0u8; 4 + string_table_sz as usize];
+            read_field(reader, &mut string_table[4..], "string table")?;
+
+            for section in self.sections.iter_mut() {
+                let Some(offset) = section.name.strip_prefix('/')
+                        .and_then(|x| x.parse::<usize>().ok()) else {
+                    continue;
+                };
+
+                // Inside unwrap is fine, `split` always returns at least one
+                // iterated value
+                if let Some(name) = string_table.get(offset..) {
+                    section.name = opts.names.decode(
+                        name.split(|x| *x == 0).next().unwrap(),
+                        Error::SectionNameUtf8)?.into_owned();
+                }
+            }
+
+            Ok(())
+        })();
+
+        // Seek back to where we were, even if the names couldn't be read
+        reader.seek(SeekFrom::Start(start)).map_err(Error::SeekCoff)?;
+
+        names
+    }
+}
+
+/// Read the `size` bytes of COFF debug information at `offset` in `reader`,
+/// leaving the position of `reader` unchanged
+///
+/// The symbols and string table are the bulk of the data, so it may be up to
+/// twice what their limits in `opts` allow, leaving room for the header and
+/// line numbers.
+fn read_coff_at(reader: &mut (impl Read + Seek), offset: u64, size: u32,
+        opts: &LoadOptions) -> Result<Vec<u8>> {
+    let start = reader.stream_position().map_err(Error::SeekCoff)?;
+    reader.seek(SeekFrom::Start(offset)).map_err(Error::SeekCoff)?;
+
+    let limit = 2 * (opts.max_symbols as u64 * 18 +
+        opts.max_string_table as u64);
+    check_size(reader, "COFF debug information size", size as u64, limit)?;
+    let mut data = vec![0u8; size as usize];
+    reader.read_exact(&mut data).map_err(|x| {
+        Error::Consume("COFF debug information", offset, x)
+    })?;
+
+    reader.seek(SeekFrom::Start(start)).map_err(Error::SeekCoff)?;
+    Ok(data)
+}
+
+impl Coff {
+    /// Parse COFF information, storing functions, globals, and lines in
+    /// `self` too
+    fn parse_collect(&mut self, reader: &mut (impl Read + Seek),
+            coff_offset: u64, opts: &LoadOptions, header: &Header,
+            sections: &[Section]) -> Result<()> {
+        let mut tables = core::mem::take(&mut self.tables);
+        let ret = self.parse(reader, coff_offset, opts, header, sections,
+                &mut |event| {
+            tables.collect(event);
+        });
+        self.tables = tables;
+        ret
+    }
+
+    /// Check that the `kind` of record at `rva`, from the symbol at `offset`,
+    /// is inside of the image described by `header`, used internally
+    ///
+    /// Records outside of it are recorded as diagnostics, or are an error
+    /// for a strict load. Images with no size aren't checked.
+    fn check_in_image(&mut self, opts: &LoadOptions, header: &Header,
+            kind: &'static str, rva: u32, offset: u64) -> Result<()> {
+        if header.size_of_image == 0 || rva < header.size_of_image {
+            return Ok(());
+        }
+
+        let error = Error::OutsideImage(kind, rva);
+        if opts.strict {
+            return Err(error);
+        }
+        self.diagnostics.push(Diagnostic { offset, error });
+        Ok(())
+    }
+
+    /// Parse COFF information, used internally
+    ///
+    /// Functions, globals, and lines are passed to `sink`, everything else is
+    /// stored in `self`. The `header` and `sections` of the image are used
+    /// to check addresses and to tell functions from globals when the
+    /// symbols have no types.
+    fn parse(&mut self, reader: &mut (impl Read + Seek), coff_offset: u64,
+            opts: &LoadOptions, header: &Header, sections: &[Section],
+            sink: &mut impl FnMut(Event)) -> Result<()> {
+        let _span = trace_span!("COFF symbols", coff_offset);
+
+        // Save current file location
+        let start = reader.stream_position().map_err(Error::SeekCoff)?;
+
+        // Seek to the COFF data header
+        reader.seek(SeekFrom::Start(coff_offset)).map_err(Error::SeekCoff)?;
+
+        // Parse COFF symbol header
+        let ch = CoffSymbolsHeader::parse(reader)?;
+        if ch.num_symbols > opts.max_symbols {
+            return Err(Error::TooLarge("num_symbols", ch.num_symbols as u64));
+        }
+        if !opts.partial {
+            check_size(reader, "num_symbols", ch.num_symbols as u64 * 18,
+                u64::MAX)?;
+            check_size(reader, "num_line_nums", ch.num_line_nums as u64 * 6,
+                u64::MAX)?;
+        }
+
+        // The line numbers and symbols normally follow the header back to
+        // back, but the header gives where they are. Offsets pointing back
+        // into the header can't be right, so the usual layout is assumed.
+        let header_size = size_of::<CoffSymbolsHeader>() as u64;
+        let mut lva = |field, lva: u32, count: u32, default: u64| {
+            if lva as u64 >= header_size {
+                return coff_offset + lva as u64;
+            }
+
+            // Empty tables often have no offset at all
+            if count != 0 {
+                self.diagnostics.push(Diagnostic {
+                    offset: coff_offset,
+                    error:  Error::Suspicious(field, lva as u64),
+                });
+            }
+            coff_offset + default
+        };
+        let lines_offset = lva("lva_first_line", ch.lva_first_line,
+            ch.num_line_nums, header_size);
+        let symbols_offset = lva("lva_first_symbol", ch.lva_first_symbol,
+            ch.num_symbols, lines_offset - coff_offset +
+                ch.num_line_nums as u64 * 6);
+
+        // Storage for lines, symbols, and the string table
+        let mut line_addrs   = Vec::new();
+        let mut symbols      = Vec::new();
+        let mut string_table = Vec::new();
+
+        // Read everything, for a partial load a truncated file just leaves
+        // us with less to work with
+        let read = (|| {
+            // Parse line number table
+            reader.seek(SeekFrom::Start(lines_offset))
+                .map_err(Error::SeekCoff)?;
+            for _ in 0..ch.num_line_nums {
+                #[derive(Debug)]
+                struct Line {
+                    addr: u32,
+                    line: u16,
+                }
+            
+                // Parse line information
+                let line = Line {
+                    addr: consume!(reader, u32, "addr")?,
+                    line: consume!(reader, u16, "line")?,
+                };
+
+                line_addrs.push(line);
+            }
+
+            // Parse all symbol entries
+            reader.seek(SeekFrom::Start(symbols_offset))
+                .map_err(Error::SeekCoff)?;
+            let mut ii = 0;
+            while ii < ch.num_symbols as usize {
+                /// A COFF symbol table entry
+                #[derive(Debug)]
+                struct Symbol {
+                    /// Name of the symbol, represented by union of three
+                    /// structures. An array of eight bytes is used if the name
+                    /// is not more than eight bytes long
+                    name:  [u8; 8],
+
+                    /// Value associated with the symbol. The interpretation
+                    /// of this field depends on Section Number and Storage
+                    /// Class. A typical meaning is the relocatable address.
+                    value: u32,
+
+                    /// Signed integer identifying the section, using a
+                    /// one-based index into the Section Table. 
+                    _num:  i16,
+
+                    /// A number representing type. Microsoft tools set this
+                    /// field to 0x20 (function) or 0x0 (not a function)
+                    typ:   u16,
+
+                    /// Enumerated value representing storage class.
+                    class: StorageClass,
+
+                    /// Number of auxiliary symbol table entries that follow
+                    /// this record.
+                    aux:   u8,
+                }
+
+                // Parse the symbol
+                let symbol = Symbol {
+                    name:  consume!(reader, 8,   "name")?,
+                    value: consume!(reader, u32, "value")?,
+                    _num:  consume!(reader, i16, "num")?,
+                    typ:   consume!(reader, u16, "typ")?,
+                    class: consume!(reader, u8,  "class")?.into(),
+                    aux:   consume!(reader, u8,  "aux")?,
+                };
+          
+                // Read the AUX data
+                // There are 18 bytes (one `Symbol` worth) for each `aux`
+                // specified. This keeps the file always `Symbol` aligned, and
+                // actually makes parsing fairly easy
+                let mut aux = vec![0u8; symbol.aux as usize * 18];
+                read_field(reader, &mut aux, "symbol aux data")?;
+
+                // Advance to the next symbol
+                let index = ii;
+                ii += 1 + symbol.aux as usize;
+
+                // Save the symbol along with its symbol table index
+                symbols.push((index, symbol, aux));
+            }
+
+            // Get string table size
+            let string_table_sz =
+                consume!(reader, u32, "string table size")?;
+            if opts.partial {
+                // Nothing is allocated up front for a partial load, so only
+                // the limit matters
+                if string_table_sz > opts.max_string_table {
+                    return Err(Error::TooLarge("string table size",
+                        string_table_sz as u64));
+                }
+            } else {
+                check_size(reader, "string table size",
+                    string_table_sz as u64, opts.max_string_table as u64)?;
+            }
+
+            // Read the string table add 4 to leave room for the 4-byte
+            // string table size. A short read keeps whatever was there.
+            string_table.resize(4, 0);
+            let offset = reader.stream_position()
+                .map_err(|x| Error::Consume("string table", 0, x))?;
+            (&mut *reader).take(string_table_sz as u64)
+                .read_to_end(&mut string_table)
+                .map_err(|x| Error::Consume("string table", offset, x))?;
+            if string_table.len() < 4 + string_table_sz as usize {
+                return Err(Error::Truncated("string table size",
+                    string_table_sz as u64));
+            }
+
+            Ok(())
+        })();
+        let truncated = match read {
+            Err(error) if opts.partial && error.is_truncation() => {
+                let offset = reader.stream_position()
+                    .map_err(Error::SeekCoff)?;
+                self.diagnostics.push(Diagnostic { offset, error });
+
+                // Drop any name which was cut off part way through
+                let end = string_table.iter().rposition(|x| *x == 0)
+                    .unwrap_or(0);
+                string_table.truncate(end + 1);
+                true
+            }
+            read => { read?; false }
+        };
+        trace_event!(lines = line_addrs.len(), symbols = symbols.len(),
+            string_table = string_table.len(), truncated, "read symbols");
+
+        // A line number of 0 isn't a line, it marks the start of a function
+        // and the address is the symbol table index of the function. The
+        // lines up to the next marker belong to that function, and are
+        // reported with its source file when the symbol is reached. Lines of
+        // functions with no `.file` before them are left to the statics.
+        let first_file = symbols.iter()
+            .find(|(_, symbol, _)| symbol.class == StorageClass::File)
+            .map(|(index, _, _)| *index);
+        let has_file = |index: u32| {
+            first_file.is_some_and(|x| x < index as usize) &&
+                symbols.binary_search_by_key(&(index as usize), |x| x.0)
+                    .is_ok()
+        };
+        let mut function_lines: BTreeMap<u32, Vec<_>> = BTreeMap::new();
+        let mut owner = None;
+        for line in core::mem::take(&mut line_addrs) {
+            if line.line == 0 {
+                owner = Some(line.addr).filter(|&x| has_file(x));
+            } else if let Some(owner) = owner {
+                function_lines.entry(owner).or_default().push(line);
+            } else {
+                line_addrs.push(line);
+            }
+        }
+
+        // Sort by address
+        line_addrs.sort_by_key(|x| x.addr);
+
+        // Storage for the most recently observed FILE class
+        let mut cur_file: Option<String> = None;
+
+        // Start RVA of the most recently observed function symbol
+        let mut cur_func: Option<u32> = None;
+
+        // Start RVA and first line of a function after its `.bf` record,
+        // waiting on the matching `.ef`
+        let mut func_begin: Option<(u32, u32)> = None;
+
+        // Some linkers leave the type of every public as 0, even for code.
+        // If none are typed as functions, fall back on the code range from
+        // the header to tell them apart, or on the characteristics of their
+        // section if there is no range. The import address table is often at
+        // the start of the code, but its `__imp_` entries are pointers.
+        let typed = symbols.iter().any(|(_, symbol, _)| symbol.typ == 0x20);
+        let code_range = ch.code_range();
+        let is_function = |typ: u16, rva: u32, name: &str| {
+            if typed {
+                typ == 0x20
+            } else if name.starts_with("__imp_") {
+                false
+            } else if let Some(code_range) = &code_range {
+                code_range.contains(&rva)
+            } else {
+                sections.iter().find(|x| x.contains(rva))
+                    .is_some_and(|x| x.category() == SectionCategory::Code)
+            }
+        };
+
+        // Now that we've read everything from the file, parse the structures
+        for (index, symbol, aux) in symbols.iter() {
+            let offset = symbols_offset + *index as u64 * 18;
+
+            // Report the lines which follow the marker for this function
+            if let (Some(lines), Some(cur_file)) =
+                    (function_lines.remove(&(*index as u32)), &cur_file) {
+                for line in lines {
+                    self.check_in_image(opts, header, "line", line.addr,
+                        offset)?;
+                    sink(Event::Line {
+                        rva:      line.addr,
+                        filename: cur_file,
+                        line:     line.line as u32,
+                    });
+                }
+            }
+
+            let name = match symbol_name(&symbol.name, &string_table,
+                    opts.names) {
+                Ok(name) => name,
+                Err(error) if truncated || opts.lenient => {
+                    // The name was in the part of the file which is missing,
+                    // or is just bad
+                    self.diagnostics.push(Diagnostic { offset, error });
+                    continue;
+                }
+                Err(error) => return Err(error),
+            };
+
+            // Track the function which following `.bf` and `.ef` belong to
+            if matches!(symbol.class,
+                    StorageClass::External | StorageClass::Static) &&
+                    is_function(symbol.typ, symbol.value, &name) {
+                cur_func = Some(symbol.value);
+            }
+
+            // If the class is a public symbol
+            if matches!(symbol.class, StorageClass::External) {
+                if is_function(symbol.typ, symbol.value, &name) {
+                    self.check_in_image(opts, header, "function",
+                        symbol.value, offset)?;
+                    sink(Event::Function { rva: symbol.value, name: &name });
+                } else {
+                    self.check_in_image(opts, header, "global", symbol.value,
+                        offset)?;
+                    sink(Event::Global { rva: symbol.value, name: &name });
+                }
+            } else if matches!(symbol.class, StorageClass::Static) {
+                // Check if it's a static class with an aux, if so, we'll look
+                // at the section boundaries and try to find matching source
+                // lines
+                if let Some(cur_file) =
+                        cur_file.as_ref().filter(|_| aux.len() >= 4) {
+                    // Get the section length, unwrap is okay due to checked
+                    // aux size.
+                    let slen = u32::from_le_bytes(
+                        aux[0..4].try_into().unwrap());
+
+                    // Get start and end RVAs for this
+                    let start = symbol.value; // inclusive
+                    let end   = start.saturating_add(slen); // exclusive
+
+                    // Search for `start` in `line_addrs`
+                    let idx = match 
+                        line_addrs.binary_search_by_key(&start,
+                            |line| line.addr) {
+                        Ok(idx)  => idx,
+                        Err(idx) => idx,
+                    };
+
+                    // Go through each line from `start` until we are
+                    // out of bounds of `end`
+                    if let Some(line_addrs) = line_addrs.get(idx..) {
+                        for line in line_addrs {
+                            // Break if we're past our address
+                            if line.addr >= end {
+                                break;
+                            }
+
+                            // Save the line information
+                            self.check_in_image(opts, header, "line",
+                                line.addr, offset)?;
+                            sink(Event::Line {
+                                rva:      line.addr,
+                                filename: cur_file,
+                                line:     line.line as u32,
+                            });
+                        }
+                    }
+                }
+            } else if matches!(symbol.class, StorageClass::File) {
+                // Latch the filename from the AUX data
+                cur_file = Some(file_name(aux, opts.names)?);
+            } else if matches!(symbol.class, StorageClass::Function) {
+                // `.bf` and `.ef` records have the source line number at
+                // offset 4 of the aux data. Unwrap is fine as the slice size
+                // is checked by `get`.
+                let line = aux.get(4..6).map(|x| {
+                    u16::from_le_bytes(x.try_into().unwrap()) as u32
+                });
+
+                if name == ".bf" {
+                    // The value of `.bf` is unused by Microsoft tools, so
+                    // prefer the address of the function symbol
+                    let start = cur_func.take().unwrap_or(symbol.value);
+                    func_begin = Some((start, line.unwrap_or(0)));
+                } else if name == ".ef" {
+                    // The value of `.ef` is the size of the function
+                    if let Some((start, first_line)) = func_begin.take() {
+                        self.function_info.insert(start, FunctionInfo {
+                            start,
+                            end: start.saturating_add(symbol.value),
+                            first_line,
+                        });
+                    }
+                }
+            } else if matches!(symbol.class, StorageClass::WeakExternal) {
+                // The aux data starts with the symbol table index of the
+                // target of the alias
+                let Some(tag) = aux.get(0..4) else { continue; };
+
+                // Unwrap is fine as the slice size is checked by `get`
+                let tag = u32::from_le_bytes(tag.try_into().unwrap()) as usize;
+
+                // Find the target symbol
+                if let Ok(idx) = symbols.binary_search_by_key(&tag, |x| x.0) {
+                    let target = &symbols[idx].1;
+                    let target_name = match symbol_name(&target.name,
+                            &string_table, opts.names) {
+                        Ok(target_name) => target_name,
+                        Err(error) if opts.lenient => {
+                            self.diagnostics.push(Diagnostic {
+                                offset,
+                                error,
+                            });
+                            continue;
+                        }
+                        Err(error) => return Err(error),
+                    };
+                    self.aliases.insert(name.to_string(), (
+                        target_name.to_string(),
+                        target.value,
+                    ));
+                }
+            } else if let StorageClass::Unknown(class) = symbol.class {
+                if !opts.lenient {
+                    return Err(Error::UnknownSymbolClass(class));
+                }
+                self.diagnostics.push(Diagnostic {
+                    offset,
+                    error: Error::UnknownSymbolClass(class),
+                });
+            } else {
+                // Locals, type information, block and function markers, and
+                // labels carry nothing we report
+            }
+        }
+
+        // Seek back to where we were
+        reader.seek(SeekFrom::Start(start)).map_err(Error::SeekCoff)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::ops::Range;
+    use crate::writer::{HEADER_SIZE, SECTION_SIZE};
+
+    /// Build a writer for an i386 image with code at 0x1000 and data at
+    /// 0x2000
+    fn writer() -> DbgWriter {
+        let mut ret = DbgWriter::new(Header {
+            machine:       Machine::I386,
+            image_base:    0x400000,
+            size_of_image: 0x3000,
+            section_align: 0x1000,
+            ..Default::default()
+        });
+        ret.section(Section {
+            name:            ".text".into(),
+            vsize:           0x1000,
+            vaddr:           0x1000,
+            raw_data_sz:     0x1000,
+            ptr_raw_data:    0x400,
+            characteristics: 0x60000020,
+        });
+        ret.section(Section {
+            name:            ".data".into(),
+            vsize:           0x1000,
+            vaddr:           0x2000,
+            raw_data_sz:     0x200,
+            ptr_raw_data:    0x1400,
+            characteristics: 0xc0000040,
+        });
+        ret
+    }
+
+    /// Read the little endian `u32` at `offset` in `data`
+    fn get_u32(data: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    /// Overwrite the little endian `u32` at `offset` in `data`
+    fn set_u32(data: &mut [u8], offset: usize, val: u32) {
+        data[offset..offset + 4].copy_from_slice(&val.to_le_bytes());
+    }
+
+    /// Get where the COFF debug information is in `data`
+    fn coff_range(data: &[u8]) -> Range<usize> {
+        let dbg = DbgFile::parse(data).unwrap();
+        let dir = dbg.debug_directory(DebugType::Coff).unwrap();
+        let start = dir.ptr_raw_data as usize;
+        start..start + dir.size_of_data as usize
+    }
+
+    /// Get the offset in `data` of each COFF line number entry
+    fn line_offsets(data: &[u8]) -> Vec<usize> {
+        let coff = coff_range(data).start;
+        let first = coff + get_u32(data, coff + 12) as usize;
+        (0..get_u32(data, coff + 8) as usize).map(|x| first + x * 6)
+            .collect()
+    }
+
+    /// Get the offset in `data` of each COFF symbol, skipping aux entries
+    fn symbol_offsets(data: &[u8]) -> Vec<usize> {
+        let coff = coff_range(data).start;
+        let mut offset = coff + get_u32(data, coff + 4) as usize;
+        let end = offset + get_u32(data, coff) as usize * 18;
+        let mut ret = Vec::new();
+        while offset < end {
+            ret.push(offset);
+            offset += 18 * (1 + data[offset + 17] as usize);
+        }
+        ret
+    }
+
+    /// Build a file from `first`, which must have only COFF debug
+    /// information, with the COFF debug information of `second` in a second
+    /// directory after its own
+    fn two_coffs(first: &DbgWriter, second: &DbgWriter) -> Vec<u8> {
+        let (first, second) = (first.to_bytes(), second.to_bytes());
+        let (coff, other) = (coff_range(&first), coff_range(&second));
+        assert_eq!(coff.end, first.len());
+
+        // Copy the directory, which is just before the data, for the second
+        // and move the data of both after it
+        let dir = coff.start - DebugDirectory::SIZE;
+        let mut ret = first[..coff.start].to_vec();
+        ret.extend_from_within(dir..);
+        let data = ret.len();
+        set_u32(&mut ret, 32, 2 * DebugDirectory::SIZE as u32);
+        set_u32(&mut ret, dir + 24, data as u32);
+        set_u32(&mut ret, coff.start + 16, other.len() as u32);
+        set_u32(&mut ret, coff.start + 24, (data + coff.len()) as u32);
+        ret.extend_from_slice(&first[coff]);
+        ret.extend_from_slice(&second[other]);
+        ret
+    }
+
+    #[test]
+    fn unknown_class_is_diagnostic_when_lenient() {
+        let mut data = writer().function(0x1000, "main")
+            .global(0x1010, "odd")
+            .global(0x2000, "gvar")
+            .to_bytes();
+        let odd = symbol_offsets(&data)[1];
+        data[odd + 16] = 0x50;
+
+        assert!(matches!(DbgFile::parse(&data),
+            Err(Error::UnknownSymbolClass(0x50))));
+
+        let opts = LoadOptions::default().lenient(true).clone();
+        let dbg = DbgFile::parse_with_options(&data, &opts).unwrap();
+        assert_eq!(dbg.functions()[&0x1000], "main");
+        assert_eq!(dbg.globals()[&0x2000], "gvar");
+        let diags = dbg.diagnostics().collect::<Vec<_>>();
+        assert_eq!(diags.len(), 1);
+        assert!(matches!(diags[0].error, Error::UnknownSymbolClass(0x50)));
+    }
+
+    #[test]
+    fn bad_alias_target_is_diagnostic_when_lenient() {
+        let mut writer = writer();
+        writer.function(0x1000, "main").function(0x1010, "_KeBugCheckEx@20");
+        writer.aliases.insert("_KeBugCheck".into(),
+            ("_KeBugCheckEx@20".into(), 0x1010));
+        let mut data = writer.to_bytes();
+
+        // Point the name of the target past the end of the string table
+        let target = symbol_offsets(&data)[1];
+        set_u32(&mut data, target + 4, 0x100);
+
+        assert!(DbgFile::parse(&data).is_err());
+
+        let opts = LoadOptions::default().lenient(true).clone();
+        let dbg = DbgFile::parse_with_options(&data, &opts).unwrap();
+        assert_eq!(dbg.functions().len(), 1);
+        assert!(dbg.aliases().is_empty());
+        assert_eq!(dbg.diagnostics().count(), 2);
+    }
+
+    #[test]
+    fn lazy_load_records_errors() {
+        let mut data = writer().function(0x1000, "main")
+            .global(0x1010, "odd")
+            .to_bytes();
+        let odd = symbol_offsets(&data)[1];
+        data[odd + 16] = 0x50;
+
+        // The symbols aren't parsed until they're looked up
+        let opts = LoadOptions::default().lazy(true).clone();
+        let dbg = DbgFile::parse_with_options(&data, &opts).unwrap();
+        assert_eq!(dbg.functions()[&0x1000], "main");
+        let diags = dbg.diagnostics().collect::<Vec<_>>();
+        assert_eq!(diags.len(), 1);
+        assert!(matches!(diags[0].error, Error::UnknownSymbolClass(0x50)));
+    }
+
+    #[test]
+    fn partial_load_of_truncated_file() {
+        let mut data = writer().function(0x1000, "main")
+            .function(0x1010, "f")
+            .function(0x1020, "g")
+            .to_bytes();
+
+        // Cut the file in the middle of the second symbol
+        data.truncate(symbol_offsets(&data)[1] + 9);
+
+        assert!(DbgFile::parse(&data)
+            .is_err_and(|error| error.is_truncation()));
+
+        let opts = LoadOptions::default().partial(true).clone();
+        let dbg = DbgFile::parse_with_options(&data, &opts).unwrap();
+        assert_eq!(dbg.functions()[&0x1000], "main");
+        assert!(!dbg.functions().contains_key(&0x1020));
+        assert!(dbg.diagnostics().any(|x| x.error.is_truncation()));
+    }
+
+    #[test]
+    fn partial_load_continues_past_truncated_directory() {
+        let mut writer = writer();
+        writer.function(0x1000, "main").function_size(0x1000, 0x20);
+        writer.image_name = Some("ntoskrnl.exe".into());
+        let mut data = writer.to_bytes();
+
+        // Point the COFF data, the first directory, past the end of the file
+        set_u32(&mut data, HEADER_SIZE + 2 * SECTION_SIZE + 24, 0x10000);
+
+        assert!(DbgFile::parse(&data)
+            .is_err_and(|error| error.is_truncation()));
+
+        // The function table and image name are still found
+        let opts = LoadOptions::default().partial(true).clone();
+        let dbg = DbgFile::parse_with_options(&data, &opts).unwrap();
+        assert!(dbg.functions().is_empty());
+        assert_eq!(dbg.exception_ranges.get(&0x1000), Some(&0x1020));
+        assert_eq!(dbg.image_name(), Some("ntoskrnl.exe"));
+        assert_eq!(dbg.debug_directories().len(), 3);
+        assert!(dbg.diagnostics().any(|x| x.error.is_truncation()));
+    }
+
+    #[test]
+    fn lazy_strict_load_records_errors() {
+        let mut data = writer().function(0x1000, "main")
+            .function(0x5000, "outside")
+            .to_bytes();
+
+        // Leave a few stray bytes after the debug directory
+        let size = get_u32(&data, 32);
+        set_u32(&mut data, 32, size + 4);
+
+        let strict = LoadOptions::default().strict(true).clone();
+        assert!(matches!(DbgFile::parse_with_options(&data, &strict),
+            Err(Error::OutsideImage("function", 0x5000))));
+
+        // Problems outside of the symbols are there before they're parsed
+        let lazy = strict.clone().lazy(true).clone();
+        let dbg = DbgFile::parse_with_options(&data, &lazy).unwrap();
+        assert!(matches!(dbg.load_diagnostics(), [Diagnostic {
+            error: Error::Suspicious("debug directory size", _), ..
+        }]));
+
+        assert_eq!(dbg.functions().len(), 1);
+        let diags = dbg.diagnostics().collect::<Vec<_>>();
+        assert_eq!(diags.len(), 2);
+        assert!(matches!(diags[1].error,
+            Error::OutsideImage("function", 0x5000)));
+    }
+
+    #[test]
+    fn coff_ranges_keep_reader_position() {
+        let data = writer().function(0x1000, "main").to_bytes();
+        let coff = coff_range(&data).start as u64;
+        let mut dbg = DbgFile::parse(&data).unwrap();
+        assert_eq!(dbg.code_range(), Some(0x1000..=0x1fff));
+        assert_eq!(dbg.data_range(), Some(0x2000..=0x2fff));
+
+        // A header cut short is an error, but doesn't move the reader
+        (dbg.code_range, dbg.data_range) = (None, None);
+        let mut reader = Cursor::new(&data[..coff as usize + 16]);
+        reader.set_position(8);
+        assert!(dbg.read_coff_ranges(&mut reader, coff).is_err());
+        assert_eq!(reader.position(), 8);
+
+        // Neither does a long section name in a missing string table
+        dbg.sections[0].name = "/4".into();
+        assert!(dbg.resolve_section_names(&mut reader, coff,
+            &LoadOptions::default()).is_err());
+        assert_eq!(reader.position(), 8);
+    }
+
+    #[test]
+    fn coff_directories_merge_first_wins() {
+        let mut first = writer();
+        first.function(0x1000, "main")
+            .function(0x1010, "b")
+            .line(0x1000, "a.c", 1);
+        let mut second = writer();
+        second.function(0x1000, "other")
+            .function(0x1020, "c")
+            .line(0x1000, "b.c", 5)
+            .line(0x1020, "b.c", 7);
+        let dbg = DbgFile::parse(&two_coffs(&first, &second)).unwrap();
+
+        assert_eq!(dbg.debug_directories().len(), 2);
+        assert_eq!(dbg.functions()[&0x1000], "main");
+        assert_eq!(dbg.functions()[&0x1010], "b");
+        assert_eq!(dbg.functions()[&0x1020], "c");
+        assert_eq!(dbg.lines()[&0x1000], ("a.c".into(), 1));
+        assert_eq!(dbg.lines()[&0x1020], ("b.c".into(), 7));
+    }
+
+    #[test]
+    fn line_markers_name_the_function_file() {
+        let mut data = writer().function(0x1000, "f")
+            .function(0x1100, "g")
+            .line(0x1000, "a.c", 1)
+            .line(0x1004, "a.c", 2)
+            .line(0x1008, "a.c", 3)
+            .line(0x100c, "a.c", 4)
+            .line(0x1100, "b.c", 20)
+            .to_bytes();
+
+        // Symbols are `f`, `g`, then a `.file` and a static covering the
+        // lines of each source file. Turn the second line into a marker
+        // naming the static of `b.c`, so the lines after it are reported
+        // with its file rather than the one of the static covering them.
+        let symbols = symbol_offsets(&data);
+        let marker = line_offsets(&data)[1];
+        set_u32(&mut data, marker, ((symbols[5] - symbols[0]) / 18) as u32);
+        data[marker + 4..marker + 6].copy_from_slice(&[0; 2]);
+        let dbg = DbgFile::parse(&data).unwrap();
+
+        let lines = dbg.lines().iter()
+            .map(|(rva, (source, line))| (*rva, &**source, *line))
+            .collect::<Vec<_>>();
+        assert_eq!(lines, [
+            (0x1000, "a.c", 1),
+            (0x1008, "b.c", 3),
+            (0x100c, "b.c", 4),
+            (0x1100, "b.c", 20),
+        ]);
+    }
+
+    /// Build a file for `machine` with a function table of two functions
+    fn function_table_file(machine: Machine) -> Vec<u8> {
+        let mut writer = DbgWriter::new(Header {
+            machine,
+            image_base:    0x10000,
+            size_of_image: 0x3000,
+            section_align: 0x1000,
+            ..Default::default()
+        });
+        writer.function(0x1000, "main").function(0x1040, "helper")
+            .function_size(0x1000, 0x40)
+            .function_size(0x1040, 0x24);
+        writer.to_bytes()
+    }
+
+    #[test]
+    fn function_tables_of_each_machine() {
+        let machines = [
+            (Machine::Alpha,   FunctionEntryLayout::Entry),
+            (Machine::R4000,   FunctionEntryLayout::Entry),
+            (Machine::PowerPc, FunctionEntryLayout::Entry),
+            (Machine::Alpha64, FunctionEntryLayout::Entry64),
+        ];
+        for (machine, layout) in machines {
+            let data = function_table_file(machine);
+            let dbg = DbgFile::parse(&data).unwrap();
+
+            let dir = dbg.debug_directory(DebugType::Exception).unwrap();
+            let start = dir.ptr_raw_data as usize;
+            let table = &data[start..start + dir.size_of_data as usize];
+            assert_eq!(FunctionEntryLayout::detect(machine, table), layout,
+                "{:?}", machine);
+
+            let ranges = dbg.exception_ranges.iter()
+                .map(|(start, end)| (*start, *end)).collect::<Vec<_>>();
+            assert_eq!(ranges, [(0x1000, 0x1040), (0x1040, 0x1064)],
+                "{:?}", machine);
+            assert_eq!(dbg.function_size(0x1040), Some(0x24));
+        }
+    }
+
+    #[test]
+    fn runtime_function_table_detected() {
+        // A copy of `.pdata` doesn't parse as sane `IMAGE_FUNCTION_ENTRY`s
+        // but does as `IMAGE_RUNTIME_FUNCTION_ENTRY`s
+        let mut table = Vec::new();
+        for (start, end) in [(0x1000u32, 0x1040u32), (0x1040, 0x1064)] {
+            for field in [start, end, 0, 0, start + 8] {
+                table.extend_from_slice(&field.to_le_bytes());
+            }
+        }
+        for machine in [Machine::Alpha, Machine::R4000, Machine::PowerPc] {
+            assert_eq!(FunctionEntryLayout::detect(machine, &table),
+                FunctionEntryLayout::Runtime, "{:?}", machine);
+        }
+        assert_eq!(FunctionEntryLayout::detect(Machine::I386, &table),
+            FunctionEntryLayout::Entry);
+    }
+}
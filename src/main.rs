@@ -1,619 +1,3621 @@
-//! Parser for `DI` debug info files. We specfically just parse the COFF
-//! data from them to get globals, functions, and line numbers
+//! Command line tool to dump symbols and line information from `DI` debug
+//! info files
 
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
 use std::fs::File;
-use std::io::{Read, Seek, SeekFrom, BufReader};
-use std::mem::size_of;
+use std::num::ParseIntError;
+use std::io::{self, BufWriter, Cursor, IsTerminal, Read, Seek, Write as _};
+use std::ops::{Range, RangeInclusive};
 use std::path::{Path, PathBuf};
-use std::collections::BTreeMap;
+use std::sync::{mpsc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+use clap::{ArgAction, Args, Parser, Subcommand};
+use dbgparse::{coverage, expand, meso, overlap, split, DbgFile, DbgWriter};
+use dbgparse::{DebugType, Diagnostic};
+use dbgparse::Error;
+use dbgparse::{LoadOptions, Machine, NameDecoding, Result, SymbolKind};
+use dbgparse::{MemoryFootprint, Session, MAX_READ_WHOLE};
+use dbgparse::SymbolSink;
+use dbgparse::overlap::Finding;
+use indicatif::{ProgressBar, ProgressStyle};
+use rayon::prelude::*;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "tui")]
+mod tui;
+
+/// Append a formatted line to the buffer `out`
+///
+/// Unwrap is fine as writing to a `Vec` never fails
+macro_rules! outln {
+    ($out:expr, $($arg:tt)*) => {
+        writeln!($out, $($arg)*).unwrap()
+    };
+}
+
+/// Dump functions, globals, and source lines from `DI` debug info files
+///
+/// Inputs may be `DI` files, CABs or ZIPs of them, or directories, which are
+/// searched recursively for `.dbg`, `.db_`, `.cab`, and `.zip` files. A file
+/// or archive member which fails to parse is reported on `stderr` and the
+/// rest are still processed, the exit code is non-zero if anything failed.
+#[derive(Parser)]
+#[command(version, subcommand_negates_reqs = true,
+    arg_required_else_help = true)]
+struct Cli {
+    #[command(flatten)]
+    flags: Flags,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Files to dump, the same as `dump`
+    #[arg(required = true)]
+    files: Vec<String>,
+}
+
+/// Flags for all commands
+#[derive(Args)]
+struct Flags {
+    /// Print MSVC C++ decorated names as C++ signatures
+    #[arg(long, global = true)]
+    demangle: bool,
+
+    /// Strip i386 C decoration, eg. `_func@12` becomes `func`
+    #[arg(long, global = true)]
+    undecorate: bool,
+
+    /// Warn about unknown symbol classes instead of failing
+    #[arg(long, global = true)]
+    lenient: bool,
+
+    /// Print whatever could be parsed from truncated files, with a warning
+    /// about where they were cut short
+    #[arg(long, global = true)]
+    partial: bool,
+
+    /// Fail on files with symbols or lines outside of the image, rather
+    /// than warning about them
+    #[arg(long, global = true)]
+    strict: bool,
+
+    /// How to decode names which aren't valid UTF-8, `lossy` to replace bad
+    /// sequences with U+FFFD, `replace` to replace bad bytes with `?`, or
+    /// `error` to fail the file
+    #[arg(long, global = true, value_name = "lossy|replace|error",
+        value_parser = parse_names)]
+    names: Option<NameDecoding>,
+
+    /// Print functions as `F <addr> <size> <function>`
+    #[arg(long, global = true)]
+    sizes: bool,
+
+    /// Print the section of each symbol after its address, and the category
+    /// (code, data, bss, rdata) of globals, along with a summary of the run
+    /// on `stderr`. Give twice to also print counts for each file.
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Don't print warnings about odd or damaged files
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Print the approximate memory used by the parsed files to `stderr`
+    /// at the end of the run, broken down by table
+    #[arg(long, global = true)]
+    stats_mem: bool,
+
+    /// Only print records with addresses in these sections
+    #[arg(long, global = true, value_name = ".text,.data",
+        value_delimiter = ',')]
+    section: Option<Vec<String>>,
+
+    /// Only print records with printed addresses in this range (in hex, the
+    /// end is exclusive and either end may be left off)
+    #[arg(long, global = true, value_name = "start..end",
+        value_parser = parse_range)]
+    range: Option<Range<u64>>,
+
+    /// Only print records of these kinds (functions, globals, and source
+    /// lines), leaving out exported names and aliases
+    #[arg(long, global = true, value_name = "f,g,s",
+        value_parser = Kinds::parse)]
+    kind: Option<Kinds>,
+
+    /// Only print functions, may be combined with `--globals` and `--lines`
+    #[arg(long, global = true)]
+    functions: bool,
+
+    /// Only print globals, may be combined with `--functions` and `--lines`
+    #[arg(long, global = true)]
+    globals: bool,
+
+    /// Only print source lines, may be combined with `--functions` and
+    /// `--globals`
+    #[arg(long, global = true)]
+    lines: bool,
+
+    /// Hide compiler and linker internal symbols, such as `$$SYMBOLS`,
+    /// string literals, and import descriptors
+    #[arg(long, global = true)]
+    no_internal: bool,
+
+    /// Only print records whose names match this regex, or whose
+    /// `source:line` does for lines
+    #[arg(long = "match", global = true, value_name = "regex")]
+    name_match: Option<Regex>,
+
+    /// Replace the start of source paths matching `from` (ignoring case)
+    /// with `to`, may be given more than once with the first match winning
+    #[arg(long, global = true, value_name = "from=to",
+        value_parser = PathMap::parse)]
+    pathmap: Vec<PathMap>,
+
+    /// Print source paths with forward slashes instead of backslashes
+    #[arg(long, global = true)]
+    forward_slashes: bool,
+
+    /// Print source paths in lowercase
+    #[arg(long, global = true)]
+    lowercase_paths: bool,
+
+    /// Strip drive letters such as `d:` from the start of source paths
+    #[arg(long, global = true)]
+    strip_drive: bool,
+
+    /// Print virtual addresses using the image base of each file, or with
+    /// `--base=<addr>` using a base address (in hex)
+    #[arg(long, global = true, value_name = "addr", num_args = 0..=1,
+        require_equals = true, default_missing_value = "image",
+        value_parser = parse_base)]
+    base: Option<Base>,
+
+    /// Number of files to parse in parallel, defaults to the number of CPUs
+    #[arg(short, long, global = true, value_name = "n")]
+    jobs: Option<usize>,
+
+    /// Show a progress bar on `stderr` while parsing
+    #[arg(long, global = true)]
+    progress: bool,
+
+    /// Print aligned and colored columns, demangled names, and with no
+    /// command the records grouped by section
+    #[arg(long, global = true)]
+    pretty: bool,
+
+    /// Format of the printed records, `text`, `jsonl` for one JSON object
+    /// per line, `msgpack` for a stream of MessagePack maps, or `kallsyms`
+    /// for `address type name` lines of just the symbols
+    #[arg(long, global = true, value_name = "text|jsonl|msgpack|kallsyms",
+        value_parser = Format::parse, conflicts_with = "pretty")]
+    format: Option<Format>,
+
+    /// Order of the printed records of each kind, by address, by name, or
+    /// by function size (smallest first)
+    #[arg(long, global = true, value_name = "addr|name|size",
+        value_parser = SortOrder::parse, conflicts_with = "pretty")]
+    sort: Option<SortOrder>,
+
+    /// Prefix the records of each debug file with the name of its module,
+    /// the image name in the file or else the file's own name
+    #[arg(long, global = true)]
+    with_module: bool,
+
+    /// Print symbol names qualified with their module the way WinDbg does,
+    /// eg. `ntoskrnl!KeBugCheckEx`
+    #[arg(long, global = true)]
+    qualify: bool,
+
+    /// Only process debug files built on or after this date, `YYYY-MM-DD`
+    /// or a raw timestamp in hex
+    #[arg(long, global = true, value_name = "date",
+        value_parser = parse_date)]
+    newer_than: Option<u32>,
+
+    /// Only process debug files built before this date, `YYYY-MM-DD` or a
+    /// raw timestamp in hex
+    #[arg(long, global = true, value_name = "date",
+        value_parser = parse_date)]
+    older_than: Option<u32>,
+
+    /// Only process the files in CABs and ZIPs with names matching this glob
+    /// pattern, may be given more than once
+    #[arg(long, global = true, value_name = "pattern",
+        value_parser = glob::Pattern::new)]
+    member: Vec<glob::Pattern>,
+
+    /// Scan the inputs as raw disk images or memory dumps, parsing every
+    /// embedded `DI` file found in them
+    #[arg(long, global = true)]
+    carve: bool,
+
+    /// Write the output to this file instead of `stdout`
+    #[arg(short, long, global = true, value_name = "path")]
+    output: Option<PathBuf>,
+
+    /// Read default flags from this file instead of
+    /// `~/.config/coff_nm.toml`
+    #[arg(long, global = true, value_name = "path")]
+    config: Option<PathBuf>,
+
+    /// Don't read default flags from a config file
+    #[arg(long, global = true, conflicts_with = "config")]
+    no_config: bool,
+}
+
+/// Defaults for the flags, read from a TOML config file. Flags given on the
+/// command line take precedence.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Config {
+    /// Default for `--demangle`
+    demangle: bool,
+
+    /// Default for `--undecorate`
+    undecorate: bool,
+
+    /// Default for `--lenient`
+    lenient: bool,
+
+    /// Default for `--partial`
+    partial: bool,
+
+    /// Default for `--strict`
+    strict: bool,
+
+    /// Default for `--names`, one of `lossy`, `replace`, or `error`
+    names: Option<String>,
+
+    /// Default for `--sizes`
+    sizes: bool,
+
+    /// Default for `--verbose`, the number of times it's given
+    verbose: u8,
+
+    /// Default for `--quiet`
+    quiet: bool,
+
+    /// Default for `--stats-mem`
+    stats_mem: bool,
+
+    /// Default for `--section`
+    section: Option<Vec<String>>,
+
+    /// Default for `--base`, either `image` or an address in hex
+    base: Option<String>,
+
+    /// Default for `--jobs`
+    jobs: Option<usize>,
+
+    /// Default for `--progress`
+    progress: bool,
+
+    /// Default for `--pretty`
+    pretty: bool,
+
+    /// Default for `--format`, one of `text`, `jsonl`, or `msgpack`
+    format: Option<String>,
+
+    /// Default for `--sort`, one of `addr`, `name`, or `size`
+    sort: Option<String>,
+
+    /// Default for `--no-internal`
+    no_internal: bool,
+
+    /// Default for `--with-module`
+    with_module: bool,
+
+    /// Default for `--qualify`
+    qualify: bool,
+
+    /// Default for `--newer-than`
+    newer_than: Option<String>,
+
+    /// Default for `--older-than`
+    older_than: Option<String>,
+
+    /// Default for `--pathmap`, rules of the form `from=to`
+    pathmap: Vec<String>,
+
+    /// Default for `--forward-slashes`
+    forward_slashes: bool,
+
+    /// Default for `--lowercase-paths`
+    lowercase_paths: bool,
+
+    /// Default for `--strip-drive`
+    strip_drive: bool,
+}
+
+impl Config {
+    /// Get the path of the config file used when `--config` isn't given,
+    /// `coff_nm.toml` in the user's config directory
+    fn default_path() -> Option<PathBuf> {
+        let dir = std::env::var_os("XDG_CONFIG_HOME").map(PathBuf::from)
+            .or_else(|| {
+                std::env::var_os("HOME").map(|x| Path::new(&x).join(".config"))
+            })
+            .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))?;
+        Some(dir.join("coff_nm.toml"))
+    }
+
+    /// Load the config for `flags`, which is empty if no config file was
+    /// given and there isn't one in the default location
+    fn load(flags: &Flags) -> std::result::Result<Self, String> {
+        if flags.no_config {
+            return Ok(Self::default());
+        }
+
+        let (path, required) = match &flags.config {
+            Some(path) => (path.clone(), true),
+            None => match Self::default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if !required &&
+                err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Self::default());
+            }
+            Err(err) => return Err(format!("{}: {}", path.display(), err)),
+        };
+
+        toml::from_str(&text).map_err(|x| format!("{}: {}", path.display(), x))
+    }
+}
+
+/// Commands of the command line tool
+#[derive(Subcommand)]
+enum Command {
+    /// Print all functions, globals, exported names, aliases, and source
+    /// lines, the default when no command is given
+    Dump {
+        #[arg(required = true)]
+        files: Vec<String>,
+    },
+
+    /// Print the functions and globals named exactly `symbol`, which may be
+    /// qualified like `ntoskrnl!KeBugCheckEx` to only look in that module
+    #[command(alias = "find")]
+    Lookup {
+        symbol: String,
+
+        #[arg(required = true)]
+        files: Vec<String>,
+    },
+
+    /// Print the addresses of a source line or range of lines, `source` may
+    /// be just the trailing part of the path
+    Line {
+        #[arg(value_name = "source:line[-line]",
+            value_parser = parse_line_spec)]
+        spec: LineSpec,
+
+        #[arg(required = true)]
+        files: Vec<String>,
+    },
+
+    /// Print the records whose name (or `source:line`) matches a regex
+    Grep {
+        /// Match case-insensitively
+        #[arg(short = 'i')]
+        ignore_case: bool,
+
+        /// Only search these record kinds (functions, globals, and source
+        /// lines)
+        #[arg(short = 'k', value_name = "f,g,s", default_value = "f,g,s",
+            value_parser = Kinds::parse)]
+        kinds: Kinds,
+
+        pattern: String,
+
+        #[arg(required = true)]
+        files: Vec<String>,
+    },
+
+    /// Print counts of the records, broken down by section, along with
+    /// histograms of function sizes and line records per source file
+    Stats {
+        #[arg(required = true)]
+        files: Vec<String>,
+    },
+
+    /// Print the line table grouped by source file
+    Sources {
+        #[arg(required = true)]
+        files: Vec<String>,
+    },
+
+    /// Report functions whose extents overlap or nest inside of each other,
+    /// globals inside of functions, and functions whose `.ef` record and
+    /// function table entry disagree
+    Overlaps {
+        #[arg(required = true)]
+        files: Vec<String>,
+    },
+
+    /// Print the header and the debug directories
+    Header {
+        #[arg(required = true)]
+        files: Vec<String>,
+    },
+
+    /// Hexdump the raw data of the debug directories of a type, for looking
+    /// at payloads which aren't parsed
+    Raw {
+        /// Type of the debug directories to dump, eg. `codeview`, `fpo`, or
+        /// `misc`, or the type number
+        #[arg(long = "type", value_name = "type",
+            value_parser = parse_debug_type)]
+        typ: DebugType,
+
+        #[arg(required = true)]
+        files: Vec<String>,
+    },
+
+    /// Print the section table
+    Sections {
+        #[arg(required = true)]
+        files: Vec<String>,
+    },
+
+    /// Translate an RVA (in hex) into an offset in the original image
+    ToOffset {
+        #[arg(value_parser = parse_hex)]
+        rva: u32,
+
+        #[arg(required = true)]
+        files: Vec<String>,
+    },
+
+    /// Translate an offset (in hex) in the original image into an RVA
+    ToRva {
+        #[arg(value_parser = parse_hex)]
+        offset: u32,
+
+        #[arg(required = true)]
+        files: Vec<String>,
+    },
+
+    /// Print the records only in `old` prefixed with `-` and the records only
+    /// in `new` prefixed with `+`
+    Diff {
+        old: String,
+        new: String,
+    },
+
+    /// List the folders and files in CABs without extracting anything
+    #[cfg(feature = "cab")]
+    List {
+        #[arg(required = true)]
+        files: Vec<String>,
+    },
+
+    /// Extract every file in a CAB into a directory
+    #[cfg(feature = "cab")]
+    Extract {
+        cab: String,
+        dir: PathBuf,
+    },
+
+    /// Write a `.dbg` with the symbols and lines of an MSVC linker `.map`
+    FromMap {
+        /// Machine the image is for, the map doesn't say
+        #[arg(long, value_name = "i386|mips|alpha|ppc", default_value = "i386",
+            value_parser = parse_machine)]
+        machine: Machine,
+
+        map: PathBuf,
+        dbg: PathBuf,
+    },
+
+    /// Write a `.dbg` with the debug information embedded in a PE image
+    Split {
+        image: PathBuf,
+
+        /// Path of the `.dbg`, the image's path with a `.dbg` extension by
+        /// default
+        dbg: Option<PathBuf>,
+    },
+
+    /// Write a copy of a `.dbg` with only some of its symbols, or with them
+    /// renamed, for handing out partial symbols
+    Strip {
+        /// Only keep symbols with names matching this glob pattern, may be
+        /// given more than once
+        #[arg(long, value_name = "pattern", value_parser = glob::Pattern::new)]
+        keep: Vec<glob::Pattern>,
+
+        /// Drop symbols with names matching this glob pattern, may be given
+        /// more than once
+        #[arg(long, value_name = "pattern", value_parser = glob::Pattern::new)]
+        drop: Vec<glob::Pattern>,
+
+        /// Drop all functions
+        #[arg(long)]
+        no_functions: bool,
+
+        /// Drop all globals
+        #[arg(long)]
+        no_globals: bool,
+
+        /// Drop the source lines
+        #[arg(long)]
+        no_lines: bool,
+
+        /// Rename functions to `sub_<rva>` and globals to `data_<rva>`,
+        /// dropping aliases as they would give the names away
+        #[arg(long)]
+        obfuscate: bool,
+
+        input: String,
+        dbg: PathBuf,
+    },
+
+    /// Write a `.meso` breakpoint list of the functions in a `.dbg` for the
+    /// `mesos` coverage tool
+    Meso {
+        /// Name of the module as it's loaded, by default the name of the
+        /// `.dbg` with `.dll` or `.exe` depending on the image
+        #[arg(long, value_name = "name")]
+        module: Option<String>,
+
+        /// Also put breakpoints on each source line in a function, as an
+        /// approximation of its basic blocks
+        #[arg(long)]
+        lines: bool,
+
+        input: String,
+
+        /// Path of the `.meso`, the input's path with a `.meso` extension by
+        /// default
+        meso: Option<PathBuf>,
+    },
+
+    /// Print how often each function and source line was hit in a trace of
+    /// executed RVAs, either one hex RVA per line or a `drcov` log
+    Coverage {
+        /// Module to take the blocks of from a `drcov` log, by default the
+        /// name of the `.dbg`
+        #[arg(long, value_name = "name")]
+        module: Option<String>,
+
+        file: String,
+        trace: PathBuf,
+    },
+
+    /// Symbolize virtual addresses across a whole kernel address space,
+    /// loading the `.dbg` of each module in a list of where they're loaded
+    Symbolize {
+        /// List of loaded modules, a `base name` pair per line as printed by
+        /// an emulator, or the output of WinDbg's `lm`
+        #[arg(long, value_name = "file")]
+        modules: PathBuf,
+
+        /// Directory searched recursively for the `.dbg` of each module
+        #[arg(long, value_name = "dir")]
+        symbols: PathBuf,
+
+        /// Addresses to symbolize in hex, by default a trace of one address
+        /// per line read from `stdin`
+        addrs: Vec<String>,
+    },
+
+    /// Load a file once and answer queries about it read from `stdin`, see
+    /// `help` at the prompt for the commands
+    Repl {
+        file: String,
+    },
+
+    /// Browse the symbols of a file in the terminal, with a section pane, a
+    /// filterable symbol list, and the lines of each symbol's source file
+    #[cfg(feature = "tui")]
+    Browse {
+        file: String,
+    },
+}
+
+/// Counts of what was parsed
+#[derive(Default)]
+struct Stats {
+    /// Debug files parsed, counting each file in a CAB
+    files: AtomicUsize,
+
+    /// Functions in the parsed files
+    functions: AtomicUsize,
+
+    /// Globals in the parsed files
+    globals: AtomicUsize,
+
+    /// Source lines in the parsed files
+    lines: AtomicUsize,
+
+    /// Warnings about the parsed files
+    warnings: AtomicUsize,
+
+    /// Memory used by the parsed files, for `--stats-mem`
+    memory: Mutex<MemoryFootprint>,
+}
+
+/// Options which apply to all subcommands
+#[derive(Default)]
+struct Options {
+    /// Demangle MSVC C++ decorated names before printing them
+    demangle: bool,
+
+    /// Strip C calling convention decoration before printing names
+    undecorate: bool,
+
+    /// Print the size of functions
+    sizes: bool,
+
+    /// Print the section containing each symbol, and a summary of the run
+    verbose: bool,
+
+    /// Print the number of records in each file
+    file_stats: bool,
+
+    /// Total up the memory used by the parsed files
+    stats_mem: bool,
+
+    /// Don't print warnings
+    quiet: bool,
+
+    /// Counts of what was parsed, for the summary
+    stats: Stats,
+
+    /// If set, only print records with addresses in these named sections
+    sections: Option<Vec<String>>,
+
+    /// If set, only print records with printed addresses in this range
+    range: Option<Range<u64>>,
+
+    /// If set, only print records of these kinds
+    kinds: Option<Kinds>,
+
+    /// If set, only print records with names matching this
+    name_match: Option<Regex>,
+
+    /// Hide compiler and linker internal symbols
+    no_internal: bool,
+
+    /// Rules replacing the start of source paths
+    path_map: Vec<PathMap>,
+
+    /// Print source paths with forward slashes
+    forward_slashes: bool,
+
+    /// Print source paths in lowercase
+    lowercase_paths: bool,
+
+    /// Strip drive letters from source paths
+    strip_drive: bool,
+
+    /// If set, base address to add to all printed addresses
+    base: Option<Base>,
+
+    /// Options to use when parsing debug files
+    load: LoadOptions,
+
+    /// If set, progress bar counting parsed files and CAB members
+    progress: Option<ProgressBar>,
+
+    /// Print aligned columns, and group the records of `dump` by section
+    pretty: bool,
+
+    /// Color record kinds and headings, only used with `pretty`
+    color: bool,
+
+    /// Print an `M` record before each debug file on a symbol CD
+    modules: bool,
+
+    /// If set, only process debug files with timestamps in this range
+    period: Option<Range<u32>>,
+
+    /// If not empty, only process CAB members matching one of these
+    members: Vec<glob::Pattern>,
+
+    /// Scan inputs for embedded debug files rather than parsing them as is
+    carve: bool,
+
+    /// Format of the printed records
+    format: Format,
+
+    /// Order of the printed records of each kind
+    sort: SortOrder,
+
+    /// Prefix the records of each debug file with the name of its module
+    with_module: bool,
+
+    /// Print symbol names as `module!name`
+    qualify: bool,
+}
+
+impl Options {
+    /// Print the function `name` at `rva` in `dbg`
+    fn print_function(&self, out: &mut Vec<u8>, dbg: &DbgFile, rva: u32,
+            name: &str) {
+        if !self.kinds.is_none_or(|x| x.functions) ||
+                !self.wanted(dbg, rva, name) {
+            return;
+        }
+        let name = &*self.qualify(dbg, name);
+
+        if self.format != Format::Text {
+            self.print_record(out, &Record::Function {
+                addr:    self.addr(dbg, rva),
+                size:    dbg.function_size(rva),
+                section: dbg.section_for_rva(rva).map(|x| x.name.as_str()),
+                name,
+            });
+            return;
+        }
+
+        let mut record = format!("{} {:08x}", self.tag("F"),
+            self.addr(dbg, rva));
+        if self.sizes {
+            write!(record, " {:08x}", dbg.function_size(rva).unwrap_or(0))
+                .unwrap();
+        }
+        if self.verbose {
+            write!(record, " {:<1$}", Self::section(dbg, rva), self.pad(8))
+                .unwrap();
+            if self.pretty {
+                write!(record, " {:<5}", Self::category(dbg, rva)).unwrap();
+            }
+        }
+        outln!(out, "{} {}", record, name);
+    }
+
+    /// Print the global `name` at `rva` in `dbg`
+    fn print_global(&self, out: &mut Vec<u8>, dbg: &DbgFile, rva: u32,
+            name: &str) {
+        if !self.kinds.is_none_or(|x| x.globals) ||
+                !self.wanted(dbg, rva, name) {
+            return;
+        }
+        let name = &*self.qualify(dbg, name);
+
+        if self.format != Format::Text {
+            self.print_record(out, &Record::Global {
+                addr:     self.addr(dbg, rva),
+                section:  dbg.section_for_rva(rva).map(|x| x.name.as_str()),
+                category: dbg.category_for_rva(rva).map(|x| x.as_str()),
+                name,
+            });
+            return;
+        }
+
+        let mut record = format!("{} {:08x}", self.tag("G"),
+            self.addr(dbg, rva));
+        self.pad_size(&mut record);
+        if self.verbose {
+            write!(record, " {:<2$} {:<3$}", Self::section(dbg, rva),
+                Self::category(dbg, rva), self.pad(8), self.pad(5)).unwrap();
+        }
+        outln!(out, "{} {}", record, name);
+    }
+
+    /// Print the source line `source:line` at `rva` in `dbg`
+    fn print_line(&self, out: &mut Vec<u8>, dbg: &DbgFile, rva: u32,
+            source: &str, line: u32) {
+        if !self.kinds.is_none_or(|x| x.lines) {
+            return;
+        }
+        let source = &*self.source(source);
+        if !self.wanted(dbg, rva, &format!("{}:{}", source, line)) {
+            return;
+        }
+
+        if self.format != Format::Text {
+            self.print_record(out, &Record::Line {
+                addr: self.addr(dbg, rva),
+                source,
+                line,
+            });
+            return;
+        }
+
+        let mut record = format!("{} {:08x}", self.tag("S"),
+            self.addr(dbg, rva));
+        self.pad_size(&mut record);
+        if self.pretty && self.verbose {
+            write!(record, " {:<8} {:<5}", Self::section(dbg, rva),
+                Self::category(dbg, rva)).unwrap();
+        }
+        outln!(out, "{} {}:{}", record, source, line);
+    }
+
+    /// Check if the archive member `name` passes the `--member` filter, by
+    /// either its whole path or just its file name. Nested archives always
+    /// do, as it's their members which are filtered.
+    fn wants_member(&self, name: &str) -> bool {
+        let options = glob::MatchOptions {
+            case_sensitive: false,
+            ..Default::default()
+        };
+        let lower = name.to_ascii_lowercase();
+        let base = name.rsplit(['/', '\\']).next().unwrap_or(name);
+        self.members.is_empty() ||
+            lower.ends_with(".cab") || lower.ends_with(".zip") ||
+            self.members.iter().any(|x| {
+                x.matches_with(name, options) || x.matches_with(base, options)
+            })
+    }
+
+    /// Print the architecture and type of the debug file `module` if `path`,
+    /// the file it came from, is laid out like a symbol CD
+    fn print_module(&self, out: &mut Vec<u8>, path: &Path, module: &str) {
+        if !self.modules {
+            return;
+        }
+
+        if let Some(layout) = CdLayout::from_path(path) {
+            if self.format != Format::Text {
+                self.print_record(out, &Record::Module {
+                    arch: layout.arch,
+                    typ:  layout.typ,
+                    module,
+                });
+                return;
+            }
+
+            outln!(out, "{} {} {} {}", self.tag("M"), layout.arch,
+                layout.typ.unwrap_or("-"), module);
+        }
+    }
+
+    /// Print the exported name `name`
+    fn print_export(&self, out: &mut Vec<u8>, name: &str) {
+        if !self.prints_exports() || !self.matches(name) {
+            return;
+        }
+
+        if self.format != Format::Text {
+            self.print_record(out, &Record::Export { name });
+        } else {
+            outln!(out, "{} {}", self.tag("E"), name);
+        }
+    }
+
+    /// Print the `C` record for a debug file carved from `offset`
+    fn print_carved(&self, out: &mut Vec<u8>, offset: usize) {
+        if self.format != Format::Text {
+            self.print_record(out, &Record::Carved { offset });
+        } else {
+            outln!(out, "{} {:08x}", self.tag("C"), offset);
+        }
+    }
+
+    /// Print `record` in one of the machine readable formats
+    ///
+    /// Unwraps are fine as records only hold strings and numbers
+    fn print_record(&self, out: &mut Vec<u8>, record: &Record) {
+        match self.format {
+            Format::Text => unreachable!(),
+            Format::Jsonl => {
+                outln!(out, "{}", serde_json::to_string(record).unwrap());
+            }
+            Format::Msgpack => {
+                rmp_serde::encode::write_named(out, record).unwrap();
+            }
+            Format::Kallsyms => {
+                let (addr, typ, name) = match *record {
+                    Record::Function { addr, name, .. } => (addr, 'T', name),
+                    Record::Global { addr, category, name, .. } =>
+                        (addr, kallsyms_type(category), name),
+                    Record::Alias { addr, alias, .. } => (addr, 'W', alias),
+
+                    // Only symbols have an address and type to print
+                    _ => return,
+                };
+                outln!(out, "{:08x} {} {}", addr, typ, name);
+            }
+        }
+    }
+
+    /// Prefix what was printed to `out` from `start` on with the module of
+    /// `dbg` for `--with-module`. `name` is the file or archive member `dbg`
+    /// came from, used if the file doesn't give the name of its image.
+    ///
+    /// Text lines get the module in front of them, and the records of the
+    /// machine readable formats get a `module` key.
+    fn prefix_module(&self, out: &mut Vec<u8>, start: usize, dbg: &DbgFile,
+            name: &str) {
+        if !self.with_module {
+            return;
+        }
+
+        let module = dbg.image_name().map(Cow::Borrowed).unwrap_or_else(|| {
+            Path::new(name).file_name().unwrap_or_default().to_string_lossy()
+        });
+        let records = out.split_off(start);
+        match self.format {
+            Format::Text => {
+                for line in records.split_inclusive(|x| *x == b'\n') {
+                    write!(out, "{} ", module).unwrap();
+                    out.extend_from_slice(line);
+                }
+            }
+            Format::Jsonl => {
+                // Every line is an object, so the module goes in as its
+                // first key
+                let module = serde_json::to_string(&module).unwrap();
+                for line in records.split_inclusive(|x| *x == b'\n') {
+                    write!(out, "{{\"module\":{},", module).unwrap();
+                    out.extend_from_slice(&line[1..]);
+                }
+            }
+            Format::Msgpack => {
+                let mut records = &records[..];
+                while let Ok(record) = rmp_serde::from_read(&mut records) {
+                    rmp_serde::encode::write_named(out, &ModuleRecord {
+                        module: &module,
+                        record,
+                    }).unwrap();
+                }
+            }
+            Format::Kallsyms => {
+                // Modules go in brackets after the name, as they're bare
+                // names rather than image names
+                let module = dbg.module_name().unwrap_or(&module);
+                for line in records.split(|x| *x == b'\n')
+                        .filter(|x| !x.is_empty()) {
+                    out.extend_from_slice(line);
+                    outln!(out, "\t[{}]", module);
+                }
+            }
+        }
+    }
+
+    /// Get the record kind `tag` to print, colored if `color` is set
+    fn tag(&self, tag: &'static str) -> Cow<'static, str> {
+        if !self.color {
+            return Cow::Borrowed(tag);
+        }
+
+        let color = match tag {
+            "F" => 33,
+            "G" => 36,
+            "E" => 32,
+            "A" => 35,
+            _   => 34,
+        };
+        Cow::Owned(format!("\x1b[{}m{}\x1b[0m", color, tag))
+    }
+
+    /// Get the heading `text` to print, bold if `color` is set
+    fn heading<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if self.color {
+            Cow::Owned(format!("\x1b[1m{}\x1b[0m", text))
+        } else {
+            Cow::Borrowed(text)
+        }
+    }
+
+    /// Get the width to pad a column to, which is only done for `pretty`
+    fn pad(&self, width: usize) -> usize {
+        if self.pretty { width } else { 0 }
+    }
+
+    /// Leave an empty size column in `record` for records which have no
+    /// size, so the names of all records line up with `pretty`
+    fn pad_size(&self, record: &mut String) {
+        if self.pretty && self.sizes {
+            record.push_str("         ");
+        }
+    }
+
+    /// Get the address to print for `rva` in `dbg`, applying `--base`
+    fn addr(&self, dbg: &DbgFile, rva: u32) -> u64 {
+        let base = match self.base {
+            None              => 0,
+            Some(Base::Image) => dbg.header().image_base as u64,
+            Some(Base::Fixed(base)) => base,
+        };
+        base + rva as u64
+    }
+
+    /// Check if `rva` in `dbg` passes the `--section` filter
+    fn in_sections(&self, dbg: &DbgFile, rva: u32) -> bool {
+        let Some(sections) = &self.sections else { return true; };
+        dbg.section_for_rva(rva)
+            .is_some_and(|section| sections.contains(&section.name))
+    }
+
+    /// Check if `rva` in `dbg` passes the `--section` and `--range` filters
+    fn in_range(&self, dbg: &DbgFile, rva: u32) -> bool {
+        self.in_sections(dbg, rva) && self.range.as_ref()
+            .is_none_or(|x| x.contains(&self.addr(dbg, rva)))
+    }
+
+    /// Check if the record named `name` at `rva` in `dbg` passes the
+    /// `--section`, `--range`, and `--match` filters
+    fn wanted(&self, dbg: &DbgFile, rva: u32, name: &str) -> bool {
+        self.in_range(dbg, rva) && self.matches(name)
+    }
+
+    /// Check if the symbol `name`, before demangling, is hidden by
+    /// `--no-internal`
+    fn hides(&self, name: &str) -> bool {
+        self.no_internal && dbgparse::is_internal(name)
+    }
+
+    /// Check if `dbg` passes the `--newer-than` and `--older-than` filters
+    fn in_period(&self, dbg: &DbgFile) -> bool {
+        self.period.as_ref()
+            .is_none_or(|x| x.contains(&dbg.header().timedatestamp))
+    }
+
+    /// Check if `name` passes the `--match` filter
+    fn matches(&self, name: &str) -> bool {
+        self.name_match.as_ref().is_none_or(|x| x.is_match(name))
+    }
+
+    /// Check if exported names are printed, as they have no address they
+    /// can't pass the `--section` and `--range` filters, and aren't one of
+    /// the kinds of `--kind`
+    fn prints_exports(&self) -> bool {
+        self.sections.is_none() && self.range.is_none() && self.kinds.is_none()
+    }
+
+    /// Get the name of the section containing `rva` in `dbg`, or `-` if it's
+    /// not in any section
+    fn section(dbg: &DbgFile, rva: u32) -> &str {
+        dbg.section_for_rva(rva).map(|x| x.name.as_str()).unwrap_or("-")
+    }
+
+    /// Get the category of the section containing `rva` in `dbg`, or `-` if
+    /// it's not in any section
+    fn category(dbg: &DbgFile, rva: u32) -> &'static str {
+        dbg.category_for_rva(rva).map(|x| x.as_str()).unwrap_or("-")
+    }
+
+    /// Get the name to print for the symbol `name`
+    fn name<'a>(&self, mut name: &'a str) -> Cow<'a, str> {
+        if self.undecorate {
+            name = dbgparse::undecorate(name);
+        }
+
+        #[cfg(feature = "demangle")]
+        if self.demangle {
+            if let Some(demangled) = dbgparse::demangle(name) {
+                return Cow::Owned(demangled);
+            }
+        }
+
+        Cow::Borrowed(name)
+    }
+
+    /// Get `name` qualified with the module of `dbg` as `module!name` for
+    /// `--qualify`
+    fn qualify<'a>(&self, dbg: &DbgFile, name: &'a str) -> Cow<'a, str> {
+        match dbg.module_name().filter(|_| self.qualify) {
+            Some(module) => Cow::Owned(format!("{}!{}", module, name)),
+            None         => Cow::Borrowed(name),
+        }
+    }
+
+    /// Get the path to print for the source file `source`, remapped by
+    /// `--pathmap` and then normalized
+    fn source<'a>(&self, source: &'a str) -> Cow<'a, str> {
+        let mut ret = match self.path_map.iter().find_map(|x| x.apply(source)) {
+            Some(mapped) => Cow::Owned(mapped),
+            None         => Cow::Borrowed(source),
+        };
+
+        let bytes = ret.as_bytes();
+        if self.strip_drive && bytes.len() >= 2 &&
+                bytes[0].is_ascii_alphabetic() && bytes[1] == b':' {
+            ret = match ret {
+                Cow::Borrowed(x) => Cow::Borrowed(&x[2..]),
+                Cow::Owned(x)    => Cow::Owned(x[2..].to_string()),
+            };
+        }
+        if self.forward_slashes && ret.contains('\\') {
+            ret = Cow::Owned(ret.replace('\\', "/"));
+        }
+        if self.lowercase_paths && ret.chars().any(char::is_uppercase) {
+            ret = Cow::Owned(ret.to_lowercase());
+        }
+        ret
+    }
+}
+
+/// Base address to add to printed addresses
+#[derive(Clone, Copy)]
+enum Base {
+    /// Use the image base from the header of each file
+    Image,
+
+    /// Use a fixed base address
+    Fixed(u64),
+}
+
+/// Format of the printed records
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+enum Format {
+    /// One line of text per record, `F <addr> <name>` and so on
+    #[default]
+    Text,
+
+    /// One JSON object per line for each record
+    Jsonl,
+
+    /// One MessagePack map for each record, with the same fields as `Jsonl`
+    Msgpack,
+
+    /// One `address type name` line per symbol, the layout of Linux's
+    /// `/proc/kallsyms`
+    Kallsyms,
+}
+
+impl Format {
+    /// Parse a `--format`
+    fn parse(text: &str) -> std::result::Result<Self, String> {
+        match text {
+            "text"    => Ok(Self::Text),
+            "jsonl"   => Ok(Self::Jsonl),
+            "msgpack" => Ok(Self::Msgpack),
+            "kallsyms" => Ok(Self::Kallsyms),
+            _ => Err(format!("unknown format `{}`", text)),
+        }
+    }
+}
+
+/// Order of the printed records of each kind
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+enum SortOrder {
+    /// By address, the order they're kept in
+    #[default]
+    Addr,
+
+    /// By printed name, or by source file and line for lines
+    Name,
+
+    /// By function size, smallest first, and by address for everything else
+    Size,
+}
+
+impl SortOrder {
+    /// Parse a `--sort`
+    fn parse(text: &str) -> std::result::Result<Self, String> {
+        match text {
+            "addr" => Ok(Self::Addr),
+            "name" => Ok(Self::Name),
+            "size" => Ok(Self::Size),
+            _ => Err(format!("unknown sort order `{}`", text)),
+        }
+    }
+}
+
+/// A record as printed for the machine readable formats, tagged by `kind`
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum Record<'a> {
+    /// An `F` record, with the size of the function if it's known
+    Function {
+        addr: u64,
+        size: Option<u32>,
+        section: Option<&'a str>,
+        name: &'a str,
+    },
+
+    /// A `G` record
+    Global {
+        addr: u64,
+        section: Option<&'a str>,
+        category: Option<&'a str>,
+        name: &'a str,
+    },
+
+    /// An `E` record
+    Export {
+        name: &'a str,
+    },
+
+    /// An `A` record
+    Alias {
+        addr: u64,
+        alias: &'a str,
+        target: &'a str,
+    },
+
+    /// An `S` record
+    Line {
+        addr: u64,
+        source: &'a str,
+        line: u32,
+    },
+
+    /// An `M` record
+    Module {
+        arch: &'a str,
+        #[serde(rename = "type")]
+        typ: Option<&'a str>,
+        module: &'a str,
+    },
+
+    /// A `C` record
+    Carved {
+        offset: usize,
+    },
+
+    /// An address from `symbolize`, with the offset from its symbol or else
+    /// from the start of its module
+    Symbolized {
+        addr: u64,
+        module: Option<&'a str>,
+        symbol: Option<&'a str>,
+        offset: Option<u32>,
+        source: Option<&'a str>,
+        line: Option<u32>,
+    },
+}
+
+/// Where a debug file sits in the layout of an NT symbol CD, eg.
+/// `SUPPORT/DEBUG/MIPS/SYMBOLS/EXE/WRITE.DB_` or
+/// `SUPPORT/DEBUG/I386/SYMBOLS.CAB`
+struct CdLayout {
+    /// Architecture directory the file is under
+    arch: &'static str,
+
+    /// Module type directory the file is under, if any
+    typ: Option<&'static str>,
+}
+
+impl CdLayout {
+    /// Per-architecture directories
+    const ARCHES: [&'static str; 4] = ["i386", "alpha", "mips", "ppc"];
+
+    /// Per-module type directories
+    const TYPES: [&'static str; 9] =
+        ["acm", "com", "cpl", "dll", "drv", "exe", "ocx", "scr", "sys"];
+
+    /// Get the layout of the file at `path`, if it's under an architecture
+    /// directory
+    fn from_path(path: &Path) -> Option<Self> {
+        let mut ret: Option<Self> = None;
+        for component in path.parent()?.components() {
+            let name = component.as_os_str().to_string_lossy()
+                .to_ascii_lowercase();
+            if let Some(arch) = Self::ARCHES.iter().find(|x| **x == name) {
+                ret = Some(Self { arch, typ: None });
+            } else if let Some(layout) = &mut ret {
+                if let Some(typ) = Self::TYPES.iter().find(|x| **x == name) {
+                    layout.typ = Some(typ);
+                }
+            }
+        }
+        ret
+    }
+}
+
+/// A record of the machine readable formats with the module it came from,
+/// for `--with-module`
+#[derive(Serialize)]
+struct ModuleRecord<'a> {
+    /// Name of the module
+    module: &'a str,
+
+    /// The record, as decoded from what was printed
+    #[serde(flatten)]
+    record: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Set of record kinds to operate on
+#[derive(Clone, Copy, Default)]
+struct Kinds {
+    /// Include `F` records
+    functions: bool,
+
+    /// Include `G` records
+    globals: bool,
+
+    /// Include `S` records
+    lines: bool,
+}
+
+impl Kinds {
+    /// Parse a comma separated list of kinds, eg. `f,g`
+    fn parse(list: &str) -> std::result::Result<Self, String> {
+        let mut ret = Self::default();
+        for kind in list.split(',') {
+            match kind {
+                "f" => ret.functions = true,
+                "g" => ret.globals   = true,
+                "s" => ret.lines     = true,
+                _   => return Err(format!("unknown record kind `{}`", kind)),
+            }
+        }
+        Ok(ret)
+    }
+}
+
+/// A `--pathmap` rule, replacing the start of source paths
+#[derive(Clone)]
+struct PathMap {
+    /// Start of the paths to replace
+    from: String,
+
+    /// What to replace it with
+    to: String,
+}
+
+impl PathMap {
+    /// Parse a `from=to` rule
+    fn parse(text: &str) -> std::result::Result<Self, String> {
+        match text.split_once('=') {
+            Some((from, to)) if !from.is_empty() => Ok(Self {
+                from: from.to_string(),
+                to:   to.to_string(),
+            }),
+            _ => Err("expected `from=to`".to_string()),
+        }
+    }
+
+    /// Get `source` with `from` replaced by `to`, if it starts with `from`
+    /// ignoring ASCII case. The match has to end at a path separator, so
+    /// `d:\nt` doesn't match `d:\ntos`.
+    fn apply(&self, source: &str) -> Option<String> {
+        let head = source.get(..self.from.len())?;
+        let rest = &source[self.from.len()..];
+        let boundary = self.from.ends_with(['\\', '/']) || rest.is_empty() ||
+            rest.starts_with(['\\', '/']);
+        (boundary && head.eq_ignore_ascii_case(&self.from))
+            .then(|| format!("{}{}", self.to, rest))
+    }
+}
+
+/// Sink printing records to `out` as configured by `opts`
+struct Printer<'a> {
+    /// Output for the current file
+    out: &'a mut Vec<u8>,
+
+    /// Options controlling the printing
+    opts: &'a Options,
+}
+
+impl SymbolSink for Printer<'_> {
+    fn function(&mut self, dbg: &DbgFile, rva: u32, name: &str)
+            -> io::Result<()> {
+        // Skip demangling names which won't be printed
+        if !self.opts.kinds.is_none_or(|x| x.functions) ||
+                self.opts.hides(name) {
+            return Ok(());
+        }
+        self.opts.print_function(self.out, dbg, rva, &self.opts.name(name));
+        Ok(())
+    }
+
+    fn global(&mut self, dbg: &DbgFile, rva: u32, name: &str)
+            -> io::Result<()> {
+        if !self.opts.kinds.is_none_or(|x| x.globals) ||
+                self.opts.hides(name) {
+            return Ok(());
+        }
+        self.opts.print_global(self.out, dbg, rva, &self.opts.name(name));
+        Ok(())
+    }
+
+    fn export(&mut self, _dbg: &DbgFile, name: &str) -> io::Result<()> {
+        if !self.opts.hides(name) {
+            self.opts.print_export(self.out, &self.opts.name(name));
+        }
+        Ok(())
+    }
+
+    fn alias(&mut self, dbg: &DbgFile, alias: &str, target: &str, rva: u32)
+            -> io::Result<()> {
+        print_alias(self.out, dbg, alias, target, rva, self.opts);
+        Ok(())
+    }
+
+    fn line(&mut self, dbg: &DbgFile, rva: u32, source: &str, line: u32)
+            -> io::Result<()> {
+        self.opts.print_line(self.out, dbg, rva, source, line);
+        Ok(())
+    }
+}
+
+/// Dump information about `dbg` to `out`
+///
+/// Unwraps are fine as printing to a `Vec` never fails
+fn dump_info(out: &mut Vec<u8>, dbg: &DbgFile, opts: &Options) -> Result<()> {
+    let mut printer = Printer { out, opts };
+    if opts.sort == SortOrder::Addr {
+        dbg.emit(&mut printer).unwrap();
+        return Ok(());
+    }
+
+    // Everything starts out in address order, and the sorts are stable so
+    // ties stay that way
+    let mut functions = dbg.functions().iter().collect::<Vec<_>>();
+    let mut globals = dbg.globals().iter().collect::<Vec<_>>();
+    let mut exports = dbg.exported_names().iter().collect::<Vec<_>>();
+    let mut lines = dbg.lines().iter().collect::<Vec<_>>();
+    match opts.sort {
+        SortOrder::Addr => unreachable!(),
+        SortOrder::Name => {
+            functions.sort_by_cached_key(|x| opts.name(x.1).into_owned());
+            globals.sort_by_cached_key(|x| opts.name(x.1).into_owned());
+            exports.sort_by_cached_key(|x| opts.name(x).into_owned());
+            lines.sort_by_key(|(_, (source, line))| (source.clone(), *line));
+        }
+        SortOrder::Size => {
+            functions.sort_by_key(|x| dbg.function_size(*x.0));
+        }
+    }
+
+    for (rva, name) in functions {
+        printer.function(dbg, *rva, name).unwrap();
+    }
+    for (rva, name) in globals {
+        printer.global(dbg, *rva, name).unwrap();
+    }
+    for name in exports {
+        printer.export(dbg, name).unwrap();
+    }
+
+    // Aliases are kept in order of their names already
+    for (alias, (target, rva)) in dbg.aliases() {
+        printer.alias(dbg, alias, target, *rva).unwrap();
+    }
+    for (rva, (source, line)) in lines {
+        printer.line(dbg, *rva, source, *line).unwrap();
+    }
+    Ok(())
+}
+
+/// Print the alias `alias` of `target` at `rva` in `dbg`
+fn print_alias(out: &mut Vec<u8>, dbg: &DbgFile, alias: &str, target: &str,
+        rva: u32, opts: &Options) {
+    // Aliases aren't one of the kinds of `--kind`, and match by either name
+    if opts.kinds.is_some() || opts.hides(alias) || !opts.in_range(dbg, rva) ||
+            !(opts.matches(alias) || opts.matches(target)) {
+        return;
+    }
+
+    let alias = opts.qualify(dbg, &opts.name(alias)).into_owned();
+    let target = opts.qualify(dbg, &opts.name(target)).into_owned();
+    if opts.format != Format::Text {
+        opts.print_record(out, &Record::Alias {
+            addr:   opts.addr(dbg, rva),
+            alias:  &alias,
+            target: &target,
+        });
+        return;
+    }
+
+    let mut record = format!("{} {:08x}", opts.tag("A"), opts.addr(dbg, rva));
+    opts.pad_size(&mut record);
+    if opts.pretty && opts.verbose {
+        write!(record, " {:<8} {:<5}", Options::section(dbg, rva),
+            Options::category(dbg, rva)).unwrap();
+    }
+    outln!(out, "{} {} -> {}", record, alias, target);
+}
+
+/// Dump information about `dbg` to `out` for `--pretty`, with the records in
+/// each section together in address order under a heading
+fn dump_pretty(out: &mut Vec<u8>, dbg: &DbgFile, opts: &Options)
+        -> Result<()> {
+    // Format every record along with its address to sort on
+    let mut records = Vec::new();
+    for (rva, name) in dbg.functions().iter().filter(|x| !opts.hides(x.1)) {
+        let mut text = Vec::new();
+        opts.print_function(&mut text, dbg, *rva, &opts.name(name));
+        records.push((*rva, text));
+    }
+    for (rva, name) in dbg.globals().iter().filter(|x| !opts.hides(x.1)) {
+        let mut text = Vec::new();
+        opts.print_global(&mut text, dbg, *rva, &opts.name(name));
+        records.push((*rva, text));
+    }
+    for (alias, (target, rva)) in dbg.aliases().iter() {
+        let mut text = Vec::new();
+        print_alias(&mut text, dbg, alias, target, *rva, opts);
+        records.push((*rva, text));
+    }
+    for (rva, (source, line)) in dbg.lines().iter() {
+        let mut text = Vec::new();
+        opts.print_line(&mut text, dbg, *rva, source, *line);
+        records.push((*rva, text));
+    }
+
+    // The sort is stable, so records at the same address stay in kind order
+    records.sort_by_key(|x| x.0);
+
+    let mut section = None;
+    for (rva, text) in records.iter().filter(|x| !x.1.is_empty()) {
+        let name = dbg.section_for_rva(*rva).map(|x| x.name.as_str());
+        if section != Some(name) {
+            if section.is_some() {
+                out.push(b'\n');
+            }
+            outln!(out, "{}", opts.heading(name.unwrap_or("(no section)")));
+            section = Some(name);
+        }
+        out.extend_from_slice(text);
+    }
+
+    // Exported names have no address so go at the end
+    if opts.prints_exports() && !dbg.exported_names().is_empty() {
+        if section.is_some() {
+            out.push(b'\n');
+        }
+        outln!(out, "{}", opts.heading("(exports)"));
+        for name in dbg.exported_names().iter().filter(|x| !opts.hides(x)) {
+            opts.print_export(out, &opts.name(name));
+        }
+    }
+
+    Ok(())
+}
+
+/// Print all addresses of symbols in `dbg` named exactly `name`, which may
+/// be qualified like `ntoskrnl!KeBugCheckEx` to only look in that module
+fn find_symbol(out: &mut Vec<u8>, dbg: &DbgFile, name: &str, opts: &Options)
+        -> Result<()> {
+    let (module, name) = dbgparse::split_qualified(name);
+    if !in_module(dbg, module) {
+        return Ok(());
+    }
+
+    for &(rva, kind) in dbg.find_symbol(name) {
+        match kind {
+            SymbolKind::Function => {
+                opts.print_function(out, dbg, rva, &opts.name(name));
+            }
+            SymbolKind::Global => {
+                opts.print_global(out, dbg, rva, &opts.name(name));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Check if `dbg` is the module `module` of a qualified name, ignoring case.
+/// Every file is in the module of an unqualified name.
+fn in_module(dbg: &DbgFile, module: Option<&str>) -> bool {
+    module.is_none_or(|module| {
+        dbg.module_name().is_some_and(|x| x.eq_ignore_ascii_case(module))
+    })
+}
+
+/// Format a Unix `timestamp` as a human-readable UTC date and time
+fn format_timestamp(timestamp: u32) -> String {
+    let (days, secs) = (timestamp / 86400, timestamp % 86400);
+
+    // Convert days since the epoch to a civil date, from Howard Hinnant's
+    // `civil_from_days`
+    let z     = days + 719468;
+    let era   = z / 146097;
+    let doe   = z - era * 146097;
+    let yoe   = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy   = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp    = (5 * doy + 2) / 153;
+    let day   = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year  = yoe + era * 400 + (month <= 2) as u32;
+
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC", year, month, day,
+        secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// Parse a `--newer-than` or `--older-than` date, `YYYY-MM-DD` for the
+/// start of that day (UTC), or a raw timestamp in hex such as `0x2f6c1a3b`
+fn parse_date(text: &str) -> std::result::Result<u32, String> {
+    if let Some(hex) = text.strip_prefix("0x") {
+        return u32::from_str_radix(hex, 16)
+            .map_err(|err| format!("bad timestamp `{}`: {}", text, err));
+    }
+
+    let err = || format!("expected `YYYY-MM-DD` or a hex timestamp, got `{}`",
+        text);
+    let mut fields = text.splitn(3, '-').map(|x| x.parse::<u64>().ok());
+    let (Some(Some(year)), Some(Some(month)), Some(Some(day))) =
+            (fields.next(), fields.next(), fields.next()) else {
+        return Err(err());
+    };
+
+    // Timestamps are seconds since 1970 in 32 bits, which run out in 2106
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let month_days = match month {
+        2              => 28 + leap as u64,
+        4 | 6 | 9 | 11 => 30,
+        _              => 31,
+    };
+    if !(1970..=2106).contains(&year) || !(1..=12).contains(&month) ||
+            !(1..=month_days).contains(&day) {
+        return Err(err());
+    }
+
+    // Convert the civil date to days since the epoch, from Howard Hinnant's
+    // `days_from_civil`
+    let year = if month <= 2 { year - 1 } else { year };
+    let era  = year / 400;
+    let yoe  = year - era * 400;
+    let doy  = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe  = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+    u32::try_from(days * 86400).map_err(|_| err())
+}
+
+/// Print the `IMAGE_SEPARATE_DEBUG_HEADER` and debug directories of `dbg`
+fn dump_header(out: &mut Vec<u8>, dbg: &DbgFile) -> Result<()> {
+    let header = dbg.header();
+    outln!(out, "Flags:           {:#06x}", header.flags);
+    outln!(out, "Machine:         {:#06x} ({:?})", u16::from(header.machine),
+        header.machine);
+    outln!(out, "Characteristics: {:#06x}", header.characteristics);
+    outln!(out, "Timestamp:       {:#010x} ({})", header.timedatestamp,
+        format_timestamp(header.timedatestamp));
+    outln!(out, "Checksum:        {:#010x}", header.checksum);
+    outln!(out, "Image base:      {:#010x}", header.image_base);
+    outln!(out, "Size of image:   {:#010x}", header.size_of_image);
+    outln!(out, "Section align:   {:#010x}", header.section_align);
+    outln!(out, "Image name:      {}", dbg.image_name().unwrap_or("-"));
+
+    // Ranges from the COFF symbols header
+    let range = |range: Option<RangeInclusive<u32>>| {
+        range.map(|x| format!("{:#010x}-{:#010x}", x.start(), x.end()))
+            .unwrap_or_else(|| "-".to_string())
+    };
+    outln!(out, "Code range:      {}", range(dbg.code_range()));
+    outln!(out, "Data range:      {}", range(dbg.data_range()));
+
+    outln!(out, "Debug directories:");
+    outln!(out, "    {:<20} {:<7} {:<8} {:<8} {:<8} Timestamp",
+        "Type", "Version", "Size", "RVA", "Offset");
+    for dd in dbg.debug_directories() {
+        outln!(out, "    {:<20} {:<7} {:08x} {:08x} {:08x} {}",
+            format!("{:?}", dd.typ),
+            format!("{}.{}", dd.major_version, dd.minor_version),
+            dd.size_of_data, dd.addr_raw_data, dd.ptr_raw_data,
+            format_timestamp(dd.timedatestamp));
+    }
+
+    Ok(())
+}
+
+/// Print the section table of `dbg`
+fn dump_sections(out: &mut Vec<u8>, dbg: &DbgFile) -> Result<()> {
+    outln!(out, "{:<8} {:<8} {:<8} {:<8} {:<8} Characteristics",
+        "Name", "VA", "VSize", "RawSize", "RawPtr");
+    for section in dbg.sections() {
+        outln!(out, "{:<8} {:08x} {:08x} {:08x} {:08x} {:08x}",
+            section.name, section.vaddr, section.vsize, section.raw_data_sz,
+            section.ptr_raw_data, section.characteristics);
+    }
+
+    Ok(())
+}
+
+/// Print a `#` bar for `count` out of `max`, at most 40 characters long
+fn histogram_bar(count: usize, max: usize) -> String {
+    "#".repeat((count * 40).div_ceil(max.max(1)))
+}
+
+/// Print counts of the records in `dbg`, broken down by section, along with
+/// histograms of function sizes and line records per source file
+fn dump_stats(out: &mut Vec<u8>, dbg: &DbgFile, opts: &Options)
+        -> Result<()> {
+    let by_file = dbg.lines_by_file();
+    outln!(out, "Functions:       {}", dbg.functions().len());
+    outln!(out, "Globals:         {}", dbg.globals().len());
+    outln!(out, "Exported names:  {}", dbg.exported_names().len());
+    outln!(out, "Aliases:         {}", dbg.aliases().len());
+    outln!(out, "Source files:    {}", by_file.len());
+    outln!(out, "Source lines:    {}", dbg.lines().len());
+
+    // Line records per KiB of code, and how many functions have any
+    let mut code_size = 0u64;
+    let mut with_lines = 0;
+    for &rva in dbg.functions().keys() {
+        let size = dbg.function_size(rva).unwrap_or(0);
+        code_size += size as u64;
+        if dbg.lines().range(rva..rva.saturating_add(size.max(1)))
+                .next().is_some() {
+            with_lines += 1;
+        }
+    }
+    outln!(out, "Code size:       {:#x}", code_size);
+    if code_size > 0 {
+        outln!(out, "Line density:    {:.2} per KiB of code",
+            dbg.lines().len() as f64 * 1024. / code_size as f64);
+    }
+    if !dbg.functions().is_empty() {
+        outln!(out, "With lines:      {} of {} functions ({:.1}%)",
+            with_lines, dbg.functions().len(),
+            with_lines as f64 * 100. / dbg.functions().len() as f64);
+    }
+
+    // Count records in each section, with `-` for those outside of all
+    let mut sections: Vec<(&str, [usize; 3])> = dbg.sections().iter()
+        .map(|section| (section.name.as_str(), [0; 3])).collect();
+    sections.push(("-", [0; 3]));
+    let mut count = |rva: u32, kind: usize| {
+        let idx = dbg.sections().iter().position(|x| x.contains(rva))
+            .unwrap_or(sections.len() - 1);
+        sections[idx].1[kind] += 1;
+    };
+    dbg.functions().keys().for_each(|&rva| count(rva, 0));
+    dbg.globals().keys().for_each(|&rva| count(rva, 1));
+    dbg.lines().keys().for_each(|&rva| count(rva, 2));
+
+    outln!(out, "Sections:");
+    outln!(out, "    {:<8} {:>9} {:>9} {:>9}",
+        "Name", "Functions", "Globals", "Lines");
+    for (name, [functions, globals, lines]) in &sections {
+        if *name == "-" && functions + globals + lines == 0 {
+            continue;
+        }
+        outln!(out, "    {:<8} {:>9} {:>9} {:>9}",
+            name, functions, globals, lines);
+    }
+
+    // Histogram of function sizes in power of two buckets
+    let mut sizes = BTreeMap::new();
+    for &rva in dbg.functions().keys() {
+        let size = dbg.function_size(rva).unwrap_or(0);
+        *sizes.entry(size.max(1).next_power_of_two()).or_insert(0) += 1;
+    }
+    let max = sizes.values().copied().max().unwrap_or(0);
+    outln!(out, "Function sizes:");
+    for (bucket, count) in &sizes {
+        outln!(out, "    <= {:<8} {:>9} {}", format!("{:#x}", bucket), count,
+            histogram_bar(*count, max));
+    }
+
+    // The source files with the most line records
+    let mut files = by_file.iter()
+        .map(|(source, lines)| (lines.len(), *source)).collect::<Vec<_>>();
+    files.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(b.1)));
+    let max = files.first().map(|x| x.0).unwrap_or(0);
+    outln!(out, "Most lines:");
+    for (count, source) in files.iter().take(10) {
+        outln!(out, "    {:>9} {:<40} {}", count, histogram_bar(*count, max),
+            opts.source(source));
+    }
+
+    Ok(())
+}
+
+/// Print the line table of `dbg` grouped by source file
+fn dump_sources(out: &mut Vec<u8>, dbg: &DbgFile, opts: &Options)
+        -> Result<()> {
+    for (source, lines) in dbg.lines_by_file() {
+        outln!(out, "{}", opts.source(source));
+        for (line, rva) in lines {
+            outln!(out, "    {:>6} {:08x}", line, opts.addr(dbg, rva));
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the problems with the function extents of `dbg`, one per line
+fn dump_overlaps(out: &mut Vec<u8>, dbg: &DbgFile, opts: &Options)
+        -> Result<()> {
+    let name = |rva: u32| {
+        dbg.functions().get(&rva).or_else(|| dbg.globals().get(&rva))
+            .map(|x| opts.name(x)).unwrap_or_default()
+    };
+    let range = |x: &Range<u32>| {
+        format!("{:08x}-{:08x} {}", opts.addr(dbg, x.start),
+            opts.addr(dbg, x.end), name(x.start))
+    };
+
+    for finding in overlap::check(dbg) {
+        match finding {
+            Finding::Overlap { first, second } => {
+                outln!(out, "overlap  {} {}", range(&first), range(&second));
+            }
+            Finding::Nested { outer, inner } => {
+                outln!(out, "nested   {} {:08x} {}", range(&outer),
+                    opts.addr(dbg, inner), name(inner));
+            }
+            Finding::Global { function, global } => {
+                outln!(out, "global   {} {:08x} {}", range(&function),
+                    opts.addr(dbg, global), name(global));
+            }
+            Finding::Empty { function, end } => {
+                outln!(out, "empty    {:08x} {:08x} {}",
+                    opts.addr(dbg, function), opts.addr(dbg, end),
+                    name(function));
+            }
+            Finding::Mismatch { function, symbols_end, table_end } => {
+                outln!(out, "mismatch {:08x} {} .ef {:08x} pdata {:08x}",
+                    opts.addr(dbg, function), name(function),
+                    opts.addr(dbg, symbols_end), opts.addr(dbg, table_end));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the hit count of each function and source line in `dbg` for the
+/// sorted `hits`, then the line coverage of each source file
+fn dump_coverage(out: &mut Vec<u8>, dbg: &DbgFile, hits: &[coverage::Hit],
+        opts: &Options) -> Result<()> {
+    for (rva, count) in coverage::function_hits(dbg, hits) {
+        outln!(out, "F {:08x} {} {}", opts.addr(dbg, rva), count,
+            opts.name(&dbg.functions()[&rva]));
+    }
+
+    let line_hits = coverage::line_hits(dbg, hits);
+    let mut files: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    for (rva, count) in &line_hits {
+        let (source, line) = &dbg.lines()[rva];
+        outln!(out, "S {:08x} {} {}:{}", opts.addr(dbg, *rva), count,
+            opts.source(source), line);
+
+        let file = files.entry(source).or_default();
+        file.0 += (*count > 0) as usize;
+        file.1 += 1;
+    }
+
+    for (source, (covered, total)) in files {
+        outln!(out, "P {}/{} {:.1}% {}", covered, total,
+            covered as f64 * 100. / total as f64, opts.source(source));
+    }
+
+    Ok(())
+}
+
+/// Print all records in `dbg` of a kind in `kinds` that match `re`
+///
+/// Functions and globals match on their printed name, source lines match on
+/// their `source:line` text.
+fn grep(out: &mut Vec<u8>, dbg: &DbgFile, re: &Regex, kinds: Kinds,
+        opts: &Options) -> Result<()> {
+    if kinds.functions {
+        for (rva, name) in dbg.functions().iter().filter(|x| !opts.hides(x.1)) {
+            let name = opts.name(name);
+            if re.is_match(&name) {
+                opts.print_function(out, dbg, *rva, &name);
+            }
+        }
+    }
+
+    if kinds.globals {
+        for (rva, name) in dbg.globals().iter().filter(|x| !opts.hides(x.1)) {
+            let name = opts.name(name);
+            if re.is_match(&name) {
+                opts.print_global(out, dbg, *rva, &name);
+            }
+        }
+    }
+
+    if kinds.lines {
+        for (rva, (source, line)) in dbg.lines().iter() {
+            if re.is_match(&format!("{}:{}", source, line)) {
+                opts.print_line(out, dbg, *rva, source, *line);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Print all addresses in `dbg` for `lines` of the source file `source`
+fn find_lines(out: &mut Vec<u8>, dbg: &DbgFile, source: &str,
+        lines: RangeInclusive<u32>, opts: &Options) -> Result<()> {
+    for (rva, source, line) in dbg.line_addresses(source, lines) {
+        opts.print_line(out, dbg, rva, source, line);
+    }
+
+    Ok(())
+}
+
+/// Parse a hexadecimal number, with or without a `0x` prefix
+fn parse_hex(text: &str) -> std::result::Result<u32, ParseIntError> {
+    let text = text.strip_prefix("0x").unwrap_or(text);
+    u32::from_str_radix(text, 16)
+}
+
+/// Parse a `--base` address, `image` for the image base of each file or a
+/// hexadecimal number
+fn parse_base(text: &str) -> std::result::Result<Base, ParseIntError> {
+    if text == "image" {
+        return Ok(Base::Image);
+    }
+
+    let text = text.strip_prefix("0x").unwrap_or(text);
+    u64::from_str_radix(text, 16).map(Base::Fixed)
+}
+
+/// Parse a `--range`, hex addresses around `..` with the end exclusive.
+/// Leaving off the start or end leaves that side open.
+fn parse_range(text: &str) -> std::result::Result<Range<u64>, String> {
+    let (start, end) = text.split_once("..")
+        .ok_or_else(|| "expected `start..end`".to_string())?;
+    let parse = |x: &str, default| {
+        if x.is_empty() {
+            return Ok(default);
+        }
+        u64::from_str_radix(x.strip_prefix("0x").unwrap_or(x), 16)
+            .map_err(|err| format!("bad address `{}`: {}", x, err))
+    };
+    Ok(parse(start, 0)?..parse(end, u64::MAX)?)
+}
+
+/// Parse a `--names` decoding of names which aren't valid UTF-8
+fn parse_names(text: &str) -> std::result::Result<NameDecoding, String> {
+    match text {
+        "lossy"   => Ok(NameDecoding::Lossy),
+        "replace" => Ok(NameDecoding::Replace),
+        "error"   => Ok(NameDecoding::Error),
+        _ => Err(format!("unknown name decoding `{}`", text)),
+    }
+}
+
+/// Parse a `--machine`, the name of an architecture on an NT CD or an
+/// `IMAGE_FILE_MACHINE_*` value in hex
+fn parse_machine(text: &str) -> std::result::Result<Machine, String> {
+    Ok(match text.to_ascii_lowercase().as_str() {
+        "i386"  => Machine::I386,
+        "mips"  => Machine::R4000,
+        "alpha" => Machine::Alpha,
+        "ppc"   => Machine::PowerPc,
+        _ => parse_hex(text).ok().and_then(|x| u16::try_from(x).ok())
+            .map(Machine::from)
+            .ok_or_else(|| format!("unknown machine `{}`", text))?,
+    })
+}
+
+/// A `source:line` or `source:first-last` specification
+#[derive(Clone)]
+struct LineSpec {
+    /// Source file, or the trailing part of its path
+    source: String,
+
+    /// Lines in the source file
+    lines: RangeInclusive<u32>,
+}
+
+/// Parse a `source:line` or `source:first-last` specification
+fn parse_line_spec(spec: &str) -> std::result::Result<LineSpec, String> {
+    let err = || "expected `source:line` or `source:first-last`".to_string();
+    let (source, lines) = spec.rsplit_once(':').ok_or_else(err)?;
+    let (first, last) = lines.split_once('-').unwrap_or((lines, lines));
+    Ok(LineSpec {
+        source: source.to_string(),
+        lines:  first.parse().map_err(|_| err())?..=
+            last.parse().map_err(|_| err())?,
+    })
+}
+
+/// Parse the debug file `name` from `reader`, reporting any diagnostics to
+/// `stderr`
+///
+/// Files without an image name are named as a module after `name` itself.
+fn load_dbg(reader: impl Read + Seek, name: &str, opts: &Options)
+        -> Result<DbgFile> {
+    let mut dbg = DbgFile::load_with(reader, &opts.load)?;
+    if dbg.module_name().is_none() {
+        dbg.set_module_name(dbgparse::module_stem(name));
+    }
+    let stats = &opts.stats;
+    stats.files.fetch_add(1, Ordering::Relaxed);
+    if opts.stats_mem {
+        *stats.memory.lock().unwrap() += &dbg.memory_footprint();
+    }
+
+    let warn = |diag: &Diagnostic| {
+        stats.warnings.fetch_add(1, Ordering::Relaxed);
+        if !opts.quiet {
+            eprintln!("{}: warning: {}", name, diag);
+        }
+    };
+
+    // Don't force a lazy load to parse symbols just to report on them, but
+    // do report problems with the rest of the file
+    if opts.load.lazy {
+        dbg.load_diagnostics().iter().for_each(warn);
+        return Ok(dbg);
+    }
+
+    dbg.diagnostics().for_each(warn);
+
+    stats.functions.fetch_add(dbg.functions().len(), Ordering::Relaxed);
+    stats.globals.fetch_add(dbg.globals().len(), Ordering::Relaxed);
+    stats.lines.fetch_add(dbg.lines().len(), Ordering::Relaxed);
+    if opts.file_stats {
+        eprintln!("{}: {} functions, {} globals, {} lines", name,
+            dbg.functions().len(), dbg.globals().len(), dbg.lines().len());
+    }
+    Ok(dbg)
+}
+
+/// Parse every debug file contained in `file` and invoke `callback` on each,
+/// with output going to `out`
+///
+/// `file` may either be a `DI` file or a CAB containing `DI` files. Files of
+/// up to `MAX_READ_WHOLE` bytes are read into memory once and parsed from
+/// there, larger ones such as CD images are read as they're parsed.
+#[cfg(not(feature = "mmap"))]
+fn for_each_dbg(file: &str, opts: &Options, out: &mut Vec<u8>,
+        errors: &mut Vec<String>,
+        callback: &(impl Fn(&mut Vec<u8>, &DbgFile) -> Result<()> + Sync))
+        -> Result<()> {
+    let open_err = |x| Error::Open(Path::new(file).to_path_buf(), x);
+    if std::fs::metadata(file).map_err(open_err)?.len() <= MAX_READ_WHOLE {
+        let data = std::fs::read(file).map_err(open_err)?;
+        return for_each_dbg_in(file, &|| Ok(Cursor::new(&data[..])), opts,
+            out, errors, callback);
+    }
+
+    for_each_dbg_in(file, &|| {
+        File::open(file).map(std::io::BufReader::new).map_err(open_err)
+    }, opts, out, errors, callback)
+}
+
+/// Parse every debug file contained in `file` and invoke `callback` on each,
+/// with output going to `out`
+///
+/// `file` may either be a `DI` file or a CAB containing `DI` files. The file
+/// is mapped into memory once and parsed directly from the mapping.
+#[cfg(feature = "mmap")]
+fn for_each_dbg(file: &str, opts: &Options, out: &mut Vec<u8>,
+        errors: &mut Vec<String>,
+        callback: &(impl Fn(&mut Vec<u8>, &DbgFile) -> Result<()> + Sync))
+        -> Result<()> {
+    let open_err = |x| Error::Open(Path::new(file).to_path_buf(), x);
+    let fd = File::open(file).map_err(open_err)?;
+
+    // Safety: the mapping outlives every parse and nothing borrows from it
+    // once we return. The file must not be modified by someone else while we
+    // are parsing it.
+    let map = unsafe { memmap2::Mmap::map(&fd) }.map_err(open_err)?;
+
+    for_each_dbg_in(file, &|| Ok(Cursor::new(&map[..])), opts, out, errors,
+        callback)
+}
+
+/// Parse every debug file contained in `file` and invoke `callback` on each,
+/// using `open` to get a fresh reader at the start of `file`
+///
+/// `file` may be a `DI` file, or a CAB or ZIP of them. A member which fails
+/// doesn't stop the others, its error message is added to `errors` instead.
+/// Members which are archives themselves are searched too. Files and members
+/// compressed by `compress.exe` are expanded first.
+fn for_each_dbg_in<R: Read + Seek + Send>(file: &str,
+        open: &dyn Fn() -> Result<R>, opts: &Options, out: &mut Vec<u8>,
+        errors: &mut Vec<String>,
+        callback: &(impl Fn(&mut Vec<u8>, &DbgFile) -> Result<()> + Sync))
+        -> Result<()> {
+    // Expand SZDD and KWAJ files in memory and start over on the result
+    let mut magic = [0u8; 8];
+    if open()?.read_exact(&mut magic).is_ok() &&
+            expand::is_compressed(&magic) {
+        let mut data = Vec::new();
+        open()?.read_to_end(&mut data)
+            .map_err(|x| Error::Consume("compressed file", 0, x))?;
+        let data = expand::decompress(&data)?;
+        return for_each_dbg_in(file, &|| Ok(Cursor::new(&data[..])), opts,
+            out, errors, callback);
+    }
+
+    // Attempt to parse as a CD image
+    #[cfg(feature = "iso")]
+    if dbgparse::iso::is_iso(&mut open()?) {
+        let mut image = dbgparse::iso::IsoImage::new(open()?)?;
+        for diag in image.diagnostics() {
+            opts.stats.warnings.fetch_add(1, Ordering::Relaxed);
+            if !opts.quiet {
+                eprintln!("{}: warning: {}", file, diag);
+            }
+        }
+        let iso_files = image.files().iter()
+            .filter(|x| is_symbol_path(&x.path) && opts.wants_member(&x.path))
+            .cloned().collect::<Vec<_>>();
+
+        let members = iso_files.into_iter().map(|entry| {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::debug_span!("extract", file,
+                member = %entry.path).entered();
+
+            let data = image.read(&entry);
+            (entry.path, data)
+        });
+        for_each_member(file, members, opts, out, errors, callback);
+        return Ok(());
+    }
+
+    // Attempt to parse as a ZIP archive
+    if is_zip(&magic) {
+        let zip_err = |x: zip::result::ZipError| Error::ExtractZip(x.into());
+        let mut archive = zip::ZipArchive::new(open()?).map_err(zip_err)?;
+
+        // Only look at the files which may be debug files
+        let mut zip_files = Vec::new();
+        for idx in 0..archive.len() {
+            let entry = archive.by_index_raw(idx).map_err(zip_err)?;
+            if entry.is_file() && is_dbg_file(Path::new(entry.name())) &&
+                    opts.wants_member(entry.name()) {
+                zip_files.push((idx, entry.name().to_string()));
+            }
+        }
 
-/// Wrapper type for `Result`
-type Result<T> = std::result::Result<T, Error>;
+        // Extract the files one at a time, as for CABs
+        let members = zip_files.into_iter().map(|(idx, name)| {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::debug_span!("extract", file, member = %name)
+                .entered();
+
+            let mut data = Vec::new();
+            let extracted = archive.by_index(idx).map_err(zip_err)
+                .and_then(|mut reader| {
+                    reader.read_to_end(&mut data).map_err(Error::ExtractZip)
+                });
+            (name, extracted.map(|_| data))
+        });
+        for_each_member(file, members, opts, out, errors, callback);
+        return Ok(());
+    }
 
-/// Error types
-#[derive(Debug)]
-pub enum Error {
-    /// Failed to open input file
-    Open(PathBuf, std::io::Error),
+    // CABs can't be read without the `cab` feature, say so rather than
+    // failing on the magic
+    #[cfg(not(feature = "cab"))]
+    if magic.starts_with(b"MSCF") {
+        return Err(Error::ExtractCab(io::Error::new(
+            io::ErrorKind::Unsupported, "built without the `cab` feature")));
+    }
 
-    /// File was not a debug info file
-    NotDebugInfo,
+    // Attempt to parse as a cabinet file
+    #[cfg(feature = "cab")]
+    if let Ok(mut cabinet) = cab::Cabinet::new(open()?) {
+        let mut cab_files = Vec::new();
 
-    /// Failed to consume a field from the file
-    Consume(&'static str, std::io::Error),
+        // Go through all files and folders
+        for folder in cabinet.folder_entries() {
+            for file in folder.file_entries() {
+                if opts.wants_member(file.name()) {
+                    cab_files.push(file.name().to_string());
+                }
+            }
+        }
 
-    /// Exported name was not valid UTF-8
-    ExportedNameUtf8(std::str::Utf8Error),
-    
-    /// Symbol table string name was not valid UTF-8
-    StringNameUtf8(std::str::Utf8Error),
-    
-    /// A source filename had an invalid UTF-8 character
-    FilenameUtf8(std::str::Utf8Error),
-
-    /// A debug type specified in a [`DebugDirectory`] was invalid
-    InvalidDebugType(u32),
-
-    /// Failed to seek to the COFF section
-    SeekCoff(std::io::Error),
-
-    /// Failed to skip over exported names
-    SkipExportedNames(std::io::Error),
-
-    /// COFF debug referenced out-of-bounds string for symbol name
-    SymbolNameOob,
-
-    /// Got a symbol class that was unknown
-    UnknownSymbolClass(u8),
-
-    /// Failed to extract a file from the CAB
-    ExtractCab(std::io::Error),
-}
-
-/// Consume bytes from a reader
-macro_rules! consume {
-    ($reader:expr, $ty:ty, $field:expr) => {{
-        // Create buffer for type
-        let mut tmp = [0u8; size_of::<$ty>()];
-
-        // Read the bytes and convert
-        $reader.read_exact(&mut tmp).map(|_| {
-            <$ty>::from_le_bytes(tmp)
-        }).map_err(|x| Error::Consume($field, x))
-    }};
-
-    ($reader:expr, $size:expr, $field:expr) => {{
-        // Create buffer for type
-        let mut tmp = [0u8; $size];
-
-        // Read the bytes and convert
-        $reader.read_exact(&mut tmp).map(|_| {
-            tmp
-        }).map_err(|x| Error::Consume($field, x))
-    }};
-}
-
-/// Debug directory types
-#[derive(Debug)]
-#[repr(u32)]
-enum DebugType {
-    ///  Unknown value, ignored by all tools. 
-    Unknown = 0,
-
-    /// COFF debugging information (line numbers, symbol table, and
-    /// string table). This type of debugging information is also
-    /// pointed to by fields in the file headers. 
-    Coff = 1,
-
-    /// CodeView debugging information. The format of the data block is
-    /// described by the CodeView 4.0 specification. 
-    CodeView = 2,
-
-    /// Frame pointer omission (FPO) information. This information
-    /// tells the debugger how to interpret nonstandard stack frames,
-    /// which use the EBP register for a purpose other than as a frame
-    /// pointer. 
-    FramePointerOmission = 3,
-
-    /// Miscellaneous information. 
-    Misc = 4,
-
-    /// Exception information. 
-    Exception = 5,
-
-    /// Fixup information. 
-    Fixup = 6,
-
-    /// Omap to source
-    OmapToSrc = 7,
-
-    /// Omap from source
-    OmapFromSrc = 8,
-
-    /// Borland debugging information. 
-    Borland = 9,
-}
-
-impl TryFrom<u32> for DebugType {
-    type Error = Error;
-
-    fn try_from(val: u32) -> Result<Self> {
-        Ok(match val {
-            0 => Self::Unknown,
-            1 => Self::Coff,
-            2 => Self::CodeView,
-            3 => Self::FramePointerOmission,
-            4 => Self::Misc,
-            5 => Self::Exception,
-            6 => Self::Fixup,
-            7 => Self::OmapToSrc,
-            8 => Self::OmapFromSrc,
-            9 => Self::Borland,
-            _ => return Err(Error::InvalidDebugType(val)),
-        })
+        // Extract the files one at a time, decompression is inherently
+        // serial, while the files already extracted are parsed in parallel
+        let members = cab_files.into_iter().map(|name| {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::debug_span!("extract", file, member = %name)
+                .entered();
+
+            let mut data = Vec::new();
+            let extracted = cabinet.read_file(&name)
+                .and_then(|mut reader| reader.read_to_end(&mut data))
+                .map_err(Error::ExtractCab);
+            (name, extracted.map(|_| data))
+        });
+        for_each_member(file, members, opts, out, errors, callback);
+        return Ok(());
+    }
+
+    // Didn't seem to be an archive, attempt to parse as `DI`
+    let dbg = load_dbg(open()?, file, opts)?;
+    if !opts.in_period(&dbg) {
+        return Ok(());
+    }
+    let path = Path::new(file);
+    let start = out.len();
+    opts.print_module(out, path,
+        &path.file_name().unwrap_or_default().to_string_lossy());
+    let ret = callback(out, &dbg);
+    opts.prefix_module(out, start, &dbg, file);
+    ret?;
+
+    Ok(())
+}
+
+/// Parse the `members` extracted from the archive `file`, given as (name,
+/// contents), in parallel and invoke `callback` on each debug file
+///
+/// The output of each member is added to `out` in the order of `members`.
+/// Members which aren't `DI` files are skipped with a warning, other failures
+/// are added to `errors`.
+fn for_each_member(file: &str,
+        members: impl ExactSizeIterator<Item = (String, Result<Vec<u8>>)> +
+            Send,
+        opts: &Options, out: &mut Vec<u8>, errors: &mut Vec<String>,
+        callback: &(impl Fn(&mut Vec<u8>, &DbgFile) -> Result<()> + Sync)) {
+    // Each member counts towards the progress of the archive
+    if let Some(progress) = &opts.progress {
+        progress.inc_length(members.len() as u64);
+    }
+
+    let members = members.enumerate().par_bridge();
+    let mut results = members.map(|(idx, (name, data))| {
+        let mut out = Vec::new();
+        let mut errors = Vec::new();
+        let ret = data.and_then(|data| {
+            let data = if expand::is_compressed(&data) {
+                expand::decompress(&data)?
+            } else {
+                data
+            };
+
+            // Some distributions put archives inside of archives
+            if data.starts_with(b"MSCF") || is_zip(&data) {
+                return for_each_dbg_in(&format!("{}/{}", file, name),
+                    &|| Ok(Cursor::new(&data[..])), opts, &mut out,
+                    &mut errors, callback);
+            }
+
+            // ZIPs may keep the layout of a symbol CD inside of them
+            let dbg = load_dbg(Cursor::new(&data[..]), &name, opts)?;
+            if !opts.in_period(&dbg) {
+                return Ok(());
+            }
+            let path = Path::new(file).join(&name);
+            opts.print_module(&mut out, &path,
+                &path.file_name().unwrap_or_default().to_string_lossy());
+            let ret = callback(&mut out, &dbg);
+            opts.prefix_module(&mut out, 0, &dbg, &name);
+            ret
+        });
+
+        if let Some(progress) = &opts.progress {
+            progress.inc(1);
+        }
+        (idx, name, out, errors, ret)
+    }).collect::<Vec<_>>();
+
+    // Emit the output in the order of the files in the archive
+    results.sort_by_key(|x| x.0);
+    for (_, name, member_out, member_errors, ret) in results {
+        out.extend_from_slice(&member_out);
+        errors.extend(member_errors);
+        match ret {
+            Ok(()) => {}
+
+            // Archives often carry other files along with the debug files
+            Err(Error::NotDebugInfo) => {
+                if !opts.quiet {
+                    eprintln!("{}: {}: warning: skipped, not a DI file",
+                        file, name);
+                }
+            }
+
+            Err(err) => {
+                errors.push(format!("{}: {}: error: {}", file, name, err));
+            }
+        }
     }
 }
 
-/// `IMAGE_SECTION_HEADER`
-#[derive(Debug)]
-#[repr(C)]
-struct SectionHeader {
-    /// An 8-byte, null-padded UTF-8 string. There is no
-    /// terminating null character if the string is exactly eight
-    /// characters long. For longer names, this member contains a
-    /// forward slash (/) followed by an ASCII representation of a
-    /// decimal number that is an offset into the string table.
-    /// Executable images do not use a string table and do not
-    /// support section names longer than eight characters.
-    name: [u8; 8],
-
-    /// The total size of the section when loaded into memory, in
-    /// bytes. If this value is greater than the SizeOfRawData
-    /// member, the section is filled with zeroes. This field is
-    /// valid only for executable images and should be set to 0 for
-    /// object files.
-    vsize: u32,
+/// Scan `file` for embedded debug files and invoke `callback` on each, with
+/// output going to `out` after a `C` record giving its offset in `file`
+///
+/// Hits which turn out not to parse are false positives or damaged, so are
+/// only warned about
+fn for_each_carved(file: &str, opts: &Options, out: &mut Vec<u8>,
+        callback: &(impl Fn(&mut Vec<u8>, &DbgFile) -> Result<()> + Sync))
+        -> Result<()> {
+    let data = std::fs::read(file)
+        .map_err(|x| Error::Open(PathBuf::from(file), x))?;
+    let hits = dbgparse::carve(&data);
+
+    let results = hits.par_iter().map(|range| {
+        let name = format!("{}+{:#x}", file, range.start);
+        let mut out = Vec::new();
+        let ret = load_dbg(Cursor::new(&data[range.clone()]), &name, opts)
+            .and_then(|dbg| {
+                if !opts.in_period(&dbg) {
+                    return Ok(());
+                }
+                opts.print_carved(&mut out, range.start);
+                let ret = callback(&mut out, &dbg);
+                opts.prefix_module(&mut out, 0, &dbg, &name);
+                ret
+            });
+        (name, out, ret)
+    }).collect::<Vec<_>>();
+
+    for (name, member_out, ret) in results {
+        out.extend_from_slice(&member_out);
+        if let Err(err) = ret {
+            if !opts.quiet {
+                eprintln!("{}: warning: skipped: {}", name, err);
+            }
+        }
+    }
 
-    /// The address of the first byte of the section when loaded
-    /// into memory, relative to the image base. For object files,
-    /// this is the address of the first byte before relocation is
-    /// applied.
-    vaddr: u32,
+    Ok(())
+}
 
-    /// The size of the initialized data on disk, in bytes. This
-    /// value must be a multiple of the `FileAlignment` member of
-    /// the `IMAGE_OPTIONAL_HEADER` structure. If this value is
-    /// less than the VirtualSize member, the remainder of the
-    /// section is filled with zeroes. If the section contains only
-    /// uninitialized data, the member is zero.
-    raw_data_sz: u32,
+/// Check if `data` starts with the signature of a ZIP archive
+fn is_zip(data: &[u8]) -> bool {
+    data.starts_with(b"PK\x03\x04") || data.starts_with(b"PK\x05\x06")
+}
 
-    /// A file pointer to the first page within the COFF file. This
-    /// value must be a multiple of the `FileAlignment` member of
-    /// the `IMAGE_OPTIONAL_HEADER` structure. If a section
-    /// contains only uninitialized data, set this member is zero.
-    ptr_raw_data: u32,
+/// Check if `path` looks like a debug file or an archive of them by its
+/// extension
+fn is_dbg_file(path: &Path) -> bool {
+    path.extension().and_then(|x| x.to_str()).is_some_and(|x| {
+        ["dbg", "db_", "zip"].iter()
+            .any(|ext| x.eq_ignore_ascii_case(ext)) ||
+            (cfg!(feature = "cab") && x.eq_ignore_ascii_case("cab")) ||
+            (cfg!(feature = "iso") && x.eq_ignore_ascii_case("iso"))
+    })
+}
 
-    /// A file pointer to the beginning of the relocation entries
-    /// for the section. If there are no relocations, this value is
-    /// zero.
-    ptr_relocation: u32,
+/// Check if `path` in a CD image is a debug file under a `DEBUG` or `SYMBOLS`
+/// directory, where NT CDs keep them (eg. `SUPPORT/DEBUG/I386/SYMBOLS.CAB`)
+#[cfg(feature = "iso")]
+fn is_symbol_path(path: &str) -> bool {
+    let mut dirs = path.split('/').rev().skip(1);
+    is_dbg_file(Path::new(path)) && dirs.any(|x| {
+        x.eq_ignore_ascii_case("debug") || x.eq_ignore_ascii_case("symbols")
+    })
+}
 
-    /// A file pointer to the beginning of the line-number entries
-    /// for the section. If there are no COFF line numbers, this
-    /// value is zero.
-    ptr_line_num: u32,
+/// Recursively collect all debug files in `dir` into `files`, in sorted order
+fn collect_dir(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let open_err = |x| Error::Open(dir.to_path_buf(), x);
+    let mut entries = std::fs::read_dir(dir).map_err(open_err)?
+        .map(|x| x.map(|x| x.path()))
+        .collect::<std::io::Result<Vec<_>>>().map_err(open_err)?;
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            collect_dir(&path, files)?;
+        } else if is_dbg_file(&path) {
+            files.push(path);
+        }
+    }
 
-    /// The number of relocation entries for the section. This
-    /// value is zero for executable images.
-    num_relocs: u16,
+    Ok(())
+}
 
-    /// The number of line-number entries for the section.
-    num_line_num: u16,
+/// Collect all debug files matching the glob `pattern` into `files`, in
+/// sorted order. Matching directories are searched recursively.
+fn collect_glob(pattern: &str, files: &mut Vec<PathBuf>) -> Result<()> {
+    let open_err = |x| Error::Open(PathBuf::from(pattern), x);
+
+    // Symbol CDs are from case insensitive file systems
+    let options = glob::MatchOptions {
+        case_sensitive: false,
+        ..Default::default()
+    };
+    let matches = glob::glob_with(pattern, options).map_err(|x| {
+        open_err(io::Error::new(io::ErrorKind::InvalidInput, x.msg))
+    })?;
+
+    let mut found = false;
+    for path in matches {
+        let path = path.map_err(|x| {
+            Error::Open(x.path().to_path_buf(), x.into())
+        })?;
+        if path.is_dir() {
+            collect_dir(&path, files)?;
+        } else if is_dbg_file(&path) {
+            files.push(path);
+        }
+        found = true;
+    }
 
-    /// The characteristics of the image. The following values are
-    /// defined.
-    characteristics: u32,
+    if !found {
+        return Err(open_err(io::Error::new(io::ErrorKind::NotFound,
+            "no files match the pattern")));
+    }
+    Ok(())
 }
 
-/// `IMAGE_DEBUG_DIRECTORY`
-#[derive(Debug)]
-#[repr(C)]
-struct DebugDirectory {
-    /// Reserved
-    characteristics: u32,
+/// Invoke `callback` on every debug file in `files`, parsing files in
+/// parallel
+///
+/// Directories in `files` are searched recursively, and glob patterns such
+/// as `SYMBOLS/**/*.DB_` are expanded for shells which don't. Each file's
+/// output is buffered and printed to `output` in the order the files were
+/// given, so the output is the same as a serial run. A file or CAB member
+/// which fails is reported on `stderr` and doesn't stop the others. Returns
+/// the number of failures.
+fn run(files: &[String], opts: &Options, output: &mut dyn io::Write,
+        callback: impl Fn(&mut Vec<u8>, &DbgFile) -> Result<()> + Sync)
+        -> io::Result<usize> {
+    let mut failures = 0;
+
+    // Expand directories and glob patterns
+    let mut paths = Vec::new();
+    for file in files {
+        let path = Path::new(file);
+        let ret = if path.is_dir() {
+            collect_dir(path, &mut paths)
+        } else if !path.exists() && file.contains(['*', '?', '[']) {
+            collect_glob(file, &mut paths)
+        } else {
+            paths.push(path.to_path_buf());
+            Ok(())
+        };
+
+        if let Err(err) = ret {
+            eprintln!("{}: error: {}", file, err);
+            failures += 1;
+        }
+    }
 
-    /// The time and date the debugging information was created.
-    timedatestamp: u32,
+    if let Some(progress) = &opts.progress {
+        progress.set_length(paths.len() as u64);
+    }
 
-    /// The major version number of the debugging information format.
-    major_version: u16,
+    std::thread::scope(|scope| -> io::Result<()> {
+        let (tx, rx) = mpsc::channel();
+
+        // Parse the files on the worker threads
+        let paths = &paths;
+        let callback = &callback;
+        scope.spawn(move || {
+            // Sending only fails once writing the output has failed, which
+            // stops the remaining files being parsed for nothing
+            let _ = paths.par_iter().enumerate()
+                    .try_for_each_with(tx, |tx, (idx, path)| {
+                #[cfg(feature = "tracing")]
+                let _span = tracing::debug_span!("file", path = %path.display())
+                    .entered();
+
+                let file = path.to_string_lossy();
+                let mut out = Vec::new();
+                let mut errors = Vec::new();
+                let ret = if opts.carve {
+                    for_each_carved(&file, opts, &mut out, callback)
+                } else {
+                    for_each_dbg(&file, opts, &mut out, &mut errors, callback)
+                };
+                if let Err(err) = ret {
+                    errors.push(format!("{}: error: {}", file, err));
+                }
 
-    /// The minor version number of the debugging information format.
-    minor_version: u16,
+                tx.send((idx, out, errors))
+            });
+        });
+
+        // Print the results in order as they come in
+        let mut pending = BTreeMap::new();
+        let mut next = 0;
+        for (idx, out, errors) in rx {
+            pending.insert(idx, (out, errors));
+            while let Some((out, errors)) = pending.remove(&next) {
+                let report = |output: &mut dyn io::Write| -> io::Result<()> {
+                    output.write_all(&out)?;
+                    for error in &errors {
+                        eprintln!("{}", error);
+                    }
+                    Ok(())
+                };
+                match &opts.progress {
+                    Some(progress) => {
+                        // Flush so the output isn't drawn over by the bar
+                        progress.suspend(|| {
+                            report(output)?;
+                            output.flush()
+                        })?;
+                        progress.inc(1);
+                        progress.set_message(paths[next].display()
+                            .to_string());
+                    }
+                    None => report(output)?,
+                }
+                failures += errors.len();
+                next += 1;
+            }
+        }
 
-    /// The format of the debugging information.
-    typ: DebugType,
+        Ok(())
+    })?;
 
-    /// The size of the debugging information, in bytes. This value
-    /// does not include the debug directory itself.
-    size_of_data: u32,
+    if let Some(progress) = &opts.progress {
+        progress.finish_and_clear();
+    }
+    Ok(failures)
+}
 
-    /// The address of the debugging information when the image is
-    /// loaded, relative to the image base.
-    addr_raw_data: u32,
+/// Print the records which differ between the files `old` and `new`,
+/// returning the number of failures
+fn diff(old: &str, new: &str, opts: &Options, output: &mut dyn io::Write)
+        -> io::Result<usize> {
+    let mut failures = 0;
+    let mut dump = |file: &str| {
+        let mut out = Vec::new();
+        let mut errors = Vec::new();
+        if let Err(err) = for_each_dbg(file, opts, &mut out, &mut errors,
+                &|out, dbg| dump_info(out, dbg, opts)) {
+            errors.push(format!("{}: error: {}", file, err));
+        }
+        for error in &errors {
+            eprintln!("{}", error);
+        }
+        failures += errors.len();
+        String::from_utf8_lossy(&out).into_owned()
+    };
+    let old = dump(old);
+    let new = dump(new);
+
+    let old_records: BTreeSet<&str> = old.lines().collect();
+    let new_records: BTreeSet<&str> = new.lines().collect();
+    for record in old.lines().filter(|x| !new_records.contains(x)) {
+        writeln!(output, "- {}", record)?;
+    }
+    for record in new.lines().filter(|x| !old_records.contains(x)) {
+        writeln!(output, "+ {}", record)?;
+    }
 
-    /// A file pointer to the debugging information.
-    ptr_raw_data: u32,
+    if let Some(progress) = &opts.progress {
+        progress.finish_and_clear();
+    }
+    Ok(failures)
 }
 
-/// `IMAGE_COFF_SYMBOLS_HEADER`
-#[derive(Debug)]
-#[repr(C)]
-struct CoffSymbolsHeader {
-    /// The number of symbols.
-    num_symbols: u32,
-
-    /// The virtual address of the first symbol.
-    lva_first_symbol: u32,
-
-    /// The number of line-number entries.
-    num_line_nums: u32,
+/// Print the folders in each CAB in `files`, with the size, modification
+/// time, and name of the files in them, returning the number of failures
+#[cfg(feature = "cab")]
+fn list(files: &[String], output: &mut dyn io::Write) -> io::Result<usize> {
+    let mut failures = 0;
+    for file in files {
+        let cabinet = File::open(file)
+            .map_err(|x| Error::Open(PathBuf::from(file), x))
+            .and_then(|fd| cab::Cabinet::new(fd).map_err(Error::ExtractCab));
+        let cabinet = match cabinet {
+            Ok(cabinet) => cabinet,
+            Err(err) => {
+                eprintln!("{}: error: {}", file, err);
+                failures += 1;
+                continue;
+            }
+        };
 
-    /// The virtual address of the first line-number entry.
-    lva_first_line: u32,
+        if files.len() > 1 {
+            writeln!(output, "{}:", file)?;
+        }
+        for (idx, folder) in cabinet.folder_entries().enumerate() {
+            writeln!(output, "Folder {} ({:?}, {} blocks)", idx,
+                folder.compression_type(), folder.num_data_blocks())?;
+            writeln!(output, "    {:<8} {:<19} Name", "Size", "Modified")?;
+            for entry in folder.file_entries() {
+                let modified = entry.datetime()
+                    .map(|x| x.to_string()).unwrap_or_else(|| "-".into());
+                writeln!(output, "    {:08x} {:<19} {}",
+                    entry.uncompressed_size(), modified, entry.name())?;
+            }
+        }
+    }
 
-    /// The relative virtual address of the first byte of code.
-    rva_first_code: u32,
+    Ok(failures)
+}
 
-    /// The relative virtual address of the last byte of code.
-    rva_last_code: u32,
+/// Extract every file in the CAB `file` into the directory `dir`, printing
+/// the path of each and returning the number of failures
+#[cfg(feature = "cab")]
+fn extract(file: &str, dir: &Path, opts: &Options,
+        output: &mut dyn io::Write) -> io::Result<usize> {
+    // Open the cabinet and create the directory to extract to
+    let opened = (|| -> Result<_> {
+        let open_err = |x| Error::Open(Path::new(file).to_path_buf(), x);
+        let cabinet = cab::Cabinet::new(File::open(file).map_err(open_err)?)
+            .map_err(Error::ExtractCab)?;
+        std::fs::create_dir_all(dir)
+            .map_err(|x| Error::Open(dir.to_path_buf(), x))?;
+        Ok(cabinet)
+    })();
+    let mut cabinet = match opened {
+        Ok(cabinet) => cabinet,
+        Err(err) => {
+            eprintln!("{}: error: {}", file, err);
+            return Ok(1);
+        }
+    };
+
+    let names = cabinet.folder_entries()
+        .flat_map(|folder| folder.file_entries())
+        .map(|file| file.name().to_string())
+        .filter(|name| opts.wants_member(name))
+        .collect::<Vec<_>>();
+
+    let mut failures = 0;
+    for name in names {
+        // Names may have DOS style directories, only keep the file name
+        let path = dir.join(name.rsplit(['\\', '/']).next().unwrap_or(&name));
+        let ret = cabinet.read_file(&name).and_then(|mut reader| {
+            std::io::copy(&mut reader, &mut File::create(&path)?)
+        });
+
+        match ret {
+            Ok(_) => writeln!(output, "{}", path.display())?,
+            Err(err) => {
+                eprintln!("{}: {}: error: {}", file, name,
+                    Error::ExtractCab(err));
+                failures += 1;
+            }
+        }
+    }
 
-    /// The relative virtual address of the first byte of data.
-    rva_first_data: u32,
+    Ok(failures)
+}
 
-    /// The relative virtual address of the last byte of data.
-    rva_last_data: u32,
+/// Write the `.dbg` file `dbg` for the linker map `map` of an image for
+/// `machine`, printing its path and returning the number of failures
+fn from_map(map: &Path, dbg: &Path, machine: Machine,
+        output: &mut dyn io::Write) -> io::Result<usize> {
+    let ret = std::fs::read_to_string(map)
+        .map_err(|x| Error::Open(map.to_path_buf(), x))
+        .and_then(|text| DbgWriter::from_map(&text))
+        .and_then(|mut writer| {
+            writer.header.machine = machine;
+            File::create(dbg).and_then(|fd| writer.write(fd))
+                .map_err(|x| Error::Open(dbg.to_path_buf(), x))
+        });
+
+    match ret {
+        Ok(()) => {
+            writeln!(output, "{}", dbg.display())?;
+            Ok(0)
+        }
+        Err(err) => {
+            eprintln!("{}: error: {}", map.display(), err);
+            Ok(1)
+        }
+    }
 }
 
-/// Windows NT `.dbg` file parser
-#[derive(Default)]
-pub struct DbgFile {
-    /// Mapping from RVA to (filename, line number)
-    addr_to_line: BTreeMap<u32, (String, u32)>,
-
-    /// Mapping from RVA to function name
-    functions: BTreeMap<u32, String>,
-
-    /// Mapping from RVA to global name
-    globals: BTreeMap<u32, String>,
-}
-
-impl DbgFile {
-    /// Parse a debug file at `path`
-    pub fn load(mut reader: impl Read + Seek) -> Result<Self> {
-        // Make sure it's a debug info file
-        if &consume!(reader, 2, "header")? != b"DI" {
-            return Err(Error::NotDebugInfo);
-        }
-
-        // `IMAGE_SEPARATE_DEBUG_HEADER`
-        let _flags           = consume!(reader, u16, "flags")?;
-        let _machine         = consume!(reader, u16, "machine")?;
-        let _characteristics = consume!(reader, u16, "characteristics")?;
-        let _timedatestamp   = consume!(reader, u32, "timedatestamp")?;
-        let _checksum        = consume!(reader, u32, "checksum")?;
-        let _image_base      = consume!(reader, u32, "image base")?;
-        let _size_of_image   = consume!(reader, u32, "size of image")?;
-        let num_sections     = consume!(reader, u32, "number of sections")?;
-        let exported_namesz  = consume!(reader, u32, "exported names size")?;
-        let debug_dirsz      = consume!(reader, u32, "debug directory size")?;
-        let _section_align   = consume!(reader, u32, "section alignment")?;
-        let _reserved        = consume!(reader, 8,   "reserved")?;
-
-        // Read each `IMAGE_SECTION_HEADER`
-        for _ in 0..num_sections {
-            // Read the section header
-            let _sh = SectionHeader {
-                name:            consume!(reader, 8,   "name")?,
-                vsize:           consume!(reader, u32, "vsize")?,
-                vaddr:           consume!(reader, u32, "vaddr")?,
-                raw_data_sz:     consume!(reader, u32, "raw_data_sz")?,
-                ptr_raw_data:    consume!(reader, u32, "ptr_raw_data")?,
-                ptr_relocation:  consume!(reader, u32, "ptr_relocation")?,
-                ptr_line_num:    consume!(reader, u32, "ptr_line_num")?,
-                num_relocs:      consume!(reader, u16, "num_relocs")?,
-                num_line_num:    consume!(reader, u16, "num_line_num")?,
-                characteristics: consume!(reader, u32, "characteristics")?,
-            };
+/// Write the `.dbg` file `dbg` for the debug information in the PE `image`,
+/// printing its path and returning the number of failures
+fn split(image: &Path, dbg: Option<&Path>, output: &mut dyn io::Write)
+        -> io::Result<usize> {
+    let dbg = dbg.map(Path::to_path_buf)
+        .unwrap_or_else(|| image.with_extension("dbg"));
+    let ret = std::fs::read(image)
+        .map_err(|x| Error::Open(image.to_path_buf(), x))
+        .and_then(|data| split::split(&data))
+        .and_then(|contents| std::fs::write(&dbg, contents)
+            .map_err(|x| Error::Open(dbg.clone(), x)));
+
+    match ret {
+        Ok(()) => {
+            writeln!(output, "{}", dbg.display())?;
+            Ok(0)
+        }
+        Err(err) => {
+            eprintln!("{}: error: {}", image.display(), err);
+            Ok(1)
+        }
+    }
+}
+
+/// Hexdump the data of each debug directory of type `typ` in `files`,
+/// returning the number of failures
+///
+/// Each directory gets a heading, then 16 bytes per line as offsets into the
+/// data, hex, and ASCII. Files without a directory of the type fail.
+fn raw(files: &[String], typ: DebugType, opts: &Options,
+        output: &mut dyn io::Write) -> io::Result<usize> {
+    let mut failures = 0;
+    for file in files {
+        let ret = std::fs::read(file)
+            .map_err(|x| Error::Open(PathBuf::from(file), x))
+            .and_then(|data| {
+                let dbg = load_dbg(Cursor::new(&data[..]), file, opts)?;
+                let mut out = Vec::new();
+                let mut found = false;
+                for (idx, dd) in dbg.debug_directories().iter().enumerate()
+                        .filter(|(_, x)| x.typ == typ) {
+                    found = true;
+                    outln!(out, "{}: {:?} directory {}, {:#x} bytes at offset \
+                        {:#x}", file, dd.typ, idx, dd.size_of_data,
+                        dd.ptr_raw_data);
+                    let Some(payload) = dd.data(&data) else {
+                        return Err(Error::Truncated("debug directory data",
+                            dd.size_of_data as u64));
+                    };
+                    hexdump(&mut out, payload);
+                }
+                Ok(found.then_some(out))
+            });
+
+        match ret {
+            Ok(Some(out)) => output.write_all(&out)?,
+            Ok(None) => {
+                eprintln!("{}: error: no {:?} debug directory", file, typ);
+                failures += 1;
+            }
+            Err(err) => {
+                eprintln!("{}: error: {}", file, err);
+                failures += 1;
+            }
+        }
+    }
+    Ok(failures)
+}
+
+/// Print `data` as 16 bytes per line of offset, hex, and ASCII, with
+/// unprintable bytes as `.`
+fn hexdump(out: &mut Vec<u8>, data: &[u8]) {
+    for (idx, chunk) in data.chunks(16).enumerate() {
+        let mut hex = String::new();
+        for (col, byte) in chunk.iter().enumerate() {
+            if col == 8 {
+                hex.push(' ');
+            }
+            write!(hex, " {:02x}", byte).unwrap();
         }
+        let ascii = chunk.iter().map(|&x| {
+            if x.is_ascii_graphic() || x == b' ' { x as char } else { '.' }
+        }).collect::<String>();
+        outln!(out, "{:08x} {:<49}  |{}|", idx * 16, hex, ascii);
+    }
+}
 
-        // Skip over the exported names
-        reader.seek(SeekFrom::Current(exported_namesz as i64))
-            .map_err(Error::SkipExportedNames)?;
+/// Parse a `--type` of debug directory, the name of a [`DebugType`] ignoring
+/// case (`fpo` for frame pointer omission), or the type number
+fn parse_debug_type(text: &str) -> std::result::Result<DebugType, String> {
+    if let Ok(typ) = text.parse::<u32>() {
+        return Ok(DebugType::from(typ));
+    }
+    if text.eq_ignore_ascii_case("fpo") {
+        return Ok(DebugType::FramePointerOmission);
+    }
 
-        // Create return `Self`
-        let mut ret = Self::default();
+    // Every named type has a number below this
+    (0..=20).map(DebugType::from)
+        .find(|x| format!("{:?}", x).eq_ignore_ascii_case(text))
+        .ok_or_else(|| format!("unknown debug directory type `{}`", text))
+}
+
+/// What to strip from a `.dbg`, from the flags of the `strip` command
+struct StripOptions<'a> {
+    /// Only keep symbols with names matching one of these, if any
+    keep: &'a [glob::Pattern],
+
+    /// Drop symbols with names matching any of these
+    drop: &'a [glob::Pattern],
+
+    /// Drop all functions
+    no_functions: bool,
+
+    /// Drop all globals
+    no_globals: bool,
+
+    /// Drop the source lines
+    no_lines: bool,
+
+    /// Rename the symbols after their address
+    obfuscate: bool,
+}
 
-        // Read each `IMAGE_DEBUG_DIRECTORY`
-        for _ in 0..debug_dirsz as usize / size_of::<DebugDirectory>() {
-            // Read the section header
-            let dd = DebugDirectory {
-                characteristics: consume!(reader, u32, "characteristics")?,
-                timedatestamp:   consume!(reader, u32, "timedatestamp")?,
-                major_version:   consume!(reader, u16, "major_version")?,
-                minor_version:   consume!(reader, u16, "minor_version")?,
-                typ:             consume!(reader, u32, "typ")?.try_into()?,
-                size_of_data:    consume!(reader, u32, "size_of_data")?,
-                addr_raw_data:   consume!(reader, u32, "addr_raw_data")?,
-                ptr_raw_data:    consume!(reader, u32, "ptr_raw_data")?,
+/// Write the `.dbg` file `dbg` with the symbols of `input` stripped as
+/// described by `strip`, printing its path and returning the number of
+/// failures
+fn strip(input: &str, dbg: &Path, strip: &StripOptions, opts: &Options,
+        output: &mut dyn io::Write) -> io::Result<usize> {
+    let ret = load_file(input, opts)
+        .and_then(|parsed| {
+            let wanted = |name: &str| {
+                !strip.drop.iter().any(|x| x.matches(name)) &&
+                    (strip.keep.is_empty() ||
+                        strip.keep.iter().any(|x| x.matches(name)))
             };
 
-            // Currently we only handle COFF
-            if matches!(dd.typ, DebugType::Coff) {
-                // Parse COFF debug information
-                ret.parse_coff(&mut reader, dd.ptr_raw_data as u64)?;
+            // Exported names are public anyway, but are still filtered so
+            // the patterns do what they say
+            let mut writer = DbgWriter::from_dbg(&parsed);
+            writer.exported_names.retain(|name| wanted(name));
+            writer.retain_symbols(|kind, _, name| {
+                let dropped = match kind {
+                    SymbolKind::Function => strip.no_functions,
+                    SymbolKind::Global   => strip.no_globals,
+                };
+                !dropped && wanted(name)
+            });
+
+            if strip.no_lines {
+                writer.lines.clear();
             }
+            if strip.obfuscate {
+                writer.aliases.clear();
+                writer.rename_symbols(|kind, rva, _| match kind {
+                    SymbolKind::Function => format!("sub_{:08x}", rva),
+                    SymbolKind::Global   => format!("data_{:08x}", rva),
+                });
+            }
+
+            File::create(dbg).and_then(|fd| writer.write(fd))
+                .map_err(|x| Error::Open(dbg.to_path_buf(), x))
+        });
+
+    match ret {
+        Ok(()) => {
+            writeln!(output, "{}", dbg.display())?;
+            Ok(0)
+        }
+        Err(err) => {
+            eprintln!("{}: error: {}", input, err);
+            Ok(1)
         }
+    }
+}
 
-        Ok(ret)
+/// Write the `.meso` file `meso` for the functions in `input`, printing its
+/// path and returning the number of failures
+fn meso(input: &str, meso: Option<&Path>, module: Option<&str>, lines: bool,
+        opts: &Options, output: &mut dyn io::Write) -> io::Result<usize> {
+    let input_path = Path::new(input);
+    let meso = meso.map(Path::to_path_buf)
+        .unwrap_or_else(|| input_path.with_extension("meso"));
+    let ret = load_file(input, opts)
+        .and_then(|parsed| {
+            let module = module.map(str::to_string).unwrap_or_else(|| {
+                let stem = input_path.file_stem().unwrap_or_default()
+                    .to_string_lossy().to_ascii_lowercase();
+                let is_dll = parsed.header().characteristics & 0x2000 != 0;
+                format!("{}.{}", stem, if is_dll { "dll" } else { "exe" })
+            });
+
+            std::fs::write(&meso, meso::to_meso(&parsed, &module, lines))
+                .map_err(|x| Error::Open(meso.clone(), x))
+        });
+
+    match ret {
+        Ok(()) => {
+            writeln!(output, "{}", meso.display())?;
+            Ok(0)
+        }
+        Err(err) => {
+            eprintln!("{}: error: {}", input, err);
+            Ok(1)
+        }
     }
+}
 
-    /// Parse COFF information, used internally
-    ///
-    /// Updates the `self` in-place with the newly parsed information
-    fn parse_coff(&mut self, reader: &mut (impl Read + Seek), coff_offset: u64)
-            -> Result<()> {
-        // Save current file location
-        let start = reader.stream_position().map_err(Error::SeekCoff)?;
-
-        // Seek to the COFF data header
-        reader.seek(SeekFrom::Start(coff_offset)).map_err(Error::SeekCoff)?;
-
-        // Parse COFF symbol header
-        let ch = CoffSymbolsHeader {
-            num_symbols:      consume!(reader, u32, "num_symbols")?,
-            lva_first_symbol:
-                consume!(reader, u32, "lva_first_symbol")?,
-            num_line_nums:    consume!(reader, u32, "num_line_nums")?,
-            lva_first_line:   consume!(reader, u32, "lva_first_line")?,
-            rva_first_code:   consume!(reader, u32, "rva_first_code")?,
-            rva_last_code:    consume!(reader, u32, "rva_last_code")?,
-            rva_first_data:   consume!(reader, u32, "rva_first_data")?,
-            rva_last_data:    consume!(reader, u32, "rva_last_data")?,
-        };
+/// Print the coverage of each debug file in `file` for the trace `trace`,
+/// returning the number of failures
+fn coverage(file: &str, trace: &Path, module: Option<&str>, opts: &Options,
+        output: &mut dyn io::Write) -> io::Result<usize> {
+    let module = module.map(str::to_string).unwrap_or_else(|| {
+        Path::new(file).file_stem().unwrap_or_default().to_string_lossy()
+            .into_owned()
+    });
+    let hits = match std::fs::read(trace)
+            .map_err(|x| Error::Open(trace.to_path_buf(), x))
+            .and_then(|data| coverage::parse_trace(&data, &module)) {
+        Ok(hits) => hits,
+        Err(err) => {
+            eprintln!("{}: error: {}", trace.display(), err);
+            return Ok(1);
+        }
+    };
+
+    let mut out = Vec::new();
+    let mut errors = Vec::new();
+    if let Err(err) = for_each_dbg(file, opts, &mut out, &mut errors,
+            &|out, dbg| dump_coverage(out, dbg, &hits, opts)) {
+        errors.push(format!("{}: error: {}", file, err));
+    }
+    for error in &errors {
+        eprintln!("{}", error);
+    }
 
-        // Parse line number table
-        let mut line_addrs = Vec::new();
-        for _ in 0..ch.num_line_nums {
-            #[derive(Debug)]
-            struct Line {
-                addr: u32,
-                line: u16,
-            }
-            
-            // Parse line information
-            let line = Line {
-                addr: consume!(reader, u32, "addr")?,
-                line: consume!(reader, u16, "line")?,
+    output.write_all(&out)?;
+    Ok(errors.len())
+}
+
+/// Get the `kallsyms` type of a global in a section of `category`, `D` for
+/// data unless it's known to be code, read-only data, or uninitialized data
+fn kallsyms_type(category: Option<&str>) -> char {
+    match category {
+        Some("code")  => 'T',
+        Some("rdata") => 'R',
+        Some("bss")   => 'B',
+        _             => 'D',
+    }
+}
+
+/// Print the functions, globals, and aliases of every module in `session` in
+/// the `kallsyms` layout, in order of their virtual addresses
+fn session_kallsyms(session: &Session, opts: &Options) -> Vec<u8> {
+    let mut symbols = Vec::new();
+    for module in session.modules() {
+        let dbg = module.dbg();
+        let va = |rva: u32| module.base() + rva as u64;
+        let name = dbg.module_name().unwrap_or(module.name());
+        for (rva, kind, symbol) in dbg.symbols_in_range(..) {
+            if opts.hides(symbol) || !opts.wanted(dbg, rva, symbol) {
+                continue;
+            }
+            let (typ, wanted) = match kind {
+                SymbolKind::Function =>
+                    ('T', opts.kinds.is_none_or(|x| x.functions)),
+                SymbolKind::Global => (kallsyms_type(
+                    dbg.category_for_rva(rva).map(|x| x.as_str())),
+                    opts.kinds.is_none_or(|x| x.globals)),
             };
+            if !wanted {
+                continue;
+            }
+            symbols.push((va(rva), typ, opts.name(symbol), name));
+        }
+        // Aliases aren't one of the kinds of `--kind`
+        for (alias, (_, rva)) in dbg.aliases() {
+            if opts.kinds.is_none() && !opts.hides(alias) &&
+                    opts.wanted(dbg, *rva, alias) {
+                symbols.push((va(*rva), 'W', opts.name(alias), name));
+            }
+        }
+    }
+
+    // The sort is stable, so symbols at the same address keep their order
+    symbols.sort_by_key(|x| x.0);
+    let mut out = Vec::new();
+    for (va, typ, symbol, module) in symbols {
+        outln!(out, "{:08x} {} {}\t[{}]", va, typ, symbol, module);
+    }
+    out
+}
 
-            line_addrs.push(line);
+/// Names the kernel can have in a module list, which WinDbg calls `nt`,
+/// and the names of its image for each processor and memory configuration
+const KERNEL_NAMES: &[&str] = &["ntoskrnl", "ntkrnlmp", "ntkrnlpa",
+    "ntkrpamp"];
+
+/// Parse the module list `text` into (base, name) pairs
+///
+/// Each line is a hex base address and a module name. The output of WinDbg's
+/// `lm` also works, where the end address after the base is skipped, as are
+/// the header and the unloaded modules. Lines which don't start with an
+/// address, and `#` comments, are skipped.
+fn parse_module_list(text: &str) -> Vec<(u64, String)> {
+    let hex = |x: &str| {
+        let x = x.strip_prefix("0x").unwrap_or(x).replace('`', "");
+        u64::from_str_radix(&x, 16).ok()
+    };
+
+    let mut ret = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if line.starts_with("Unloaded modules") {
+            break;
         }
 
-        // Sort by address
-        line_addrs.sort_by_key(|x| x.addr);
+        let mut fields = line.split_whitespace().peekable();
+        let Some(base) = fields.next().and_then(hex) else { continue; };
+        let mut name = fields.next();
+        if name.and_then(hex).is_some() && fields.peek().is_some() {
+            name = fields.next();
+        }
+        if let Some(name) = name {
+            ret.push((base, name.to_string()));
+        }
+    }
+    ret
+}
 
-        // Storage for symbols
-        let mut symbols = Vec::new();
+/// Find the debug file of the module `name` in `files`, by the names of the
+/// files without their extensions, ignoring case
+fn find_module_dbg<'a>(files: &'a [PathBuf], name: &str) -> Option<&'a Path> {
+    let stem = dbgparse::module_stem(name);
+    let names = if stem.eq_ignore_ascii_case("nt") { KERNEL_NAMES }
+        else { &[stem][..] };
+    names.iter().find_map(|name| {
+        files.iter().find(|file| {
+            file.file_stem().is_some_and(|x| {
+                x.to_string_lossy().eq_ignore_ascii_case(name)
+            })
+        })
+    }).map(PathBuf::as_path)
+}
 
-        // Parse all symbol entries
-        let mut ii = 0;
-        while ii < ch.num_symbols as usize {
-            /// A COFF symbol table entry
-            #[derive(Debug)]
-            struct Symbol {
-                /// Name of the symbol, represented by union of three
-                /// structures. An array of eight bytes is used if the name is
-                /// not more than eight bytes long
-                name:  [u8; 8],
+/// Load the `.dbg` in `symbols` of each module in the list at `modules` into
+/// a session, and print the module, symbol, and source line of `addrs`, or
+/// of each address read from `stdin` if there are none
+fn symbolize(modules: &Path, symbols: &Path, addrs: &[String],
+        opts: &Options, output: &mut dyn io::Write) -> io::Result<usize> {
+    let list = match std::fs::read(modules) {
+        Ok(data) => parse_module_list(&String::from_utf8_lossy(&data)),
+        Err(err) => {
+            eprintln!("{}: error: {}", modules.display(), err);
+            return Ok(1);
+        }
+    };
 
-                /// Value associated with the symbol. The interpretation of
-                /// this field depends on Section Number and Storage Class. A
-                /// typical meaning is the relocatable address.
-                value: u32,
+    // Archives can't be loaded as a single module, so only take plain files
+    let mut files = Vec::new();
+    if let Err(err) = collect_dir(symbols, &mut files) {
+        eprintln!("{}: error: {}", symbols.display(), err);
+        return Ok(1);
+    }
+    files.retain(|x| {
+        x.extension().is_some_and(|x| {
+            x.eq_ignore_ascii_case("dbg") || x.eq_ignore_ascii_case("db_")
+        })
+    });
+
+    let mut failures = 0;
+    let mut session = Session::new();
+    for (base, name) in &list {
+        let Some(file) = find_module_dbg(&files, name) else {
+            if !opts.quiet {
+                eprintln!("{}: warning: no debug file in {}", name,
+                    symbols.display());
+            }
+            continue;
+        };
+        match load_file(&file.to_string_lossy(), opts) {
+            Ok(dbg) => {
+                session.add_module(name, *base, dbg);
+            }
+            Err(err) => {
+                eprintln!("{}: error: {}", file.display(), err);
+                failures += 1;
+            }
+        }
+    }
 
-                /// Signed integer identifying the section, using a one-based
-                /// index into the Section Table. 
-                _num:  i16,
+    if opts.format == Format::Kallsyms {
+        if !addrs.is_empty() {
+            eprintln!("error: addresses can't be given with --format kallsyms");
+            return Ok(failures + 1);
+        }
+        output.write_all(&session_kallsyms(&session, opts))?;
+        return Ok(failures);
+    }
 
-                /// A number representing type. Microsoft tools set this field
-                /// to 0x20 (function) or 0x0 (not a function)
-                typ:   u16,
+    let mut symbolize = |text: &str| -> io::Result<()> {
+        let text = text.trim();
+        let Ok(va) = u64::from_str_radix(
+                &text.strip_prefix("0x").unwrap_or(text).replace('`', ""),
+                16) else {
+            eprintln!("error: bad address `{}`", text);
+            failures += 1;
+            return Ok(());
+        };
 
-                /// Enumerated value representing storage class.
-                class: u8,
+        if opts.format != Format::Text {
+            let resolved = session.resolve_va(va);
+            let module = resolved.map(|x| {
+                x.module.dbg().module_name().unwrap_or(x.module.name())
+            });
+            let symbol = resolved.and_then(|x| x.symbol);
+            let name = symbol.map(|(name, _)| opts.name(name));
+            let source = resolved.and_then(|x| x.line)
+                .map(|(source, _)| opts.source(source));
+            let mut out = Vec::new();
+            opts.print_record(&mut out, &Record::Symbolized {
+                addr: va,
+                module,
+                symbol: name.as_deref(),
+                offset: symbol.map(|(_, offset)| offset)
+                    .or(resolved.map(|x| x.rva)),
+                source: source.as_deref(),
+                line: resolved.and_then(|x| x.line).map(|(_, line)| line),
+            });
+            return output.write_all(&out);
+        }
 
-                /// Number of auxiliary symbol table entries that follow this
-                /// record.
-                aux:   u8,
+        let Some(resolved) = session.resolve_va(va) else {
+            return writeln!(output, "{:08x} -", va);
+        };
+        let mut record = format!("{:08x} ", va);
+        match resolved.symbol {
+            Some((name, offset)) => {
+                let module = resolved.module;
+                let module = module.dbg().module_name()
+                    .unwrap_or(module.name());
+                write!(record, "{}!{}", module, opts.name(name)).unwrap();
+                if offset != 0 {
+                    write!(record, "+{:#x}", offset).unwrap();
+                }
             }
+            None => write!(record, "{}", resolved).unwrap(),
+        }
+        if let Some((source, line)) = resolved.line {
+            write!(record, " {}:{}", opts.source(source), line).unwrap();
+        }
+        writeln!(output, "{}", record)
+    };
 
-            // Parse the symbol
-            let symbol = Symbol {
-                name:  consume!(reader, 8,   "name")?,
-                value: consume!(reader, u32, "value")?,
-                _num:  consume!(reader, i16, "num")?,
-                typ:   consume!(reader, u16, "typ")?,
-                class: consume!(reader, u8,  "class")?,
-                aux:   consume!(reader, u8,  "aux")?,
-            };
-          
-            // Read the AUX data
-            // There are 18 bytes (one `Symbol` worth) for each `aux` specified
-            // This keeps the file always `Symbol` aligned, and actually makes
-            // parsing fairly easy
-            let mut aux = vec![0u8; symbol.aux as usize * 18];
-            reader.read_exact(&mut aux).map_err(|x| {
-                Error::Consume("symbol aux data", x)
-            })?;
-
-            // Advance to the next symbol
-            ii += 1 + symbol.aux as usize;
-
-            // Save the symbol
-            symbols.push((symbol, aux));
-        }
-
-        // Get string table size
-        let string_table_sz =
-            consume!(reader, u32, "string table size")?;
-
-        // Read the string table add 4 to leave room for the 4-byte
-        // string table size
-        let mut string_table =
-            vec![0u8; 4 + string_table_sz as usize];
-        reader.read_exact(&mut string_table[4..]).map_err(|x| {
-            Error::Consume("string table", x)
-        })?;
+    if !addrs.is_empty() {
+        for addr in addrs {
+            symbolize(addr)?;
+        }
+    } else {
+        for line in io::stdin().lines() {
+            let line = line?;
+            if !line.trim().is_empty() && !line.starts_with('#') {
+                symbolize(&line)?;
+            }
+        }
+    }
 
-        // Storage for the most recently observed FILE class
-        let mut cur_file: Option<String> = None;
-
-        // Now that we've read everything from the file, parse the structures
-        for (symbol, aux) in symbols {
-            // Check if the symbol name is a pointer
-            let name_is_ptr = &symbol.name[..4] == b"\0\0\0\0";
-            let name = if name_is_ptr {
-                // Unwrap is fine because the size is constant
-                let ptr = u32::from_le_bytes(
-                    symbol.name[4..].try_into().unwrap());
-
-                // Inside unwrap is fine, `split` always returns at least one
-                // iterated value
-                String::from_utf8_lossy(
-                    string_table.get(ptr as usize..).map(|x| {
-                        x.split(|x| *x == 0).next().unwrap()
-                    }).ok_or(Error::SymbolNameOob)?)
-            } else {
-                // Inside unwrap is fine, `split` always returns at least one
-                // iterated value
-                String::from_utf8_lossy(
-                    symbol.name.split(|x| *x == 0).next().unwrap())
-            };
+    Ok(failures)
+}
 
-            // If the class is a public symbol
-            if matches!(symbol.class, 2) {
-                if symbol.typ == 0x20 {
-                    self.functions.insert(symbol.value, name.to_string());
-                } else {
-                    self.globals.insert(symbol.value, name.to_string());
+/// Help for the commands of `repl`
+const REPL_HELP: &str = "\
+sym <addr>                 symbol and source line at an address (in hex)
+find [module!]<pattern>    functions and globals matching a glob pattern
+line <source:line[-line]>  addresses of source lines
+sections                   section table
+header                     header and debug directories
+help                       this help
+quit                       exit, as does end of input";
+
+/// Run a single `repl` command `line` on `dbg`, adding its output to `out`
+fn repl_command(out: &mut Vec<u8>, dbg: &DbgFile, line: &str, opts: &Options)
+        -> std::result::Result<(), String> {
+    let (command, arg) = line.split_once(char::is_whitespace)
+        .map(|(command, arg)| (command, arg.trim()))
+        .unwrap_or((line, ""));
+
+    match command {
+        "sym" => {
+            let addr = parse_hex(arg)
+                .map_err(|x| format!("bad address: {}", x))?;
+            let rva = (addr as u64).checked_sub(opts.addr(dbg, 0))
+                .and_then(|x| u32::try_from(x).ok())
+                .ok_or("address is below the base")?;
+
+            let (name, offset) = dbg.nearest_symbol(rva)
+                .ok_or("no symbol at or before the address")?;
+            let name = opts.qualify(dbg, &opts.name(name)).into_owned();
+            let mut record = format!("{:08x} {}", addr, name);
+            if offset != 0 {
+                record += &format!("+{:#x}", offset);
+            }
+            if let Some((_, (source, line))) =
+                    dbg.lines().range(..=rva).next_back() {
+                record += &format!(" {}:{}", source, line);
+            }
+            outln!(out, "{}", record);
+        }
+        "find" => {
+            let (module, arg) = dbgparse::split_qualified(arg);
+            if !in_module(dbg, module) {
+                return Ok(());
+            }
+            let pattern = glob::Pattern::new(arg)
+                .map_err(|x| format!("bad pattern: {}", x))?;
+            for (rva, kind, name) in dbg.symbols_in_range(..) {
+                if !pattern.matches(name) {
+                    continue;
                 }
-            } else if matches!(symbol.class, 3) {
-                // Check if it's a static class with an aux, if so, we'll look
-                // at the section boundaries and try to find matching source
-                // lines
-                if aux.len() >= 4 && cur_file.is_some() {
-                    // Get the section length, unwrap is okay due to checked
-                    // aux size.
-                    let slen = u32::from_le_bytes(
-                        aux[0..4].try_into().unwrap());
-
-                    // Get start and end RVAs for this
-                    let start = symbol.value; // inclusive
-                    let end   = start + slen; // exclusive
-
-                    // Search for `start` in `line_addrs`
-                    let idx = match 
-                        line_addrs.binary_search_by_key(&start,
-                            |line| line.addr) {
-                        Ok(idx)  => idx,
-                        Err(idx) => idx,
-                    };
-
-                    // Go through each line from `start` until we are
-                    // out of bounds of `end`
-                    if let Some(line_addrs) = line_addrs.get(idx..) {
-                        for line in line_addrs {
-                            // Break if we're past our address
-                            if line.addr >= end {
-                                break;
-                            }
-
-                            // Save the line information
-                            // Unwrap is fine since `cur_file` was checked
-                            // to be `Some`
-                            self.addr_to_line.insert(line.addr,
-                                (cur_file.as_ref().unwrap().clone(),
-                                 line.line as u32));
-                        }
+                match kind {
+                    SymbolKind::Function => {
+                        opts.print_function(out, dbg, rva, &opts.name(name));
+                    }
+                    SymbolKind::Global => {
+                        opts.print_global(out, dbg, rva, &opts.name(name));
                     }
                 }
-            } else if matches!(symbol.class, 103) {
-                // Latch the filename from AUX data, split at the null
-                // terminator.
-                // Unwrap is fine due to `next` always having at least one
-                // return on `split`
-                let filename = std::str::from_utf8(
-                    aux.split(|x| *x == 0).next().unwrap())
-                    .map_err(Error::FilenameUtf8)?;
-                cur_file = Some(filename.to_string());
-            } else if matches!(symbol.class, 105) {
-                // Ignored alias
-            } else {
-                return Err(Error::UnknownSymbolClass(symbol.class));
             }
         }
+        "line" => {
+            let spec = parse_line_spec(arg)?;
+            find_lines(out, dbg, &spec.source, spec.lines, opts)
+                .map_err(|x| x.to_string())?;
+        }
+        "sections" => dump_sections(out, dbg).map_err(|x| x.to_string())?,
+        "header" => dump_header(out, dbg).map_err(|x| x.to_string())?,
+        "help" => outln!(out, "{}", REPL_HELP),
+        _ => return Err(format!("unknown command `{}`, try `help`", command)),
+    }
 
-        // Seek back to where we were
-        reader.seek(SeekFrom::Start(start)).map_err(Error::SeekCoff)?;
+    Ok(())
+}
 
-        Ok(())
+/// Load the single debug file `file`, reading it into memory at once unless
+/// it's over `MAX_READ_WHOLE` bytes. Files read into memory are expanded
+/// first if they're compressed, eg. a `.DB_` from a symbol CD.
+fn load_file(file: &str, opts: &Options) -> Result<DbgFile> {
+    let open_err = |x| Error::Open(Path::new(file).to_path_buf(), x);
+    let fd = File::open(file).map_err(open_err)?;
+    if fd.metadata().map_err(open_err)?.len() <= MAX_READ_WHOLE {
+        let mut data = Vec::new();
+        (&fd).read_to_end(&mut data).map_err(open_err)?;
+        if expand::is_compressed(&data) {
+            data = expand::decompress(&data)?;
+        }
+        return load_dbg(Cursor::new(&data[..]), file, opts);
     }
+    load_dbg(io::BufReader::new(fd), file, opts)
 }
 
-/// Dump information about `path` to `stdout`
-fn dump_info(reader: impl Read + Seek) -> Result<()> {
-    // Parse the debug file
-    let dbg = DbgFile::load(reader)?;
+/// Load `file` and run `repl` commands read from `stdin` on it until `quit`
+/// or the end of input, returning the number of failures
+fn repl(file: &str, opts: &Options, output: &mut dyn io::Write)
+        -> io::Result<usize> {
+    let dbg = match load_file(file, opts) {
+        Ok(dbg) => dbg,
+        Err(err) => {
+            eprintln!("{}: error: {}", file, err);
+            return Ok(1);
+        }
+    };
+
+    // Only prompt when someone is typing
+    let interactive = io::stdin().is_terminal();
+    let mut failures = 0;
+    let mut line = String::new();
+    loop {
+        if interactive {
+            write!(output, "> ")?;
+            output.flush()?;
+        }
 
-    // Print functions
-    for (rva, name) in dbg.functions.iter() {
-        println!("F {:08x} {}", rva, name);
-    }
-    
-    // Print globals
-    for (rva, name) in dbg.globals.iter() {
-        println!("G {:08x} {}", rva, name);
-    }
-    
-    // Print source lines
-    for (rva, (source, line)) in dbg.addr_to_line.iter() {
-        println!("S {:08x} {}:{}", rva, source, line);
+        line.clear();
+        if io::stdin().read_line(&mut line)? == 0 {
+            break;
+        }
+        let command = line.trim();
+        if command == "quit" || command == "exit" {
+            break;
+        } else if command.is_empty() {
+            continue;
+        }
+
+        let mut out = Vec::new();
+        if let Err(err) = repl_command(&mut out, &dbg, command, opts) {
+            eprintln!("error: {}", err);
+            failures += 1;
+        }
+        output.write_all(&out)?;
+        output.flush()?;
     }
 
-    Ok(())
+    Ok(failures)
 }
 
 fn main() -> Result<()> {
-    // Get arguments
-    let args = std::env::args().collect::<Vec<_>>();
-    if args.len() < 2 {
-        println!("Usage: dbgparse <file1.dbg | file1.cab> ...");
-        return Ok(());
+    let start = Instant::now();
+
+    #[cfg(feature = "tracing")]
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_writer(std::io::stderr)
+        .init();
+
+    let cli = Cli::parse();
+    let flags = &cli.flags;
+
+    // Get the defaults for anything not given on the command line
+    let config = Config::load(flags).unwrap_or_else(|err| {
+        eprintln!("Invalid config: {}", err);
+        std::process::exit(1);
+    });
+    let config_base = config.base.as_deref().map(parse_base).transpose()
+        .unwrap_or_else(|err| {
+            eprintln!("Invalid config: base: {}", err);
+            std::process::exit(1);
+        });
+
+    let config_format = config.format.as_deref().map(Format::parse)
+        .transpose()
+        .unwrap_or_else(|err| {
+            eprintln!("Invalid config: format: {}", err);
+            std::process::exit(1);
+        });
+
+    let config_sort = config.sort.as_deref().map(SortOrder::parse)
+        .transpose()
+        .unwrap_or_else(|err| {
+            eprintln!("Invalid config: sort: {}", err);
+            std::process::exit(1);
+        });
+
+    let date = |flag: Option<u32>, config: &Option<String>, key| {
+        flag.map(Ok).or_else(|| config.as_deref().map(parse_date))
+            .transpose()
+            .unwrap_or_else(|err| {
+                eprintln!("Invalid config: {}: {}", key, err);
+                std::process::exit(1);
+            })
+    };
+    let newer_than = date(flags.newer_than, &config.newer_than, "newer_than");
+    let older_than = date(flags.older_than, &config.older_than, "older_than");
+    let period = (newer_than.is_some() || older_than.is_some()).then(|| {
+        newer_than.unwrap_or(0)..older_than.unwrap_or(u32::MAX)
+    });
+
+    let config_names = config.names.as_deref().map(parse_names).transpose()
+        .unwrap_or_else(|err| {
+            eprintln!("Invalid config: names: {}", err);
+            std::process::exit(1);
+        });
+
+    // The record type flags add to the kinds of `--kind`
+    let mut kinds = flags.kind;
+    if flags.functions || flags.globals || flags.lines {
+        let kinds = kinds.get_or_insert(Kinds::default());
+        kinds.functions |= flags.functions;
+        kinds.globals   |= flags.globals;
+        kinds.lines     |= flags.lines;
     }
 
-    for file in &args[1..] {
-        // Open the file
-        let fd = File::open(file).map_err(|x| {
-            Error::Open(Path::new(file).to_path_buf(), x)
-        })?;
+    let path_map = if flags.pathmap.is_empty() {
+        config.pathmap.iter().map(|x| PathMap::parse(x))
+            .collect::<std::result::Result<_, _>>()
+            .unwrap_or_else(|err| {
+                eprintln!("Invalid config: pathmap: {}", err);
+                std::process::exit(1);
+            })
+    } else {
+        flags.pathmap.clone()
+    };
+
+    let pretty = flags.pretty || config.pretty;
+    let mut opts = Options {
+        demangle:   flags.demangle   || config.demangle ||
+            (pretty && cfg!(feature = "demangle")),
+        undecorate: flags.undecorate || config.undecorate,
+        sizes:      flags.sizes      || config.sizes,
+        verbose:    flags.verbose.max(config.verbose) >= 1,
+        file_stats: flags.verbose.max(config.verbose) >= 2,
+        stats_mem:  flags.stats_mem  || config.stats_mem,
+        quiet:      flags.quiet      || config.quiet,
+        sections:   flags.section.clone().or(config.section),
+        range:      flags.range.clone(),
+        kinds,
+        name_match: flags.name_match.clone(),
+        no_internal: flags.no_internal || config.no_internal,
+        path_map,
+        forward_slashes: flags.forward_slashes || config.forward_slashes,
+        lowercase_paths: flags.lowercase_paths || config.lowercase_paths,
+        strip_drive: flags.strip_drive || config.strip_drive,
+        base:       flags.base.or(config_base),
+        members:    flags.member.clone(),
+        period,
+        carve:      flags.carve,
+        format:     flags.format.or(config_format).unwrap_or_default(),
+        sort:       flags.sort.or(config_sort).unwrap_or_default(),
+        with_module: flags.with_module || config.with_module,
+        qualify: flags.qualify || config.qualify,
+        pretty,
+        ..Default::default()
+    };
+
+    // Only color output going straight to a terminal
+    opts.color = pretty && flags.output.is_none() &&
+        io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+    opts.load.lenient = flags.lenient || config.lenient;
+    opts.load.partial = flags.partial || config.partial;
+    opts.load.strict  = flags.strict || config.strict;
+    opts.load.names   = flags.names.or(config_names).unwrap_or_default();
+
+    #[cfg(not(feature = "demangle"))]
+    if opts.demangle {
+        eprintln!("--demangle requires the `demangle` feature");
+        std::process::exit(1);
+    }
 
-        // Attempt to parse as a cabinet file
-        if let Ok(mut cabinet) = cab::Cabinet::new(fd) {
-            let mut cab_files = Vec::new();
+    // Unwrap is fine as the template is constant
+    if flags.progress || config.progress {
+        opts.progress = Some(ProgressBar::new(0).with_style(
+            ProgressStyle::with_template(
+                "{bar:40} {pos}/{len} [{elapsed}] {wide_msg}")
+            .unwrap()));
+    }
 
-            // Go through all files and folders
-            for folder in cabinet.folder_entries() {
-                for file in folder.file_entries() {
-                    cab_files.push(file.name().to_string());
-                }
+    // Unwrap is fine, the global pool hasn't been used or built yet
+    if let Some(jobs) = flags.jobs.or(config.jobs) {
+        rayon::ThreadPoolBuilder::new().num_threads(jobs)
+            .build_global().unwrap();
+    }
+
+    // Without a command the files are dumped
+    let command = cli.command.unwrap_or(Command::Dump { files: cli.files });
+
+    // Commands which only look at the header and sections don't need the
+    // symbols parsed
+    if matches!(command, Command::Header { .. } | Command::Sections { .. } |
+            Command::ToOffset { .. } | Command::ToRva { .. } |
+            Command::Raw { .. }) {
+        opts.load.lazy = true;
+    }
+
+    // Everything printed goes through one buffered writer
+    let mut output: Box<dyn io::Write> = match &flags.output {
+        Some(path) => match File::create(path) {
+            Ok(fd) => Box::new(BufWriter::new(fd)),
+            Err(err) => {
+                eprintln!("{}: error: {}", path.display(), err);
+                std::process::exit(1);
             }
-            
-            // Extract the files and parse them
-            for filename in cab_files {
-                let reader = cabinet.read_file(&filename)
-                    .map_err(Error::ExtractCab)?;
-                dump_info(reader)?;
+        },
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+    let output = output.as_mut();
+
+    // Tag dumps of symbol CDs with the architecture and type of each module
+    opts.modules = matches!(command, Command::Dump { .. });
+
+    let ret = match &command {
+        Command::Dump { files } if opts.pretty => {
+            run(files, &opts, output, |out, dbg| dump_pretty(out, dbg, &opts))
+        }
+        Command::Dump { files } => {
+            run(files, &opts, output, |out, dbg| dump_info(out, dbg, &opts))
+        }
+        Command::Lookup { symbol, files } => {
+            run(files, &opts, output, |out, dbg| {
+                find_symbol(out, dbg, symbol, &opts)
+            })
+        }
+        Command::Line { spec, files } => {
+            run(files, &opts, output, |out, dbg| {
+                find_lines(out, dbg, &spec.source, spec.lines.clone(), &opts)
+            })
+        }
+        Command::Grep { ignore_case, kinds, pattern, files } => {
+            let re = match RegexBuilder::new(pattern)
+                    .case_insensitive(*ignore_case).build() {
+                Ok(re) => re,
+                Err(err) => {
+                    eprintln!("Invalid pattern: {}", err);
+                    std::process::exit(1);
+                }
+            };
+
+            run(files, &opts, output, |out, dbg| {
+                grep(out, dbg, &re, *kinds, &opts)
+            })
+        }
+        Command::Sources { files } => {
+            run(files, &opts, output, |out, dbg| dump_sources(out, dbg, &opts))
+        }
+        Command::Overlaps { files } => {
+            run(files, &opts, output, |out, dbg| {
+                dump_overlaps(out, dbg, &opts)
+            })
+        }
+        Command::Header { files } => run(files, &opts, output, dump_header),
+        Command::Stats { files } => {
+            run(files, &opts, output, |out, dbg| dump_stats(out, dbg, &opts))
+        }
+        Command::Sections { files } => run(files, &opts, output, dump_sections),
+        Command::ToOffset { rva: addr, files } |
+                Command::ToRva { offset: addr, files } => {
+            // Translate between RVAs and offsets in the original image
+            let to_offset = matches!(command, Command::ToOffset { .. });
+            run(files, &opts, output, |out, dbg| {
+                let translated = if to_offset {
+                    dbg.rva_to_file_offset(*addr)
+                } else {
+                    dbg.file_offset_to_rva(*addr)
+                };
+
+                match translated {
+                    Some(translated) => {
+                        outln!(out, "{:08x} -> {:08x}", addr, translated);
+                    }
+                    None => outln!(out, "{:08x} -> not in any section", addr),
+                }
+                Ok(())
+            })
+        }
+        Command::Diff { old, new } => diff(old, new, &opts, output),
+        #[cfg(feature = "cab")]
+        Command::List { files } => list(files, output),
+        #[cfg(feature = "cab")]
+        Command::Extract { cab, dir } => extract(cab, dir, &opts, output),
+        Command::FromMap { machine, map, dbg } => {
+            from_map(map, dbg, *machine, output)
+        }
+        Command::Split { image, dbg } => split(image, dbg.as_deref(), output),
+        Command::Raw { typ, files } => raw(files, *typ, &opts, output),
+        Command::Strip {
+            keep, drop, no_functions, no_globals, no_lines, obfuscate,
+            input, dbg,
+        } => {
+            let strip_opts = StripOptions {
+                keep,
+                drop,
+                no_functions: *no_functions,
+                no_globals:   *no_globals,
+                no_lines:     *no_lines,
+                obfuscate:    *obfuscate,
+            };
+            strip(input, dbg, &strip_opts, &opts, output)
+        }
+        Command::Repl { file } => repl(file, &opts, output),
+        #[cfg(feature = "tui")]
+        Command::Browse { file } => match load_file(file, &opts) {
+            Ok(dbg) => tui::browse(&dbg, &opts).map(|()| 0),
+            Err(err) => {
+                eprintln!("{}: error: {}", file, err);
+                Ok(1)
             }
-        } else {
-            // Didn't seem to be a CAB, attempt to parse as `DI`
-            dump_info(BufReader::new(File::open(file).map_err(|x| {
-                Error::Open(Path::new(file).to_path_buf(), x)
-            })?))?;
+        },
+        Command::Coverage { module, file, trace } => {
+            coverage(file, trace, module.as_deref(), &opts, output)
+        }
+        Command::Symbolize { modules, symbols, addrs } => {
+            symbolize(modules, symbols, addrs, &opts, output)
         }
+        Command::Meso { module, lines, input, meso: path } => {
+            meso(input, path.as_deref(), module.as_deref(), *lines, &opts,
+                output)
+        }
+    };
+
+    let failures = match ret.and_then(|failures| {
+        output.flush()?;
+        Ok(failures)
+    }) {
+        Ok(failures) => failures,
+
+        // Whatever was reading the output went away, eg. `head`
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+
+        Err(err) => {
+            eprintln!("dbgparse: failed to write output: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    if opts.verbose {
+        let stats = &opts.stats;
+        eprintln!("dbgparse: {} files, {} functions, {} globals, {} lines, \
+            {} warnings, {} failed in {:.2?}",
+            stats.files.load(Ordering::Relaxed),
+            stats.functions.load(Ordering::Relaxed),
+            stats.globals.load(Ordering::Relaxed),
+            stats.lines.load(Ordering::Relaxed),
+            stats.warnings.load(Ordering::Relaxed),
+            failures, start.elapsed());
+    }
+
+    if opts.stats_mem {
+        let memory = opts.stats.memory.lock().unwrap();
+        eprintln!("dbgparse: memory of {} files:",
+            opts.stats.files.load(Ordering::Relaxed));
+        eprintln!("    {:<16} {:>9} {:>12}", "Table", "Entries", "Bytes");
+        for (name, table) in memory.tables() {
+            eprintln!("    {:<16} {:>9} {:>12}", name, table.entries,
+                table.bytes);
+        }
+        eprintln!("    {:<16} {:>9} {:>12}", "total", "", memory.total());
+    }
+
+    if failures > 0 {
+        eprintln!("dbgparse: {} input(s) failed", failures);
+        std::process::exit(1);
     }
 
     Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_dates() {
+        for date in ["1970-01-01", "1995-01-29", "2000-02-29", "2024-12-31",
+                "2106-02-07"] {
+            let stamp = parse_date(date).unwrap();
+            assert_eq!(format_timestamp(stamp),
+                format!("{} 00:00:00 UTC", date));
+        }
+        assert_eq!(parse_date("1970-01-02"), Ok(86400));
+        assert_eq!(parse_date("0x2f6c1a3b"), Ok(0x2f6c1a3b));
+    }
+
+    #[test]
+    fn bad_dates() {
+        for date in ["1969-12-31", "2106-02-08", "1000000000000-01-01",
+                "2001-02-29", "2100-02-29", "2001-02-31", "2001-04-31",
+                "2001-13-01", "2001-00-10", "2001-01-00", "2001-01",
+                "01/02/2001", "0x", "0x100000000", ""] {
+            assert!(parse_date(date).is_err(), "{}", date);
+        }
+    }
+}
@@ -0,0 +1,336 @@
+//! Parser for MSVC linker `.map` files, turning them into a [`DbgWriter`] so
+//! projects which only kept their map files can get a `.dbg`
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use crate::{DbgWriter, Error, Header, Result, Section};
+
+/// Part of the map file being parsed
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Part {
+    /// Before or between the interesting parts
+    Other,
+
+    /// The table of segments, each part of a section
+    Segments,
+
+    /// The publics or static symbols, by value
+    Symbols,
+
+    /// Line numbers for a source file
+    Lines,
+}
+
+/// A segment from the table at the start of the map, used internally
+struct Segment {
+    /// Name of the section, eg. `.text` for `.text$mn`
+    name: String,
+
+    /// Size of the section as far as the map knows
+    size: u32,
+
+    /// Set if the class of the section is `CODE`
+    code: bool,
+
+    /// Line of the map the segment was first given on
+    line: usize,
+}
+
+/// Parse a `seg:offset` address
+fn parse_address(text: &str) -> Option<(u16, u32)> {
+    let (seg, offset) = text.split_once(':')?;
+    Some((u16::from_str_radix(seg, 16).ok()?,
+        u32::from_str_radix(offset, 16).ok()?))
+}
+
+/// Get the `IMAGE_SCN_*` characteristics for a section, from its name and
+/// whether it holds code as the map doesn't say anything else about it
+fn characteristics(segment: &Segment) -> u32 {
+    if segment.code {
+        0x60000020 // code, execute, read
+    } else if segment.name == ".bss" {
+        0xc0000080 // uninitialized data, read, write
+    } else if matches!(segment.name.as_str(),
+            ".rdata" | ".edata" | ".rsrc" | ".xdata" | ".pdata") {
+        0x40000040 // initialized data, read
+    } else {
+        0xc0000040 // initialized data, read, write
+    }
+}
+
+impl DbgWriter {
+    /// Build a writer from the text of an MSVC linker `.map` file
+    ///
+    /// The publics and static symbols become functions (those marked `f`)
+    /// and globals, the segment table becomes the sections, and any line
+    /// number tables become source lines. The map doesn't say what machine
+    /// the image is for, so the header is left with [`Machine::Unknown`] for
+    /// the caller to fill in.
+    ///
+    /// [`Machine::Unknown`]: crate::Machine::Unknown
+    pub fn from_map(text: &str) -> Result<Self> {
+        let mut ret = Self::new(Header {
+            section_align: 0x1000,
+            ..Default::default()
+        });
+
+        let mut part = Part::Other;
+        let mut segments: BTreeMap<u16, Segment> = BTreeMap::new();
+        let mut seg_bases: BTreeMap<u16, u32> = BTreeMap::new();
+        let mut symbols = Vec::new();
+        let mut lines = Vec::new();
+        let mut source = String::new();
+
+        for (idx, line) in text.lines().enumerate() {
+            let err = |reason| Error::Map(idx + 1, reason);
+            let trimmed = line.trim();
+            let tokens = trimmed.split_whitespace().collect::<Vec<_>>();
+
+            // Headers which start each part
+            if let Some(stamp) = trimmed.strip_prefix("Timestamp is ") {
+                let stamp = stamp.split_whitespace().next().unwrap_or("");
+                ret.header.timedatestamp = u32::from_str_radix(stamp, 16)
+                    .map_err(|_| err("bad timestamp"))?;
+                continue;
+            } else if let Some(base) =
+                    trimmed.strip_prefix("Preferred load address is ") {
+                ret.header.image_base = u32::from_str_radix(base.trim(), 16)
+                    .map_err(|_| err("bad load address"))?;
+                continue;
+            } else if tokens.starts_with(&["Start", "Length"]) {
+                part = Part::Segments;
+                continue;
+            } else if trimmed.contains("Publics by Value") ||
+                    trimmed == "Static symbols" {
+                part = Part::Symbols;
+                continue;
+            } else if trimmed.starts_with("entry point at") {
+                part = Part::Other;
+                continue;
+            } else if let Some(rest) =
+                    trimmed.strip_prefix("Line numbers for ") {
+                // `obj(source) segment .text`, the source may have
+                // parentheses of its own
+                let open = rest.find('(').ok_or_else(|| err("no source"))?;
+                let close = rest.rfind(')').ok_or_else(|| err("no source"))?;
+                source = rest.get(open + 1..close)
+                    .ok_or_else(|| err("no source"))?.to_string();
+                part = Part::Lines;
+                continue;
+            }
+
+            match part {
+                // `0001:00000000 00001234H .text$mn CODE`
+                Part::Segments => {
+                    let [address, length, name, class, ..] = tokens[..] else {
+                        continue;
+                    };
+                    let Some((seg, offset)) = parse_address(address) else {
+                        continue;
+                    };
+                    let length = u32::from_str_radix(
+                        length.trim_end_matches(['H', 'h']), 16)
+                        .map_err(|_| err("bad segment length"))?;
+
+                    let name = name.split('$').next().unwrap_or(name);
+                    let segment = segments.entry(seg).or_insert(Segment {
+                        name: name.to_string(),
+                        size: 0,
+                        code: false,
+                        line: idx + 1,
+                    });
+                    segment.size = segment.size.max(offset.saturating_add(
+                        length));
+                    segment.code |= class == "CODE";
+                }
+
+                // `0001:00000000 _WinMain@16 00401000 f write.obj`
+                Part::Symbols => {
+                    let [address, name, va, ref rest @ ..] = tokens[..] else {
+                        continue;
+                    };
+                    let Some((seg, offset)) = parse_address(address) else {
+                        continue;
+                    };
+                    let va = u32::from_str_radix(va, 16)
+                        .map_err(|_| err("bad symbol address"))?;
+
+                    // Absolute symbols have no address in the image
+                    if seg == 0 || va < ret.header.image_base {
+                        continue;
+                    }
+
+                    let rva = va - ret.header.image_base;
+                    seg_bases.entry(seg).or_insert(rva.wrapping_sub(offset));
+                    symbols.push((rva, name.to_string(),
+                        rest.first() == Some(&"f")));
+                }
+
+                // `    6 0001:00000000     8 0001:0000000c`
+                Part::Lines => {
+                    // Anything else ends the table, such as the exports or
+                    // the fixups of `/MAPINFO` which may follow it
+                    let pairs = tokens.chunks(2).map(|pair| {
+                        let [number, address] = pair else { return None; };
+                        Some((number.parse::<u32>().ok()?,
+                            parse_address(address)?))
+                    }).collect::<Option<Vec<_>>>();
+                    let Some(pairs) = pairs else {
+                        part = Part::Other;
+                        continue;
+                    };
+
+                    for (number, (seg, offset)) in pairs {
+                        lines.push((seg, offset, source.clone(), number));
+                    }
+                }
+
+                Part::Other => {}
+            }
+        }
+
+        // Sections without any symbols to place them are laid out after the
+        // ones before them
+        let mut next = ret.header.section_align;
+        for (seg, segment) in &segments {
+            let vaddr = *seg_bases.entry(*seg).or_insert(next);
+            next = vaddr.checked_add(segment.size)
+                .and_then(|x| {
+                    x.checked_next_multiple_of(ret.header.section_align)
+                })
+                .ok_or(Error::Map(segment.line, "segment doesn't fit"))?;
+
+            ret.section(Section {
+                name:            segment.name.clone(),
+                vsize:           segment.size,
+                vaddr,
+                raw_data_sz:     0,
+                ptr_raw_data:    0,
+                characteristics: characteristics(segment),
+            });
+        }
+        ret.header.size_of_image = next;
+
+        for (rva, name, is_function) in symbols {
+            if is_function {
+                ret.function(rva, &name);
+            } else {
+                ret.global(rva, &name);
+            }
+        }
+
+        for (seg, offset, source, number) in lines {
+            if let Some(base) = seg_bases.get(&seg) {
+                ret.line(base.wrapping_add(offset), &source, number);
+            }
+        }
+
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DbgFile;
+
+    /// Map of a small image, with statics, lines, and the exports after them
+    const MAP: &str = r" write
+
+ Timestamp is 2f1c4a3b (Sun Jan 29 18:12:11 1995)
+
+ Preferred load address is 01000000
+
+ Start         Length     Name                   Class
+ 0001:00000000 00001234H .text                   CODE
+ 0002:00000000 00000100H .rdata                  DATA
+ 0003:00000000 00000200H .data                   DATA
+ 0003:00000200 00000080H .bss                    DATA
+
+  Address         Publics by Value              Rva+Base     Lib:Object
+
+ 0001:00000000       _WinMain@16                01001000 f   write.obj
+ 0001:00000100       _helper                    01001100 f   write.obj
+ 0003:00000010       _g_count                   01004010     write.obj
+
+ entry point at        0001:00000000
+
+ Static symbols
+
+ 0001:00000200       _static_fn                 01001200 f   write.obj
+
+Line numbers for .\write.obj(d:\src\write (x86)\write.c) segment .text
+
+     6 0001:00000000     8 0001:0000000c    10 0001:00000100
+    12 0001:00000200
+
+ Exports
+
+  ordinal    name
+
+        1    _WinMain@16
+";
+
+    #[test]
+    fn from_map() {
+        let writer = DbgWriter::from_map(MAP).unwrap();
+        assert_eq!(writer.header.timedatestamp, 0x2f1c4a3b);
+        assert_eq!(writer.header.image_base, 0x01000000);
+        assert_eq!(writer.header.size_of_image, 0x5000);
+
+        // `.rdata` has no symbols, so goes after `.text`
+        let sections = writer.sections.iter()
+            .map(|x| (x.name.as_str(), x.vaddr, x.vsize, x.characteristics))
+            .collect::<Vec<_>>();
+        assert_eq!(sections, [
+            (".text",  0x1000, 0x1234, 0x60000020),
+            (".rdata", 0x3000, 0x100,  0x40000040),
+            (".data",  0x4000, 0x280,  0xc0000040),
+        ]);
+
+        let functions = writer.functions.iter()
+            .map(|(rva, name)| (*rva, name.as_str())).collect::<Vec<_>>();
+        assert_eq!(functions, [
+            (0x1000, "_WinMain@16"),
+            (0x1100, "_helper"),
+            (0x1200, "_static_fn"),
+        ]);
+        assert_eq!(writer.globals[&0x4010], "_g_count");
+
+        let source = r"d:\src\write (x86)\write.c";
+        let lines = writer.lines.iter()
+            .map(|(rva, (file, line))| (*rva, file.as_str(), *line))
+            .collect::<Vec<_>>();
+        assert_eq!(lines, [
+            (0x1000, source, 6),
+            (0x100c, source, 8),
+            (0x1100, source, 10),
+            (0x1200, source, 12),
+        ]);
+
+        // And it makes a file which parses
+        let dbg = DbgFile::parse(&writer.to_bytes()).unwrap();
+        assert_eq!(dbg.functions()[&0x1200], "_static_fn");
+        assert_eq!(dbg.lines()[&0x100c], (source.into(), 8));
+    }
+
+    #[test]
+    fn segment_too_large() {
+        let map = " Start         Length     Name                   Class
+ 0001:00000000 00001000H .text                   CODE
+ 0002:00000000 FFFFF000H .data                   DATA
+";
+        assert!(matches!(DbgWriter::from_map(map),
+            Err(Error::Map(3, "segment doesn't fit"))));
+    }
+
+    #[test]
+    fn bad_symbol_address() {
+        let map = "  Address         Publics by Value              Rva+Base
+ 0001:00000000       _main                      0100100g f   a.obj
+";
+        assert!(matches!(DbgWriter::from_map(map),
+            Err(Error::Map(2, "bad symbol address"))));
+    }
+}
@@ -0,0 +1,48 @@
+//! Breakpoint lists in the `.meso` format of the `mesos` coverage tool, so
+//! NT-era images can be coverage traced with nothing but their `.dbg`
+//!
+//! A `.meso` is the length prefixed name of the module, then each function
+//! as its length prefixed name, its RVA, and the offsets from its start of
+//! the places to put breakpoints. All integers are little endian.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use crate::DbgFile;
+
+/// Build the `.meso` for `dbg`, describing the module `module` (as `mesos`
+/// sees it loaded, eg. `ntdll.dll`)
+///
+/// Each function gets a breakpoint on its entry. With `line_blocks` it also
+/// gets one at each source line inside it, which is the closest we can get
+/// to basic blocks without the code. Functions whose extent isn't known only
+/// get their entry.
+pub fn to_meso(dbg: &DbgFile, module: &str, line_blocks: bool) -> Vec<u8> {
+    let mut ret = Vec::new();
+    put_name(&mut ret, module);
+
+    for (&rva, name) in dbg.functions() {
+        let mut offsets = vec![0i32];
+        if let Some(size) = dbg.function_size(rva).filter(|_| line_blocks) {
+            let end = rva.saturating_add(size);
+            offsets.extend(dbg.lines_in_range(rva.saturating_add(1)..end)
+                .map(|(line_rva, _, _)| (line_rva - rva) as i32));
+        }
+
+        put_name(&mut ret, name);
+        ret.extend_from_slice(&(rva as u64).to_le_bytes());
+        ret.extend_from_slice(&(offsets.len() as u32).to_le_bytes());
+        for offset in offsets {
+            ret.extend_from_slice(&offset.to_le_bytes());
+        }
+    }
+
+    ret
+}
+
+/// Append `name` to `out` with a `u16` length prefix, truncating it if it's
+/// too long for one
+fn put_name(out: &mut Vec<u8>, name: &str) {
+    let name = &name.as_bytes()[..name.len().min(u16::MAX as usize)];
+    out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+    out.extend_from_slice(name);
+}
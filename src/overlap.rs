@@ -0,0 +1,87 @@
+//! Sanity checks of function extents, finding functions which overlap or
+//! nest oddly, as a check of reconstructed or converted symbol data before
+//! it's fed to other tools
+//!
+//! Only extents which are actually recorded are checked, those from the
+//! `.bf` and `.ef` records of a function or from the exception function
+//! table. Sizes guessed from the distance to the next symbol can't overlap.
+
+use alloc::vec::Vec;
+use core::ops::Range;
+use crate::DbgFile;
+
+/// A problem with the extent of a function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Finding {
+    /// The function at `second` starts inside of `first`, and ends past it
+    Overlap { first: Range<u32>, second: Range<u32> },
+
+    /// The function at `inner` is entirely inside of `outer`, or its extent
+    /// isn't known
+    Nested { outer: Range<u32>, inner: u32 },
+
+    /// The global at `global` is inside of the function `function`
+    Global { function: Range<u32>, global: u32 },
+
+    /// The function at `function` ends at or before its start
+    Empty { function: u32, end: u32 },
+
+    /// The `.ef` record and the exception function table disagree on where
+    /// the function at `function` ends
+    Mismatch { function: u32, symbols_end: u32, table_end: u32 },
+}
+
+/// Get the recorded extent of the function at `rva` in `dbg`, preferring its
+/// `.bf` and `.ef` records to the exception function table
+fn extent(dbg: &DbgFile, rva: u32) -> Option<Range<u32>> {
+    dbg.function_info().get(&rva).map(|x| x.start..x.end)
+        .or_else(|| dbg.exception_ranges.get(&rva).map(|&end| rva..end))
+}
+
+/// Check the extents of the functions in `dbg`, returning the problems found
+/// in address order
+pub fn check(dbg: &DbgFile) -> Vec<Finding> {
+    let mut ret = Vec::new();
+    for &rva in dbg.functions().keys() {
+        let info = dbg.function_info().get(&rva);
+        if let (Some(info), Some(&table_end)) =
+                (info, dbg.exception_ranges.get(&rva)) {
+            if info.end != table_end {
+                ret.push(Finding::Mismatch {
+                    function:    rva,
+                    symbols_end: info.end,
+                    table_end,
+                });
+            }
+        }
+
+        let Some(function) = extent(dbg, rva) else { continue; };
+        if function.end <= function.start {
+            ret.push(Finding::Empty { function: rva, end: function.end });
+            continue;
+        }
+
+        for (inner, _) in dbg.functions().range(rva + 1..function.end) {
+            match extent(dbg, *inner) {
+                Some(second) if second.end > function.end => {
+                    ret.push(Finding::Overlap {
+                        first: function.clone(),
+                        second,
+                    });
+                }
+                _ => ret.push(Finding::Nested {
+                    outer: function.clone(),
+                    inner: *inner,
+                }),
+            }
+        }
+
+        for (global, _) in dbg.globals().range(function.clone()) {
+            ret.push(Finding::Global {
+                function: function.clone(),
+                global:   *global,
+            });
+        }
+    }
+    ret
+}
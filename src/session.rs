@@ -0,0 +1,309 @@
+//! Symbolization across several modules loaded at their own base addresses,
+//! eg. the kernel, HAL, and drivers of a running system
+
+use core::fmt;
+use alloc::string::String;
+use alloc::vec::Vec;
+use crate::{module_stem, split_qualified, DbgFile, SymbolKind};
+
+/// A debug file loaded at a base address in a [`Session`]
+pub struct Module {
+    /// Name the module was added with, eg. `ntoskrnl.exe`
+    name: String,
+
+    /// Virtual address the image is loaded at
+    base: u64,
+
+    /// Debug information of the image
+    dbg: DbgFile,
+}
+
+impl Module {
+    /// Get the name the module was added with
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the virtual address the image is loaded at
+    pub fn base(&self) -> u64 {
+        self.base
+    }
+
+    /// Get the debug information of the image
+    pub fn dbg(&self) -> &DbgFile {
+        &self.dbg
+    }
+
+    /// Get the virtual address one past the end of the image, or `None` if
+    /// the header doesn't give the size of the image
+    pub fn end(&self) -> Option<u64> {
+        match self.dbg.header().size_of_image {
+            0    => None,
+            size => Some(self.base + size as u64),
+        }
+    }
+
+    /// Get the RVA of the virtual address `va` in the module, or `None` if
+    /// it's outside of the image
+    pub fn rva(&self, va: u64) -> Option<u32> {
+        if self.end().is_some_and(|end| va >= end) {
+            return None;
+        }
+        u32::try_from(va.checked_sub(self.base)?).ok()
+    }
+}
+
+/// A virtual address resolved to a module, and the symbol and source line
+/// at or before it, see [`Session::resolve_va`]
+#[derive(Clone, Copy)]
+pub struct Resolved<'a> {
+    /// Module containing the address
+    pub module: &'a Module,
+
+    /// RVA of the address in the module
+    pub rva: u32,
+
+    /// Name of the closest symbol at or before the address and the offset
+    /// of the address from the start of it
+    pub symbol: Option<(&'a str, u32)>,
+
+    /// Source file and line number at or before the address
+    pub line: Option<(&'a str, u32)>,
+}
+
+impl fmt::Display for Resolved<'_> {
+    /// Format as a debugger would, `ntoskrnl!KeBugCheckEx+0x12`, or
+    /// `ntoskrnl+0x1234` if there's no symbol
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let module = self.module.dbg.module_name()
+            .unwrap_or(&self.module.name);
+        match self.symbol {
+            Some((name, 0)) => write!(f, "{}!{}", module, name),
+            Some((name, offset)) =>
+                write!(f, "{}!{}+{:#x}", module, name, offset),
+            None => write!(f, "{}+{:#x}", module, self.rva),
+        }
+    }
+}
+
+/// Modules loaded at their base addresses, for looking up virtual addresses
+/// and names across all of them
+///
+/// ```no_run
+/// # fn main() -> dbgparse::Result<()> {
+/// use dbgparse::{DbgFile, Session};
+///
+/// let mut session = Session::new();
+/// session.add_module("ntoskrnl.exe", 0x80100000,
+///     DbgFile::open("ntoskrnl.dbg")?);
+/// session.add_module("hal.dll", 0x80010000, DbgFile::open("hal.dbg")?);
+/// if let Some(resolved) = session.resolve_va(0x8012a4f3) {
+///     println!("{}", resolved);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct Session {
+    /// Modules sorted by base address
+    modules: Vec<Module>,
+}
+
+impl Session {
+    /// Create a session without any modules
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the module `name` loaded at `base`, described by `dbg`
+    ///
+    /// The module name of `dbg` is set from `name` without its directory and
+    /// extension, so symbols are qualified as they'd be in a debugger even
+    /// if the file's image name differs. Modules loaded at the same base
+    /// are all kept, with addresses resolving to the first one added.
+    pub fn add_module(&mut self, name: &str, base: u64, mut dbg: DbgFile)
+            -> &Module {
+        dbg.set_module_name(module_stem(name));
+        let idx = self.modules.partition_point(|x| x.base <= base);
+        self.modules.insert(idx, Module { name: name.into(), base, dbg });
+        &self.modules[idx]
+    }
+
+    /// Get the modules sorted by base address
+    pub fn modules(&self) -> &[Module] {
+        &self.modules
+    }
+
+    /// Get the module named `name`, either the name it was added with or its
+    /// module name, ignoring case
+    pub fn module(&self, name: &str) -> Option<&Module> {
+        self.modules.iter().find(|x| {
+            x.name.eq_ignore_ascii_case(name) || x.dbg.module_name()
+                .is_some_and(|x| x.eq_ignore_ascii_case(name))
+        })
+    }
+
+    /// Get the module containing the virtual address `va`
+    ///
+    /// Modules whose header doesn't give the size of the image are taken to
+    /// extend up to the next module.
+    pub fn module_for_va(&self, va: u64) -> Option<&Module> {
+        // Of modules at the same base, the first added is first of them
+        let idx = self.modules.partition_point(|x| x.base <= va)
+            .checked_sub(1)?;
+        let base = self.modules[idx].base;
+        let module = &self.modules[self.modules.partition_point(|x| {
+            x.base < base
+        })];
+        module.rva(va).map(|_| module)
+    }
+
+    /// Resolve the virtual address `va` to its module, and the closest
+    /// symbol and source line at or before it in that module
+    ///
+    /// Returns `None` if `va` isn't in any module.
+    pub fn resolve_va(&self, va: u64) -> Option<Resolved<'_>> {
+        let module = self.module_for_va(va)?;
+        let rva = module.rva(va)?;
+        let line = module.dbg.lines().range(..=rva).next_back()
+            .map(|(_, (source, line))| (&**source, *line));
+        Some(Resolved {
+            module,
+            rva,
+            symbol: module.dbg.nearest_symbol(rva),
+            line,
+        })
+    }
+
+    /// Find the virtual addresses of the functions and globals named
+    /// exactly `name` in every module, as (module, address, kind)
+    ///
+    /// `name` may be qualified like `ntoskrnl!KeBugCheckEx` to only look in
+    /// the module of that name, see [`Session::module`].
+    pub fn find_symbol(&self, name: &str)
+            -> Vec<(&Module, u64, SymbolKind)> {
+        let (module, name) = split_qualified(name);
+        let modules: Vec<&Module> = match module {
+            Some(module) => self.module(module).into_iter().collect(),
+            None         => self.modules.iter().collect(),
+        };
+
+        modules.into_iter().flat_map(|module| {
+            module.dbg.find_symbol(name).iter()
+                .map(move |&(rva, kind)| (module, module.base + rva as u64,
+                    kind))
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+    use crate::{DbgWriter, Header, Machine, Section};
+
+    /// Build a debug file for an image of `size_of_image` bytes with code at
+    /// 0x1000 and `functions`, each with a line in `source`
+    fn dbg(size_of_image: u32, source: &str, functions: &[(u32, &str)])
+            -> DbgFile {
+        let mut writer = DbgWriter::new(Header {
+            machine: Machine::I386,
+            size_of_image,
+            ..Default::default()
+        });
+        writer.section(Section {
+            name:            ".text".into(),
+            vsize:           0x1000,
+            vaddr:           0x1000,
+            raw_data_sz:     0x1000,
+            ptr_raw_data:    0x400,
+            characteristics: 0x60000020,
+        });
+        for (idx, &(rva, name)) in functions.iter().enumerate() {
+            writer.function(rva, name).line(rva, source, idx as u32 + 1);
+        }
+        DbgFile::parse(&writer.to_bytes()).unwrap()
+    }
+
+    /// Build a session of the kernel, and the HAL with no image size
+    fn session() -> Session {
+        let mut ret = Session::new();
+        ret.add_module("ntoskrnl.exe", 0x80100000, dbg(0x3000, "bugcheck.c",
+            &[(0x1000, "KeBugCheckEx"), (0x1100, "KeBugCheck")]));
+        ret.add_module(r"C:\WINNT\system32\hal.dll", 0x80010000,
+            dbg(0, "halx86.c", &[(0x1000, "HalReturnToFirmware"),
+                (0x1100, "KeBugCheck")]));
+        ret
+    }
+
+    #[test]
+    fn resolve_across_modules() {
+        let session = session();
+        let names = session.modules().iter().map(|x| x.name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, [r"C:\WINNT\system32\hal.dll", "ntoskrnl.exe"]);
+
+        let resolved = session.resolve_va(0x80101012).unwrap();
+        assert_eq!(resolved.module.name(), "ntoskrnl.exe");
+        assert_eq!(resolved.rva, 0x1012);
+        assert_eq!(resolved.symbol, Some(("KeBugCheckEx", 0x12)));
+        assert_eq!(resolved.line, Some(("bugcheck.c", 1)));
+        assert_eq!(format!("{}", resolved), "ntoskrnl!KeBugCheckEx+0x12");
+
+        let resolved = session.resolve_va(0x80011100).unwrap();
+        assert_eq!(format!("{}", resolved), "hal!KeBugCheck");
+        assert_eq!(resolved.line, Some(("halx86.c", 2)));
+
+        // Before the first symbol of a module
+        let resolved = session.resolve_va(0x80100010).unwrap();
+        assert_eq!(resolved.symbol, None);
+        assert_eq!(format!("{}", resolved), "ntoskrnl+0x10");
+    }
+
+    #[test]
+    fn module_extents() {
+        let session = session();
+        assert!(session.resolve_va(0x8000ffff).is_none());
+
+        // The kernel ends with its image, the HAL goes up to the kernel
+        assert_eq!(session.module("ntoskrnl").unwrap().end(),
+            Some(0x80103000));
+        assert!(session.resolve_va(0x80103000).is_none());
+        assert_eq!(session.module_for_va(0x800fffff).unwrap().name(),
+            r"C:\WINNT\system32\hal.dll");
+        assert_eq!(session.module("HAL").unwrap().rva(0x800fffff),
+            Some(0xeffff));
+        assert!(session.module("ntdll").is_none());
+    }
+
+    #[test]
+    fn same_base() {
+        let mut session = Session::new();
+        session.add_module("first.dll", 0x10000000,
+            dbg(0x2000, "first.c", &[(0x1000, "First")]));
+        session.add_module("second.dll", 0x10000000,
+            dbg(0x2000, "second.c", &[(0x1000, "Second")]));
+        assert_eq!(session.modules().len(), 2);
+        assert_eq!(format!("{}", session.resolve_va(0x10001000).unwrap()),
+            "first!First");
+    }
+
+    #[test]
+    fn find_symbols() {
+        let session = session();
+        let found = |name| session.find_symbol(name).iter()
+            .map(|(module, va, kind)| (module.name().to_string(), *va, *kind))
+            .collect::<Vec<_>>();
+
+        assert_eq!(found("KeBugCheck"), [
+            (r"C:\WINNT\system32\hal.dll".into(), 0x80011100,
+                SymbolKind::Function),
+            ("ntoskrnl.exe".into(), 0x80101100, SymbolKind::Function),
+        ]);
+        assert_eq!(found("ntoskrnl!KeBugCheck"), [
+            ("ntoskrnl.exe".into(), 0x80101100, SymbolKind::Function),
+        ]);
+        assert!(found("ntdll!KeBugCheck").is_empty());
+        assert!(found("KeBugCheck2").is_empty());
+    }
+}
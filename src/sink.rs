@@ -0,0 +1,114 @@
+//! Sinks receiving the records of a parsed file, so output formats can be
+//! added without touching the parser
+//!
+//! [`DbgFile::emit`] walks a file and hands each function, global, exported
+//! name, alias, and source line to a [`SymbolSink`]. [`TextSink`] writes the
+//! plain text format of the `dbgparse` tool.
+
+use std::io::{self, Write};
+use crate::DbgFile;
+
+/// Receiver of the records of a [`DbgFile`], see [`DbgFile::emit`]
+///
+/// Records of each kind come in address order, functions first, then
+/// globals, exported names, aliases, and lines. The file is passed along with
+/// each record so sinks can look up sizes and sections. Exported names and
+/// aliases are ignored unless overridden.
+pub trait SymbolSink {
+    /// Receive the function `name` at `rva`
+    fn function(&mut self, dbg: &DbgFile, rva: u32, name: &str)
+        -> io::Result<()>;
+
+    /// Receive the global `name` at `rva`
+    fn global(&mut self, dbg: &DbgFile, rva: u32, name: &str)
+        -> io::Result<()>;
+
+    /// Receive the exported name `name`
+    fn export(&mut self, _dbg: &DbgFile, _name: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Receive the alias `alias` of `target` at `rva`
+    fn alias(&mut self, _dbg: &DbgFile, _alias: &str, _target: &str,
+            _rva: u32) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Receive line `line` of `source` at `rva`
+    fn line(&mut self, dbg: &DbgFile, rva: u32, source: &str, line: u32)
+        -> io::Result<()>;
+}
+
+impl DbgFile {
+    /// Feed every record of the file to `sink`, stopping at the first error
+    pub fn emit(&self, sink: &mut impl SymbolSink) -> io::Result<()> {
+        for (&rva, name) in self.functions() {
+            sink.function(self, rva, name)?;
+        }
+        for (&rva, name) in self.globals() {
+            sink.global(self, rva, name)?;
+        }
+        for name in self.exported_names() {
+            sink.export(self, name)?;
+        }
+        for (alias, (target, rva)) in self.aliases() {
+            sink.alias(self, alias, target, *rva)?;
+        }
+        for (&rva, (source, line)) in self.lines() {
+            sink.line(self, rva, source, *line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Sink writing the plain text format of `dbgparse`, one record per line:
+///
+/// ```text
+/// F <rva> <function>
+/// G <rva> <global>
+/// E <exported name>
+/// A <rva> <alias> -> <target>
+/// S <rva> <source>:<line>
+/// ```
+pub struct TextSink<W: Write> {
+    /// Where the records are written
+    out: W,
+}
+
+impl<W: Write> TextSink<W> {
+    /// Create a sink writing to `out`
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+
+    /// Get back the writer
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<W: Write> SymbolSink for TextSink<W> {
+    fn function(&mut self, _dbg: &DbgFile, rva: u32, name: &str)
+            -> io::Result<()> {
+        writeln!(self.out, "F {:08x} {}", rva, name)
+    }
+
+    fn global(&mut self, _dbg: &DbgFile, rva: u32, name: &str)
+            -> io::Result<()> {
+        writeln!(self.out, "G {:08x} {}", rva, name)
+    }
+
+    fn export(&mut self, _dbg: &DbgFile, name: &str) -> io::Result<()> {
+        writeln!(self.out, "E {}", name)
+    }
+
+    fn alias(&mut self, _dbg: &DbgFile, alias: &str, target: &str, rva: u32)
+            -> io::Result<()> {
+        writeln!(self.out, "A {:08x} {} -> {}", rva, alias, target)
+    }
+
+    fn line(&mut self, _dbg: &DbgFile, rva: u32, source: &str, line: u32)
+            -> io::Result<()> {
+        writeln!(self.out, "S {:08x} {}:{}", rva, source, line)
+    }
+}
@@ -0,0 +1,409 @@
+//! Splitting the debug information embedded in a PE image out into a
+//! separate `.dbg`, like the `REBASE -x` and `SPLITSYM` tools did
+
+use alloc::vec::Vec;
+use crate::writer::{put_u16, put_u32, HEADER_SIZE, SECTION_SIZE};
+use crate::{DebugDirectory, DebugType, Error, Result};
+
+/// Offset of `e_lfanew` in the MS-DOS header
+const LFANEW_OFFSET: usize = 0x3c;
+
+/// Size of the `IMAGE_FILE_HEADER` following the PE signature
+const FILE_HEADER_SIZE: usize = 20;
+
+/// Magic of a PE32 optional header
+const PE32_MAGIC: u16 = 0x10b;
+
+/// Magic of a PE32+ optional header
+const PE32_PLUS_MAGIC: u16 = 0x20b;
+
+/// Offset of the data directories in a PE32 optional header
+const DATA_DIRECTORIES_OFFSET: usize = 96;
+
+/// Index of the export table in the data directories
+const EXPORT_DIRECTORY: usize = 0;
+
+/// Index of the exception table (`.pdata`) in the data directories
+const EXCEPTION_DIRECTORY: usize = 3;
+
+/// Index of the debug directory in the data directories
+const DEBUG_DIRECTORY: usize = 6;
+
+/// Read a little endian `u16` at `offset` of `data`
+fn read_u16(data: &[u8], offset: usize) -> Result<u16> {
+    // Unwrap is fine as the slice size is checked by `get`
+    data.get(offset..offset.saturating_add(2))
+        .map(|x| u16::from_le_bytes(x.try_into().unwrap()))
+        .ok_or(Error::Pe("header is truncated"))
+}
+
+/// Read a little endian `u32` at `offset` of `data`
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    // Unwrap is fine as the slice size is checked by `get`
+    data.get(offset..offset.saturating_add(4))
+        .map(|x| u32::from_le_bytes(x.try_into().unwrap()))
+        .ok_or(Error::Pe("header is truncated"))
+}
+
+/// Get the offset of the PE signature in `data`, if it is a PE image
+fn pe_offset(data: &[u8]) -> Option<usize> {
+    if !data.starts_with(b"MZ") {
+        return None;
+    }
+
+    let offset = read_u32(data, LFANEW_OFFSET).ok()? as usize;
+    (data.get(offset..offset.checked_add(4)?)? == b"PE\0\0").then_some(offset)
+}
+
+/// Check if `data` is a PE image, by the MS-DOS header pointing at a PE
+/// signature
+pub fn is_pe(data: &[u8]) -> bool {
+    pe_offset(data).is_some()
+}
+
+/// A section of the image, used internally to map RVAs to file offsets
+struct ImageSection {
+    /// RVA of the start of the section
+    vaddr: u32,
+
+    /// Size of the section in memory
+    vsize: u32,
+
+    /// Size of the section's data in the file
+    raw_data_sz: u32,
+
+    /// File offset of the section's data
+    ptr_raw_data: u32,
+}
+
+/// Get the file offset of the `size` bytes at the RVA `rva`, using the
+/// sections to find where they are in the file
+fn rva_offset(sections: &[ImageSection], rva: u32, size: u32)
+        -> Result<usize> {
+    let section = sections.iter().find(|x| {
+        rva >= x.vaddr && rva - x.vaddr < x.vsize.max(x.raw_data_sz)
+    }).ok_or(Error::Pe("RVA is not in any section"))?;
+
+    // Data past the end of the raw data is zero fill, which we don't expect
+    // for anything we copy
+    let delta = rva - section.vaddr;
+    if delta as u64 + size as u64 > section.raw_data_sz as u64 {
+        return Err(Error::Pe("data is past the end of its section"));
+    }
+
+    Ok(section.ptr_raw_data as usize + delta as usize)
+}
+
+/// Get the `size` bytes of `data` at the RVA `rva`
+fn rva_slice<'a>(data: &'a [u8], sections: &[ImageSection], rva: u32,
+        size: u32) -> Result<&'a [u8]> {
+    let offset = rva_offset(sections, rva, size)?;
+    data.get(offset..offset + size as usize)
+        .ok_or(Error::Pe("section data is truncated"))
+}
+
+/// Build a `.dbg` from the debug information in the PE image `data`
+///
+/// The header is filled in from the image's file and optional headers, and
+/// the section table and exported names are copied over. Every debug
+/// directory with data in the file is copied along with its data, and
+/// `.pdata` is added as the function table if there is no exception
+/// directory already. The image itself isn't changed, so it keeps its copy
+/// of the debug information.
+///
+/// Only PE32 images are supported, as the `.dbg` header has no room for a
+/// 64-bit image base.
+pub fn split(data: &[u8]) -> Result<Vec<u8>> {
+    let pe = pe_offset(data).ok_or(Error::Pe("not a PE image"))?;
+
+    // `IMAGE_FILE_HEADER`
+    let file_header = pe + 4;
+    let machine         = read_u16(data, file_header)?;
+    let num_sections    = read_u16(data, file_header + 2)?;
+    let timedatestamp   = read_u32(data, file_header + 4)?;
+    let opt_size        = read_u16(data, file_header + 16)?;
+    let characteristics = read_u16(data, file_header + 18)?;
+
+    // `IMAGE_OPTIONAL_HEADER`
+    let opt = file_header + FILE_HEADER_SIZE;
+    match read_u16(data, opt)? {
+        PE32_MAGIC => {}
+        PE32_PLUS_MAGIC =>
+            return Err(Error::Pe("PE32+ images can't be split")),
+        _ => return Err(Error::Pe("unknown optional header magic")),
+    }
+    let image_base    = read_u32(data, opt + 28)?;
+    let section_align = read_u32(data, opt + 32)?;
+    let size_of_image = read_u32(data, opt + 56)?;
+    let checksum      = read_u32(data, opt + 64)?;
+    let num_dirs      = read_u32(data, opt + 92)? as usize;
+
+    // Get the (RVA, size) of a data directory, if the image has it
+    let data_dir = |idx: usize| -> Result<Option<(u32, u32)>> {
+        if idx >= num_dirs ||
+                DATA_DIRECTORIES_OFFSET + idx * 8 + 8 > opt_size as usize {
+            return Ok(None);
+        }
+
+        let offset = opt + DATA_DIRECTORIES_OFFSET + idx * 8;
+        let rva  = read_u32(data, offset)?;
+        let size = read_u32(data, offset + 4)?;
+        Ok((rva != 0 && size != 0).then_some((rva, size)))
+    };
+
+    // The section table follows the optional header
+    let table = opt + opt_size as usize;
+    let section_headers = data
+        .get(table..table + num_sections as usize * SECTION_SIZE)
+        .ok_or(Error::Pe("section table is truncated"))?;
+    let sections = section_headers.chunks_exact(SECTION_SIZE)
+        .map(|x| Ok(ImageSection {
+            vsize:        read_u32(x, 8)?,
+            vaddr:        read_u32(x, 12)?,
+            raw_data_sz:  read_u32(x, 16)?,
+            ptr_raw_data: read_u32(x, 20)?,
+        }))
+        .collect::<Result<Vec<_>>>()?;
+
+    // Names from the export directory, each null terminated
+    let mut exported_names = Vec::new();
+    if let Some((rva, size)) = data_dir(EXPORT_DIRECTORY)? {
+        let exports = rva_slice(data, &sections, rva, size)?;
+        let num_names = read_u32(exports, 24)?;
+        let names_rva = read_u32(exports, 32)?;
+        let names = rva_slice(data, &sections, names_rva,
+            num_names.checked_mul(4)
+                .ok_or(Error::Pe("too many exported names"))?)?;
+
+        for name_rva in names.chunks_exact(4) {
+            let offset = rva_offset(&sections, read_u32(name_rva, 0)?, 1)?;
+            let name = data.get(offset..).unwrap_or_default()
+                .split(|&x| x == 0).next().unwrap_or_default();
+            exported_names.extend_from_slice(name);
+            exported_names.push(0);
+        }
+    }
+
+    // Debug directories with their data, as (directory, data)
+    let mut directories = Vec::new();
+    if let Some((rva, size)) = data_dir(DEBUG_DIRECTORY)? {
+        let raw = rva_slice(data, &sections, rva, size)?;
+        for dir in raw.chunks_exact(DebugDirectory::SIZE) {
+            let size = read_u32(dir, 16)?;
+            let ptr  = read_u32(dir, 24)?;
+            if size == 0 || ptr == 0 {
+                continue;
+            }
+
+            let contents = data.get(ptr as usize..ptr as usize + size as usize)
+                .ok_or(Error::Pe("debug data is truncated"))?;
+            directories.push((dir.to_vec(), contents));
+        }
+    }
+    if directories.is_empty() {
+        return Err(Error::Pe("no debug information"));
+    }
+
+    // RISC images have their function table in `.pdata`
+    let exception = u32::from(DebugType::Exception);
+    if !directories.iter().any(|(dir, _)| read_u32(dir, 12).ok() ==
+            Some(exception)) {
+        if let Some((rva, size)) = data_dir(EXCEPTION_DIRECTORY)? {
+            let contents = rva_slice(data, &sections, rva, size)?;
+
+            let mut dir = Vec::with_capacity(DebugDirectory::SIZE);
+            put_u32(&mut dir, 0); // characteristics
+            put_u32(&mut dir, timedatestamp);
+            put_u16(&mut dir, 0); // major_version
+            put_u16(&mut dir, 0); // minor_version
+            put_u32(&mut dir, exception);
+            put_u32(&mut dir, size);
+            put_u32(&mut dir, 0); // addr_raw_data
+            put_u32(&mut dir, 0); // ptr_raw_data, filled in below
+            directories.push((dir, contents));
+        }
+    }
+
+    let mut ret = Vec::new();
+    ret.extend_from_slice(b"DI");
+    put_u16(&mut ret, 0); // flags
+    put_u16(&mut ret, machine);
+    put_u16(&mut ret, characteristics);
+    put_u32(&mut ret, timedatestamp);
+    put_u32(&mut ret, checksum);
+    put_u32(&mut ret, image_base);
+    put_u32(&mut ret, size_of_image);
+    put_u32(&mut ret, num_sections as u32);
+    put_u32(&mut ret, exported_names.len() as u32);
+    put_u32(&mut ret, (directories.len() * DebugDirectory::SIZE) as u32);
+    put_u32(&mut ret, section_align);
+    ret.extend_from_slice(&[0; 8]);
+    ret.extend_from_slice(section_headers);
+    ret.extend_from_slice(&exported_names);
+
+    // The data follows the directories, in the same order
+    let mut offset = HEADER_SIZE + section_headers.len() +
+        exported_names.len() + directories.len() * DebugDirectory::SIZE;
+    for (dir, contents) in &directories {
+        ret.extend_from_slice(&dir[..24]);
+        put_u32(&mut ret, offset as u32);
+        offset += contents.len();
+    }
+    for (_, contents) in &directories {
+        ret.extend_from_slice(contents);
+    }
+
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use crate::{DbgFile, DbgWriter, Header, Machine, Section};
+
+    /// Overwrite the little endian `u32` at `offset` in `data`
+    fn set_u32(data: &mut [u8], offset: usize, val: u32) {
+        data[offset..offset + 4].copy_from_slice(&val.to_le_bytes());
+    }
+
+    /// Build the COFF debug information of a `main` function at 0x1000
+    fn coff() -> Vec<u8> {
+        let mut writer = DbgWriter::new(Header {
+            machine: Machine::I386,
+            ..Default::default()
+        });
+        writer.section(Section {
+            name:            ".text".into(),
+            vsize:           0x1000,
+            vaddr:           0x1000,
+            raw_data_sz:     0x200,
+            ptr_raw_data:    0x200,
+            characteristics: 0x60000020,
+        });
+        writer.function(0x1000, "_main").line(0x1000, "main.c", 3);
+        let data = writer.to_bytes();
+        let dir = DbgFile::parse(&data).unwrap().debug_directory(
+            DebugType::Coff).unwrap().clone();
+        data[dir.ptr_raw_data as usize..][..dir.size_of_data as usize]
+            .to_vec()
+    }
+
+    /// Build an i386 image with an optional header of `magic`, with `.text`
+    /// at 0x1000 and `.rdata` at 0x2000 holding the debug directory, an
+    /// export of `Main`, and `.pdata` for `_main`. The COFF debug
+    /// information isn't mapped, it's after the sections.
+    fn image(magic: u16) -> Vec<u8> {
+        let coff = coff();
+        let mut ret = vec![0u8; 0x600];
+        ret[..2].copy_from_slice(b"MZ");
+        set_u32(&mut ret, LFANEW_OFFSET, 0x80);
+        ret[0x80..0x84].copy_from_slice(b"PE\0\0");
+
+        // `IMAGE_FILE_HEADER`
+        ret[0x84..0x86].copy_from_slice(&0x14cu16.to_le_bytes());
+        ret[0x86..0x88].copy_from_slice(&2u16.to_le_bytes());
+        set_u32(&mut ret, 0x88, 0x2f6c1a3b);
+        ret[0x94..0x96].copy_from_slice(&224u16.to_le_bytes());
+        ret[0x96..0x98].copy_from_slice(&0x102u16.to_le_bytes());
+
+        // `IMAGE_OPTIONAL_HEADER`, then the data directories
+        let opt = 0x98;
+        ret[opt..opt + 2].copy_from_slice(&magic.to_le_bytes());
+        for (offset, val) in [(28, 0x400000), (32, 0x1000), (56, 0x3000),
+                (64, 0x1234), (92, 16)] {
+            set_u32(&mut ret, opt + offset, val);
+        }
+        for (idx, rva, size) in [(EXPORT_DIRECTORY, 0x2040, 40),
+                (EXCEPTION_DIRECTORY, 0x20c0, 12),
+                (DEBUG_DIRECTORY, 0x2000, DebugDirectory::SIZE as u32)] {
+            set_u32(&mut ret, opt + DATA_DIRECTORIES_OFFSET + idx * 8, rva);
+            set_u32(&mut ret, opt + DATA_DIRECTORIES_OFFSET + idx * 8 + 4,
+                size);
+        }
+
+        // Section table
+        let mut table = opt + 224;
+        for (name, vaddr, ptr, characteristics) in [
+                (b".text\0\0\0", 0x1000, 0x200, 0x60000020),
+                (b".rdata\0\0", 0x2000, 0x400, 0x40000040)] {
+            ret[table..table + 8].copy_from_slice(name);
+            for (offset, val) in [(8, 0x1000), (12, vaddr), (16, 0x200),
+                    (20, ptr), (36, characteristics)] {
+                set_u32(&mut ret, table + offset, val);
+            }
+            table += SECTION_SIZE;
+        }
+
+        // `.rdata`, the debug directory first
+        let rdata = 0x400;
+        set_u32(&mut ret, rdata + 12, DebugType::Coff.into());
+        set_u32(&mut ret, rdata + 16, coff.len() as u32);
+        set_u32(&mut ret, rdata + 24, 0x600);
+
+        // Export directory with one name
+        set_u32(&mut ret, rdata + 0x40 + 24, 1);
+        set_u32(&mut ret, rdata + 0x40 + 32, 0x2080);
+        set_u32(&mut ret, rdata + 0x80, 0x2090);
+        ret[rdata + 0x90..rdata + 0x95].copy_from_slice(b"Main\0");
+
+        // Function table entry for `_main`
+        for (offset, val) in [(0, 0x1000), (4, 0x1040), (8, 0x1000)] {
+            set_u32(&mut ret, rdata + 0xc0 + offset, val);
+        }
+
+        ret.extend_from_slice(&coff);
+        ret
+    }
+
+    #[test]
+    fn split_pe32() {
+        let image = image(PE32_MAGIC);
+        assert!(is_pe(&image));
+
+        let dbg = DbgFile::parse(&split(&image).unwrap()).unwrap();
+        let header = dbg.header();
+        assert_eq!(header.machine, Machine::I386);
+        assert_eq!(header.timedatestamp, 0x2f6c1a3b);
+        assert_eq!(header.image_base, 0x400000);
+        assert_eq!(header.size_of_image, 0x3000);
+        assert_eq!(header.checksum, 0x1234);
+
+        let names = dbg.sections().iter().map(|x| x.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, [".text", ".rdata"]);
+        assert_eq!(dbg.exported_names(), ["Main"]);
+
+        // The COFF debug information is copied, and `.pdata` added
+        let types = dbg.debug_directories().iter().map(|x| x.typ)
+            .collect::<Vec<_>>();
+        assert_eq!(types, [DebugType::Coff, DebugType::Exception]);
+        assert_eq!(dbg.functions()[&0x1000], "_main");
+        assert_eq!(dbg.lines()[&0x1000], ("main.c".into(), 3));
+        assert_eq!(dbg.function_size(0x1000), Some(0x40));
+    }
+
+    #[test]
+    fn rejects_pe32_plus() {
+        assert!(matches!(split(&image(PE32_PLUS_MAGIC)),
+            Err(Error::Pe("PE32+ images can't be split"))));
+    }
+
+    #[test]
+    fn rejects_bad_images() {
+        let mut image = image(PE32_MAGIC);
+        assert!(!is_pe(&image[..0x80]));
+        assert!(matches!(split(b"MZ"), Err(Error::Pe("not a PE image"))));
+
+        // Debug directory pointing past the end of the file
+        set_u32(&mut image, 0x400 + 24, 0x10000);
+        assert!(matches!(split(&image),
+            Err(Error::Pe("debug data is truncated"))));
+
+        // No debug directory at all
+        let opt = 0x98 + DATA_DIRECTORIES_OFFSET + DEBUG_DIRECTORY * 8;
+        set_u32(&mut image, opt, 0);
+        assert!(matches!(split(&image),
+            Err(Error::Pe("no debug information"))));
+    }
+}
@@ -0,0 +1,282 @@
+//! Compact address lookup of named symbols, usable on its own for symbols
+//! which come from somewhere other than a `.dbg` file
+
+use core::ops::{Bound, RangeBounds};
+use alloc::string::String;
+use alloc::vec::Vec;
+use crate::SymbolKind;
+
+/// Compact address lookup of named symbols, each carrying a `T`
+///
+/// All symbols live in sorted parallel `Vec`s with the names packed into a
+/// single string, which is much smaller and more cache friendly than a map
+/// per symbol. [`DbgFile::symbol_map`] builds one of the functions and
+/// globals of a file, with their [`SymbolKind`]. Maps from other sources are
+/// built with [`SymbolMap::insert`], or collected from `(rva, name, data)`
+/// tuples, and maps of several modules can be merged at their bases.
+///
+/// Symbols at the same address are kept in the order they were added.
+///
+/// [`DbgFile::symbol_map`]: crate::DbgFile::symbol_map
+#[derive(Debug, Clone)]
+pub struct SymbolMap<T = SymbolKind> {
+    /// RVA of each symbol, sorted
+    addrs: Vec<u32>,
+
+    /// Data of each symbol
+    data: Vec<T>,
+
+    /// Offsets into `names` of the start and end of the name of each symbol
+    name_spans: Vec<(u32, u32)>,
+
+    /// All names, back to back in the order they were added
+    names: String,
+}
+
+impl<T> Default for SymbolMap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> SymbolMap<T> {
+    /// Create an empty map
+    pub fn new() -> Self {
+        Self {
+            addrs:      Vec::new(),
+            data:       Vec::new(),
+            name_spans: Vec::new(),
+            names:      String::new(),
+        }
+    }
+
+    /// Get the number of symbols in the map
+    pub fn len(&self) -> usize {
+        self.addrs.len()
+    }
+
+    /// Check if there are no symbols in the map
+    pub fn is_empty(&self) -> bool {
+        self.addrs.is_empty()
+    }
+
+    /// Add the symbol `name` at `rva` carrying `data`, after any symbols
+    /// already at `rva`
+    ///
+    /// Adding in address order is cheap, anything else moves every symbol
+    /// after `rva`. Collect into the map to add many symbols in any order.
+    pub fn insert(&mut self, rva: u32, name: &str, data: T) {
+        let idx = self.addrs.partition_point(|x| *x <= rva);
+        let span = self.push_name(name);
+        self.addrs.insert(idx, rva);
+        self.data.insert(idx, data);
+        self.name_spans.insert(idx, span);
+    }
+
+    /// Add `name` to the packed names, returning its span
+    fn push_name(&mut self, name: &str) -> (u32, u32) {
+        let start = self.names.len() as u32;
+        self.names.push_str(name);
+        (start, self.names.len() as u32)
+    }
+
+    /// Get the (RVA, data, name) of the symbol at `idx`
+    pub fn get(&self, idx: usize) -> Option<(u32, &T, &str)> {
+        let (start, end) = *self.name_spans.get(idx)?;
+        Some((self.addrs[idx], &self.data[idx],
+            &self.names[start as usize..end as usize]))
+    }
+
+    /// Get all symbols in address order as (RVA, data, name)
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &T, &str)> {
+        (0..self.len()).filter_map(|idx| self.get(idx))
+    }
+
+    /// Get all symbols with RVAs in `range`, see [`SymbolMap::iter`]
+    pub fn range(&self, range: impl RangeBounds<u32>)
+            -> impl Iterator<Item = (u32, &T, &str)> {
+        let start = self.addrs.partition_point(|x| match range.start_bound() {
+            Bound::Included(start) => x < start,
+            Bound::Excluded(start) => x <= start,
+            Bound::Unbounded       => false,
+        });
+        let end = self.addrs.partition_point(|x| match range.end_bound() {
+            Bound::Included(end) => x <= end,
+            Bound::Excluded(end) => x < end,
+            Bound::Unbounded     => true,
+        });
+        (start..end.max(start)).filter_map(|idx| self.get(idx))
+    }
+
+    /// Get the index of the closest symbol at or before `rva`, for
+    /// [`SymbolMap::get`]. Of several symbols at the same address the first
+    /// one added wins, which for [`DbgFile::symbol_map`] is the function.
+    ///
+    /// [`DbgFile::symbol_map`]: crate::DbgFile::symbol_map
+    pub fn nearest_index(&self, rva: u32) -> Option<usize> {
+        // Find the last symbol at or before `rva`, then the first symbol at
+        // that address
+        let idx = self.addrs.partition_point(|x| *x <= rva).checked_sub(1)?;
+        let addr = self.addrs[idx];
+        Some(self.addrs.partition_point(|x| *x < addr))
+    }
+
+    /// Find the closest symbol at or before `rva`
+    ///
+    /// Returns the name of the symbol and the offset of `rva` from the start
+    /// of it, the same as [`DbgFile::nearest_symbol`]
+    ///
+    /// [`DbgFile::nearest_symbol`]: crate::DbgFile::nearest_symbol
+    pub fn nearest(&self, rva: u32) -> Option<(&str, u32)> {
+        self.get(self.nearest_index(rva)?)
+            .map(|(addr, _, name)| (name, rva - addr))
+    }
+
+    /// Add every symbol of `other`, moved up by `base`, eg. to look up
+    /// virtual addresses across the maps of several modules at their load
+    /// addresses. Addresses wrap around at 4 GiB.
+    pub fn merge(&mut self, other: &SymbolMap<T>, base: u32) where T: Clone {
+        self.extend(other.iter().map(|(rva, data, name)| {
+            (base.wrapping_add(rva), name, data.clone())
+        }));
+    }
+
+    /// Restore address order after symbols were added at the end out of
+    /// order, keeping symbols at the same address in the order they came
+    fn sort(&mut self) {
+        if self.addrs.is_sorted() {
+            return;
+        }
+
+        let mut order = (0..self.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&idx| self.addrs[idx]);
+        self.addrs = order.iter().map(|&idx| self.addrs[idx]).collect();
+        self.name_spans = order.iter().map(|&idx| self.name_spans[idx])
+            .collect();
+
+        // Unwrap is fine as each index is in `order` once
+        let mut data = core::mem::take(&mut self.data).into_iter()
+            .map(Some).collect::<Vec<_>>();
+        self.data = order.iter().map(|&idx| data[idx].take().unwrap())
+            .collect();
+    }
+
+    /// Release any memory held for symbols which weren't added
+    pub fn shrink_to_fit(&mut self) {
+        self.addrs.shrink_to_fit();
+        self.data.shrink_to_fit();
+        self.name_spans.shrink_to_fit();
+        self.names.shrink_to_fit();
+    }
+}
+
+impl<T, S: AsRef<str>> Extend<(u32, S, T)> for SymbolMap<T> {
+    fn extend<I: IntoIterator<Item = (u32, S, T)>>(&mut self, iter: I) {
+        for (rva, name, data) in iter {
+            let span = self.push_name(name.as_ref());
+            self.addrs.push(rva);
+            self.data.push(data);
+            self.name_spans.push(span);
+        }
+        self.sort();
+    }
+}
+
+impl<T, S: AsRef<str>> FromIterator<(u32, S, T)> for SymbolMap<T> {
+    fn from_iter<I: IntoIterator<Item = (u32, S, T)>>(iter: I) -> Self {
+        let mut ret = Self::new();
+        ret.extend(iter);
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Get the (RVA, data, name) of every symbol in `iter`, owned
+    fn owned<'a>(iter: impl Iterator<Item = (u32, &'a u8, &'a str)>)
+            -> Vec<(u32, u8, String)> {
+        iter.map(|(rva, data, name)| (rva, *data, name.into())).collect()
+    }
+
+    #[test]
+    fn insert_in_any_order() {
+        let mut map = SymbolMap::new();
+        assert!(map.is_empty());
+        map.insert(0x2000, "b", 2u8);
+        map.insert(0x1000, "a", 1);
+        map.insert(0x2000, "b2", 3);
+        map.insert(0x3000, "c", 4);
+        assert_eq!(map.len(), 4);
+
+        assert_eq!(owned(map.iter()), [
+            (0x1000, 1, "a".into()),
+            (0x2000, 2, "b".into()),
+            (0x2000, 3, "b2".into()),
+            (0x3000, 4, "c".into()),
+        ]);
+        assert_eq!(map.get(4), None);
+    }
+
+    #[test]
+    fn collect_keeps_order_at_an_address() {
+        let map = [(0x2000, "b", 2u8), (0x1000, "a", 1), (0x2000, "b2", 3)]
+            .into_iter().collect::<SymbolMap<_>>();
+        assert_eq!(owned(map.iter()), [
+            (0x1000, 1, "a".into()),
+            (0x2000, 2, "b".into()),
+            (0x2000, 3, "b2".into()),
+        ]);
+    }
+
+    #[test]
+    fn nearest() {
+        let map = [(0x1000, "a", 1u8), (0x2000, "b", 2), (0x2000, "b2", 3)]
+            .into_iter().collect::<SymbolMap<_>>();
+        assert_eq!(map.nearest(0xfff), None);
+        assert_eq!(map.nearest(0x1000), Some(("a", 0)));
+        assert_eq!(map.nearest(0x1fff), Some(("a", 0xfff)));
+
+        // The first symbol added at an address wins
+        assert_eq!(map.nearest_index(0x2010), Some(1));
+        assert_eq!(map.nearest(0x2010), Some(("b", 0x10)));
+        assert_eq!(map.nearest(u32::MAX), Some(("b", u32::MAX - 0x2000)));
+    }
+
+    #[test]
+    fn range() {
+        let map = [(0x1000, "a", 1u8), (0x2000, "b", 2), (0x3000, "c", 3)]
+            .into_iter().collect::<SymbolMap<_>>();
+        let names = |iter: &mut dyn Iterator<Item = (u32, &u8, &str)>| {
+            iter.map(|x| x.2.to_string()).collect::<Vec<_>>()
+        };
+        assert_eq!(names(&mut map.range(0x1000..0x3000)), ["a", "b"]);
+        assert_eq!(names(&mut map.range(0x1001..=0x3000)), ["b", "c"]);
+        assert_eq!(names(&mut map.range(..)), ["a", "b", "c"]);
+        assert_eq!(names(&mut map.range((Bound::Excluded(0x2000),
+            Bound::Unbounded))), ["c"]);
+        assert!(names(&mut map.range((Bound::Included(0x3001),
+            Bound::Excluded(0x1000)))).is_empty());
+    }
+
+    #[test]
+    fn merge_at_bases() {
+        let kernel = [(0x1000, "KeBugCheckEx", 1u8)].into_iter()
+            .collect::<SymbolMap<_>>();
+        let hal = [(0x100, "HalReturnToFirmware", 2u8)].into_iter()
+            .collect::<SymbolMap<_>>();
+
+        let mut map = SymbolMap::new();
+        map.merge(&kernel, 0x80100000);
+        map.merge(&hal, 0x80010000);
+        assert_eq!(owned(map.iter()), [
+            (0x80010100, 2, "HalReturnToFirmware".into()),
+            (0x80101000, 1, "KeBugCheckEx".into()),
+        ]);
+
+        // Addresses past 4 GiB wrap around
+        map.merge(&hal, 0xffffff80);
+        assert_eq!(map.nearest(0x80), Some(("HalReturnToFirmware", 0)));
+    }
+}
@@ -0,0 +1,310 @@
+//! Interactive symbol browser for the `browse` command, with a section pane,
+//! a filterable list of symbols, and the lines of the selected symbol's
+//! source file
+
+use std::collections::BTreeMap;
+use std::io;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Modifier, Style};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use dbgparse::{DbgFile, SymbolKind};
+use crate::Options;
+
+/// Pane with the keyboard focus
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    /// The section list
+    Sections,
+
+    /// The symbol list
+    Symbols,
+}
+
+/// State of the browser
+struct App<'a> {
+    /// File being browsed
+    dbg: &'a DbgFile,
+
+    /// Options for printing addresses and names
+    opts: &'a Options,
+
+    /// Every function and global in address order, with its printed name
+    symbols: Vec<(u32, SymbolKind, String)>,
+
+    /// Line records of each source file as (line, RVA), sorted by line
+    by_file: BTreeMap<&'a str, Vec<(u32, u32)>>,
+
+    /// Indices into `symbols` of the symbols passing the filter and section
+    visible: Vec<usize>,
+
+    /// Text the names of symbols must contain, ignoring case
+    filter: String,
+
+    /// Set while the filter is being typed
+    editing: bool,
+
+    /// Selected section, 0 for all of them
+    section: usize,
+
+    /// Selected index into `visible`
+    selected: usize,
+
+    /// Index into `visible` of the first symbol shown
+    offset: usize,
+
+    /// Number of symbols which fit in the symbol list, for paging
+    page: usize,
+
+    /// Pane with the keyboard focus
+    focus: Focus,
+}
+
+impl<'a> App<'a> {
+    /// Create the browser for `dbg` showing all symbols
+    fn new(dbg: &'a DbgFile, opts: &'a Options) -> Self {
+        let symbols = dbg.symbols_in_range(..).map(|(rva, kind, name)| {
+            (rva, kind, opts.name(name).into_owned())
+        }).collect();
+
+        let mut ret = Self {
+            dbg,
+            opts,
+            symbols,
+            by_file:  dbg.lines_by_file(),
+            visible:  Vec::new(),
+            filter:   String::new(),
+            editing:  false,
+            section:  0,
+            selected: 0,
+            offset:   0,
+            page:     1,
+            focus:    Focus::Symbols,
+        };
+        ret.update_visible();
+        ret
+    }
+
+    /// Recompute the visible symbols after the filter or section changed
+    fn update_visible(&mut self) {
+        let filter = self.filter.to_lowercase();
+        let section = self.section.checked_sub(1)
+            .and_then(|x| self.dbg.sections().get(x));
+
+        self.visible = self.symbols.iter().enumerate()
+            .filter(|(_, (rva, _, name))| {
+                section.is_none_or(|x| x.contains(*rva)) &&
+                    (filter.is_empty() ||
+                        name.to_lowercase().contains(&filter))
+            })
+            .map(|(idx, _)| idx)
+            .collect();
+        self.selected = 0;
+        self.offset = 0;
+    }
+
+    /// Move the selection of the focused pane by `delta`, clamping at the
+    /// ends
+    fn move_by(&mut self, delta: isize) {
+        match self.focus {
+            Focus::Sections => {
+                let last = self.dbg.sections().len();
+                let section = self.section.saturating_add_signed(delta)
+                    .min(last);
+                if section != self.section {
+                    self.section = section;
+                    self.update_visible();
+                }
+            }
+            Focus::Symbols => {
+                let last = self.visible.len().saturating_sub(1);
+                self.selected = self.selected.saturating_add_signed(delta)
+                    .min(last);
+            }
+        }
+    }
+
+    /// Handle a key press, returning `false` to quit
+    fn key(&mut self, code: KeyCode) -> bool {
+        if self.editing {
+            match code {
+                KeyCode::Char(c) => self.filter.push(c),
+                KeyCode::Backspace => { self.filter.pop(); }
+                KeyCode::Esc => {
+                    self.filter.clear();
+                    self.editing = false;
+                }
+                KeyCode::Enter => self.editing = false,
+                _ => return true,
+            }
+            self.update_visible();
+            return true;
+        }
+
+        let page = self.page as isize;
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char('/') => {
+                self.editing = true;
+                self.focus = Focus::Symbols;
+            }
+            KeyCode::Tab | KeyCode::BackTab => {
+                self.focus = match self.focus {
+                    Focus::Sections => Focus::Symbols,
+                    Focus::Symbols  => Focus::Sections,
+                };
+            }
+            KeyCode::Up   | KeyCode::Char('k') => self.move_by(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_by(1),
+            KeyCode::PageUp   => self.move_by(-page),
+            KeyCode::PageDown => self.move_by(page),
+            KeyCode::Home | KeyCode::Char('g') => self.move_by(isize::MIN),
+            KeyCode::End  | KeyCode::Char('G') => self.move_by(isize::MAX),
+            _ => {}
+        }
+        true
+    }
+
+    /// Get the style of the border of a pane, bold if it has the focus
+    fn border(&self, focus: Focus) -> Style {
+        if self.focus == focus {
+            Style::new().add_modifier(Modifier::BOLD)
+        } else {
+            Style::new()
+        }
+    }
+
+    /// Draw the whole browser
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, lines, status] = Layout::vertical([
+            Constraint::Min(3),
+            Constraint::Length(10),
+            Constraint::Length(1),
+        ]).areas(frame.area());
+        let [sections, symbols] = Layout::horizontal([
+            Constraint::Length(28),
+            Constraint::Min(20),
+        ]).areas(main);
+
+        self.draw_sections(frame, sections);
+        self.draw_symbols(frame, symbols);
+        self.draw_lines(frame, lines);
+
+        let text = if self.editing {
+            format!("/{}", self.filter)
+        } else {
+            format!("{} of {} symbols | / filter | tab switch pane | q quit",
+                self.visible.len(), self.symbols.len())
+        };
+        frame.render_widget(Paragraph::new(text), status);
+    }
+
+    /// Draw the section list, with everything as the first entry
+    fn draw_sections(&self, frame: &mut Frame, area: Rect) {
+        let items = std::iter::once(ListItem::new("(all)"))
+            .chain(self.dbg.sections().iter().map(|x| {
+                ListItem::new(format!("{:<8} {:08x}", x.name,
+                    self.opts.addr(self.dbg, x.vaddr)))
+            }));
+        let list = List::new(items)
+            .block(Block::bordered().title("Sections")
+                .border_style(self.border(Focus::Sections)))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+
+        let mut state = ListState::default().with_selected(Some(self.section));
+        frame.render_stateful_widget(list, area, &mut state);
+    }
+
+    /// Draw the window of the symbol list around the selection
+    fn draw_symbols(&mut self, frame: &mut Frame, area: Rect) {
+        // Only build the rows which fit, big kernels have a lot of symbols
+        self.page = (area.height as usize).saturating_sub(2).max(1);
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + self.page {
+            self.offset = self.selected + 1 - self.page;
+        }
+
+        let end = self.visible.len().min(self.offset + self.page);
+        let items = self.visible[self.offset..end].iter().map(|&idx| {
+            let (rva, kind, name) = &self.symbols[idx];
+            let tag = match kind {
+                SymbolKind::Function => "F",
+                SymbolKind::Global   => "G",
+            };
+            ListItem::new(format!("{} {:08x} {}", tag,
+                self.opts.addr(self.dbg, *rva), name))
+        });
+        let list = List::new(items)
+            .block(Block::bordered().title("Symbols")
+                .border_style(self.border(Focus::Symbols)))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+
+        let mut state = ListState::default()
+            .with_selected((!self.visible.is_empty())
+                .then_some(self.selected - self.offset));
+        frame.render_stateful_widget(list, area, &mut state);
+    }
+
+    /// Draw the lines of the source file of the selected symbol, starting at
+    /// the first line of the symbol with its lines highlighted
+    fn draw_lines(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::bordered().title("Lines");
+        let Some(&(rva, kind, _)) = self.visible.get(self.selected)
+                .map(|&idx| &self.symbols[idx]) else {
+            frame.render_widget(block, area);
+            return;
+        };
+
+        // Functions own the lines up to their end, globals just their own
+        let size = match kind {
+            SymbolKind::Function => self.dbg.function_size(rva).unwrap_or(1),
+            SymbolKind::Global   => 1,
+        };
+        let range = rva..rva.saturating_add(size.max(1));
+        let Some((_, (source, _))) = self.dbg.lines().range(range.clone())
+                .next() else {
+            frame.render_widget(block.title_bottom("(no lines)"), area);
+            return;
+        };
+
+        let lines = &self.by_file[source.as_ref()];
+        let first = lines.iter().position(|(_, x)| range.contains(x))
+            .unwrap_or(0);
+        let items = lines[first..].iter()
+            .take((area.height as usize).saturating_sub(2))
+            .map(|&(line, line_rva)| {
+                let item = ListItem::new(format!("{:>6} {:08x}", line,
+                    self.opts.addr(self.dbg, line_rva)));
+                if range.contains(&line_rva) {
+                    item.style(Style::new().add_modifier(Modifier::BOLD))
+                } else {
+                    item
+                }
+            });
+        frame.render_widget(List::new(items)
+            .block(block.title_bottom(self.opts.source(source))), area);
+    }
+
+    /// Draw and handle keys until the user quits
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && !self.key(key.code) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Browse `dbg` in the terminal until the user quits
+pub fn browse(dbg: &DbgFile, opts: &Options) -> io::Result<()> {
+    let mut app = App::new(dbg, opts);
+    let mut terminal = ratatui::init();
+    let ret = app.run(&mut terminal);
+    ratatui::restore();
+    ret
+}
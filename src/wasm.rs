@@ -0,0 +1,102 @@
+//! `wasm-bindgen` bindings, so a web page can parse dropped `.dbg` files
+//!
+//! ```js
+//! const dbg = parse(new Uint8Array(await file.arrayBuffer()));
+//! const rvas = dbg.symbolRvas(), names = dbg.symbolNames();
+//! ```
+//!
+//! Symbols and lines are returned as parallel arrays, which crosses the
+//! JavaScript boundary much faster than an array of objects.
+
+use wasm_bindgen::prelude::*;
+use crate::{DbgFile, SymbolKind};
+
+/// A parsed debug file
+#[wasm_bindgen]
+pub struct Dbg {
+    /// The parsed file
+    dbg: DbgFile,
+}
+
+/// Parse the debug file in `bytes`, throwing an `Error` if it isn't valid
+#[wasm_bindgen]
+pub fn parse(bytes: &[u8]) -> Result<Dbg, JsError> {
+    DbgFile::parse(bytes).map(|dbg| Dbg { dbg })
+        .map_err(|x| JsError::new(&x.to_string()))
+}
+
+#[wasm_bindgen]
+impl Dbg {
+    /// Image base of the image the file describes
+    #[wasm_bindgen(getter, js_name = imageBase)]
+    pub fn image_base(&self) -> u32 {
+        self.dbg.header().image_base
+    }
+
+    /// Name of the machine the image is for, eg. `I386`
+    #[wasm_bindgen(getter)]
+    pub fn machine(&self) -> String {
+        format!("{:?}", self.dbg.header().machine)
+    }
+
+    /// Names of the sections
+    #[wasm_bindgen(js_name = sectionNames)]
+    pub fn section_names(&self) -> Vec<String> {
+        self.dbg.sections().iter().map(|x| x.name.clone()).collect()
+    }
+
+    /// RVAs of the functions and globals, in address order
+    #[wasm_bindgen(js_name = symbolRvas)]
+    pub fn symbol_rvas(&self) -> Vec<u32> {
+        self.dbg.symbols_in_range(..).map(|(rva, _, _)| rva).collect()
+    }
+
+    /// Kinds of the symbols, 0 for functions and 1 for globals, in the same
+    /// order as [`Dbg::symbol_rvas`]
+    #[wasm_bindgen(js_name = symbolKinds)]
+    pub fn symbol_kinds(&self) -> Vec<u8> {
+        self.dbg.symbols_in_range(..).map(|(_, kind, _)| match kind {
+            SymbolKind::Function => 0,
+            SymbolKind::Global   => 1,
+        }).collect()
+    }
+
+    /// Names of the symbols, in the same order as [`Dbg::symbol_rvas`]
+    #[wasm_bindgen(js_name = symbolNames)]
+    pub fn symbol_names(&self) -> Vec<String> {
+        self.dbg.symbols_in_range(..).map(|(_, _, name)| name.to_string())
+            .collect()
+    }
+
+    /// RVAs of the source lines, in address order
+    #[wasm_bindgen(js_name = lineRvas)]
+    pub fn line_rvas(&self) -> Vec<u32> {
+        self.dbg.lines().keys().copied().collect()
+    }
+
+    /// Source filenames of the lines, in the same order as
+    /// [`Dbg::line_rvas`]
+    #[wasm_bindgen(js_name = lineFilenames)]
+    pub fn line_filenames(&self) -> Vec<String> {
+        self.dbg.lines().values().map(|(source, _)| source.to_string())
+            .collect()
+    }
+
+    /// Line numbers of the lines, in the same order as [`Dbg::line_rvas`]
+    #[wasm_bindgen(js_name = lineNumbers)]
+    pub fn line_numbers(&self) -> Vec<u32> {
+        self.dbg.lines().values().map(|(_, line)| *line).collect()
+    }
+
+    /// Describe `rva` as `symbol+offset`, or `undefined` if there's no
+    /// symbol at or before it
+    pub fn lookup(&self, rva: u32) -> Option<String> {
+        self.dbg.nearest_symbol(rva).map(|(name, offset)| {
+            if offset == 0 {
+                name.to_string()
+            } else {
+                format!("{}+{:#x}", name, offset)
+            }
+        })
+    }
+}
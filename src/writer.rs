@@ -0,0 +1,639 @@
+//! Writer for `DI` debug info files, the reverse of [`DbgFile::load`]
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use crate::{DbgFile, DebugDirectory, DebugType, FunctionEntryLayout};
+use crate::{FunctionInfo, Header, Machine, Section, SectionCategory};
+use crate::SymbolKind;
+
+/// Size of an `IMAGE_SEPARATE_DEBUG_HEADER`
+pub(crate) const HEADER_SIZE: usize = 48;
+
+/// Size of an `IMAGE_SECTION_HEADER`
+pub(crate) const SECTION_SIZE: usize = 40;
+
+/// Size of an `IMAGE_COFF_SYMBOLS_HEADER`
+const COFF_HEADER_SIZE: usize = 32;
+
+/// Size of a COFF line number entry
+const LINE_SIZE: usize = 6;
+
+/// Size of a COFF symbol table entry, and of each of its aux entries
+const SYMBOL_SIZE: usize = 18;
+
+/// `IMAGE_SYM_CLASS_EXTERNAL`
+const CLASS_EXTERNAL: u8 = 2;
+
+/// `IMAGE_SYM_CLASS_STATIC`
+const CLASS_STATIC: u8 = 3;
+
+/// `IMAGE_SYM_CLASS_FUNCTION`
+const CLASS_FUNCTION: u8 = 101;
+
+/// `IMAGE_SYM_CLASS_FILE`
+const CLASS_FILE: u8 = 103;
+
+/// `IMAGE_SYM_CLASS_WEAK_EXTERNAL`
+const CLASS_WEAK_EXTERNAL: u8 = 105;
+
+/// Builder for a `DI` file with COFF symbols and line numbers
+///
+/// Start from an existing file with [`DbgWriter::from_dbg`], or from just a
+/// header with [`DbgWriter::new`], add or change whatever is needed, then
+/// [`DbgWriter::write`] it out. The result can be loaded by
+/// [`DbgFile::load`] and by the debuggers of the time.
+#[derive(Debug, Clone, Default)]
+pub struct DbgWriter {
+    /// Header describing the image
+    pub header: Header,
+
+    /// Sections of the image
+    pub sections: Vec<Section>,
+
+    /// Names exported by the image
+    pub exported_names: Vec<String>,
+
+    /// File name of the image, written as miscellaneous debug information
+    pub image_name: Option<String>,
+
+    /// Mapping from RVA to function name
+    pub functions: BTreeMap<u32, String>,
+
+    /// Mapping from RVA to global name
+    pub globals: BTreeMap<u32, String>,
+
+    /// Mapping from RVA to (filename, line number). Line numbers over 65535
+    /// don't fit in a COFF line number entry and are truncated.
+    pub lines: BTreeMap<u32, (String, u32)>,
+
+    /// Mapping from alias (weak external) name to the name and RVA of the
+    /// function or global it resolves to. Aliases of symbols which aren't in
+    /// `functions` or `globals` are dropped.
+    pub aliases: BTreeMap<String, (String, u32)>,
+
+    /// Mapping from function start RVA to its extent, written as `.bf` and
+    /// `.ef` records after the function
+    pub function_info: BTreeMap<u32, FunctionInfo>,
+
+    /// Mapping from function start RVA to end RVA (exclusive), written as an
+    /// exception information function table
+    pub function_table: BTreeMap<u32, u32>,
+}
+
+impl DbgWriter {
+    /// Create a writer for a file with `header` and nothing else
+    pub fn new(header: Header) -> Self {
+        Self { header, ..Default::default() }
+    }
+
+    /// Create a writer holding everything parsed from `dbg`
+    pub fn from_dbg(dbg: &DbgFile) -> Self {
+        Self {
+            header:         dbg.header().clone(),
+            sections:       dbg.sections().to_vec(),
+            exported_names: dbg.exported_names().to_vec(),
+            image_name:     dbg.image_name().map(str::to_string),
+            functions:      dbg.functions().clone(),
+            globals:        dbg.globals().clone(),
+            lines:          dbg.lines().iter().map(|(rva, (source, line))| {
+                (*rva, (source.to_string(), *line))
+            }).collect(),
+            aliases:        dbg.aliases().clone(),
+            function_info:  dbg.function_info().clone(),
+            function_table: dbg.exception_ranges.clone(),
+        }
+    }
+
+    /// Add a section
+    pub fn section(&mut self, section: Section) -> &mut Self {
+        self.sections.push(section);
+        self
+    }
+
+    /// Add an exported name
+    pub fn exported_name(&mut self, name: &str) -> &mut Self {
+        self.exported_names.push(name.to_string());
+        self
+    }
+
+    /// Add the function `name` at `rva`
+    pub fn function(&mut self, rva: u32, name: &str) -> &mut Self {
+        self.functions.insert(rva, name.to_string());
+        self
+    }
+
+    /// Add the global `name` at `rva`
+    pub fn global(&mut self, rva: u32, name: &str) -> &mut Self {
+        self.globals.insert(rva, name.to_string());
+        self
+    }
+
+    /// Add the source line `filename:line` at `rva`
+    pub fn line(&mut self, rva: u32, filename: &str, line: u32) -> &mut Self {
+        self.lines.insert(rva, (filename.to_string(), line));
+        self
+    }
+
+    /// Set the size of the function at `rva`, in bytes
+    pub fn function_size(&mut self, rva: u32, size: u32) -> &mut Self {
+        self.function_table.insert(rva, rva.saturating_add(size));
+        self
+    }
+
+    /// Keep only the functions and globals for which `keep` returns `true`,
+    /// given the kind, RVA, and name of each
+    ///
+    /// Aliases are checked with their own name and the kind and RVA of their
+    /// target, and go away with their target. The extents of dropped
+    /// functions go with them, but their function table entries stay as
+    /// they don't name anything.
+    pub fn retain_symbols(&mut self,
+            mut keep: impl FnMut(SymbolKind, u32, &str) -> bool) -> &mut Self {
+        self.functions.retain(|&rva, name| {
+            keep(SymbolKind::Function, rva, name)
+        });
+        self.globals.retain(|&rva, name| keep(SymbolKind::Global, rva, name));
+        self.function_info.retain(|rva, _| self.functions.contains_key(rva));
+
+        let (functions, globals) = (&self.functions, &self.globals);
+        self.aliases.retain(|alias, (target, rva)| {
+            if functions.get(rva) == Some(target) {
+                keep(SymbolKind::Function, *rva, alias)
+            } else if globals.get(rva) == Some(target) {
+                keep(SymbolKind::Global, *rva, alias)
+            } else {
+                false
+            }
+        });
+        self
+    }
+
+    /// Rename every function, global, and alias to what `rename` returns,
+    /// given the kind, RVA, and current name of each
+    ///
+    /// Aliases are renamed with the kind and RVA of their target, and keep
+    /// pointing at it under its new name. Aliases of symbols which aren't in
+    /// `functions` or `globals` are dropped.
+    pub fn rename_symbols(&mut self,
+            mut rename: impl FnMut(SymbolKind, u32, &str) -> String)
+            -> &mut Self {
+        // Find the kind of each alias before its target is renamed
+        let aliases = core::mem::take(&mut self.aliases).into_iter()
+            .filter_map(|(alias, (target, rva))| {
+                if self.functions.get(&rva) == Some(&target) {
+                    Some((alias, SymbolKind::Function, rva))
+                } else if self.globals.get(&rva) == Some(&target) {
+                    Some((alias, SymbolKind::Global, rva))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        for (&rva, name) in self.functions.iter_mut() {
+            *name = rename(SymbolKind::Function, rva, name);
+        }
+        for (&rva, name) in self.globals.iter_mut() {
+            *name = rename(SymbolKind::Global, rva, name);
+        }
+
+        for (alias, kind, rva) in aliases {
+            let targets = match kind {
+                SymbolKind::Function => &self.functions,
+                SymbolKind::Global   => &self.globals,
+            };
+            let target = targets[&rva].clone();
+            self.aliases.insert(rename(kind, rva, &alias), (target, rva));
+        }
+        self
+    }
+
+    /// Serialize the file into a buffer
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut strings = StringTable::default();
+
+        // Section headers, long names go in the string table
+        let mut section_headers =
+            Vec::with_capacity(self.sections.len() * SECTION_SIZE);
+        for section in &self.sections {
+            let mut name = [0u8; 8];
+            if section.name.len() <= 8 {
+                name[..section.name.len()]
+                    .copy_from_slice(section.name.as_bytes());
+            } else {
+                let long = format!("/{}", strings.add(&section.name));
+                let len = long.len().min(8);
+                name[..len].copy_from_slice(&long.as_bytes()[..len]);
+            }
+
+            section_headers.extend_from_slice(&name);
+            put_u32(&mut section_headers, section.vsize);
+            put_u32(&mut section_headers, section.vaddr);
+            put_u32(&mut section_headers, section.raw_data_sz);
+            put_u32(&mut section_headers, section.ptr_raw_data);
+            put_u32(&mut section_headers, 0); // ptr_relocation
+            put_u32(&mut section_headers, 0); // ptr_line_num
+            put_u16(&mut section_headers, 0); // num_relocs
+            put_u16(&mut section_headers, 0); // num_line_num
+            put_u32(&mut section_headers, section.characteristics);
+        }
+
+        // Exported names, each null terminated
+        let mut exported_names = Vec::new();
+        for name in &self.exported_names {
+            exported_names.extend_from_slice(name.as_bytes());
+            exported_names.push(0);
+        }
+
+        let coff = self.coff(&mut strings);
+        let function_table = self.function_table();
+        let misc = self.misc();
+
+        // The debug directories follow the exported names, and the data
+        // they describe follows them
+        let num_dirs = 1 + !function_table.is_empty() as usize +
+            !misc.is_empty() as usize;
+        let dirs_offset = HEADER_SIZE + section_headers.len() +
+            exported_names.len();
+        let coff_offset = dirs_offset + num_dirs * DebugDirectory::SIZE;
+        let table_offset = coff_offset + coff.len();
+        let misc_offset = table_offset + function_table.len();
+
+        let mut ret = Vec::new();
+        ret.extend_from_slice(b"DI");
+        put_u16(&mut ret, self.header.flags);
+        put_u16(&mut ret, self.header.machine.into());
+        put_u16(&mut ret, self.header.characteristics);
+        put_u32(&mut ret, self.header.timedatestamp);
+        put_u32(&mut ret, self.header.checksum);
+        put_u32(&mut ret, self.header.image_base);
+        put_u32(&mut ret, self.header.size_of_image);
+        put_u32(&mut ret, self.sections.len() as u32);
+        put_u32(&mut ret, exported_names.len() as u32);
+        put_u32(&mut ret, (num_dirs * DebugDirectory::SIZE) as u32);
+        put_u32(&mut ret, self.header.section_align);
+        ret.extend_from_slice(&[0; 8]);
+        ret.extend_from_slice(&section_headers);
+        ret.extend_from_slice(&exported_names);
+
+        self.put_directory(&mut ret, DebugType::Coff, coff.len(), coff_offset);
+        if !function_table.is_empty() {
+            self.put_directory(&mut ret, DebugType::Exception,
+                function_table.len(), table_offset);
+        }
+        if !misc.is_empty() {
+            self.put_directory(&mut ret, DebugType::Misc, misc.len(),
+                misc_offset);
+        }
+
+        ret.extend_from_slice(&coff);
+        ret.extend_from_slice(&function_table);
+        ret.extend_from_slice(&misc);
+        ret
+    }
+
+    /// Serialize the file to `writer`
+    #[cfg(feature = "std")]
+    pub fn write(&self, mut writer: impl std::io::Write)
+            -> std::io::Result<()> {
+        writer.write_all(&self.to_bytes())
+    }
+
+    /// Append an `IMAGE_DEBUG_DIRECTORY` of `typ` for `size` bytes at
+    /// `offset` to `out`
+    fn put_directory(&self, out: &mut Vec<u8>, typ: DebugType, size: usize,
+            offset: usize) {
+        put_u32(out, 0); // characteristics
+        put_u32(out, self.header.timedatestamp);
+        put_u16(out, 0); // major_version
+        put_u16(out, 0); // minor_version
+        put_u32(out, typ.into());
+        put_u32(out, size as u32);
+        put_u32(out, 0); // addr_raw_data, not mapped into the image
+        put_u32(out, offset as u32);
+    }
+
+    /// Get the range of RVAs covered by sections of `category`, as the first
+    /// and last byte, for the COFF symbols header
+    fn category_range(&self, category: SectionCategory) -> (u32, u32) {
+        self.sections.iter().filter(|x| x.category() == category)
+            .map(|x| {
+                let size = x.vsize.max(x.raw_data_sz).max(1);
+                (x.vaddr, x.vaddr.saturating_add(size - 1))
+            })
+            .reduce(|a, b| (a.0.min(b.0), a.1.max(b.1)))
+            .unwrap_or((0, 0))
+    }
+
+    /// Get the one-based index of the section containing `rva` for the
+    /// section number of a symbol, or -1 (absolute) if there is none
+    fn section_number(&self, rva: u32) -> i16 {
+        self.sections.iter().position(|x| x.contains(rva))
+            .map(|x| x as i16 + 1).unwrap_or(-1)
+    }
+
+    /// Build the COFF debug information: the header, line numbers, symbols,
+    /// and string table
+    fn coff(&self, strings: &mut StringTable) -> Vec<u8> {
+        let mut symbols = SymbolTable::default();
+
+        // Functions and globals, remembering where each is for aliases
+        let mut index = BTreeMap::new();
+        let mut publics = self.functions.iter().map(|x| (x, true))
+            .chain(self.globals.iter().map(|x| (x, false)))
+            .collect::<Vec<_>>();
+        publics.sort_by_key(|((rva, _), _)| **rva);
+        for ((&rva, name), is_function) in publics {
+            let info = self.function_info.get(&rva).filter(|_| is_function);
+            index.insert((name.as_str(), rva), symbols.count);
+            symbols.add(strings, name, rva, self.section_number(rva),
+                if is_function { 0x20 } else { 0 }, CLASS_EXTERNAL, &[]);
+
+            // The extent of the function, `.bf` holds the first line and
+            // `.ef` the size
+            if let Some(info) = info {
+                let mut aux = vec![0; SYMBOL_SIZE];
+                aux[4..6].copy_from_slice(&(info.first_line as u16)
+                    .to_le_bytes());
+                symbols.add(strings, ".bf", info.start,
+                    self.section_number(rva), 0, CLASS_FUNCTION, &[aux]);
+                symbols.add(strings, ".ef", info.end.saturating_sub(info.start),
+                    self.section_number(rva), 0, CLASS_FUNCTION,
+                    &[vec![0; SYMBOL_SIZE]]);
+            }
+        }
+
+        // Aliases point at the symbol table index of their target
+        for (alias, (target, rva)) in &self.aliases {
+            if let Some(&target) = index.get(&(target.as_str(), *rva)) {
+                let mut aux = vec![0; SYMBOL_SIZE];
+                aux[0..4].copy_from_slice(&(target as u32).to_le_bytes());
+                aux[4..8].copy_from_slice(&3u32.to_le_bytes()); // alias
+                symbols.add(strings, alias, 0, 0, 0, CLASS_WEAK_EXTERNAL,
+                    &[aux]);
+            }
+        }
+
+        // Lines are attributed to the most recent `.file` by statics which
+        // cover their addresses, so emit a static for each run of lines from
+        // the same file
+        let mut lines = Vec::new();
+        let mut runs: Vec<(&str, u32, u32)> = Vec::new();
+        for (&rva, (filename, line)) in &self.lines {
+            put_u32(&mut lines, rva);
+            put_u16(&mut lines, *line as u16);
+
+            match runs.last_mut() {
+                Some(run) if run.0 == filename => run.2 = rva,
+                _ => runs.push((filename, rva, rva)),
+            }
+        }
+        for (filename, start, last) in runs {
+            // The filename fills as many aux entries as needed
+            let mut name = filename.as_bytes().to_vec();
+            name.resize(name.len().div_ceil(SYMBOL_SIZE).max(1) *
+                SYMBOL_SIZE, 0);
+            let aux = name.chunks(SYMBOL_SIZE).map(|x| x.to_vec())
+                .collect::<Vec<_>>();
+            symbols.add(strings, ".file", 0, -2, 0, CLASS_FILE, &aux);
+
+            // Aux of the static holds the length it covers
+            let mut aux = vec![0; SYMBOL_SIZE];
+            let len = (last - start).saturating_add(1);
+            aux[0..4].copy_from_slice(&len.to_le_bytes());
+            symbols.add(strings, ".text", start, self.section_number(start),
+                0, CLASS_STATIC, &[aux]);
+        }
+
+        let (first_code, last_code) =
+            self.category_range(SectionCategory::Code);
+        let (first_data, last_data) =
+            self.category_range(SectionCategory::Data);
+
+        let mut ret = Vec::new();
+        put_u32(&mut ret, symbols.count as u32);
+        put_u32(&mut ret, (COFF_HEADER_SIZE + lines.len()) as u32);
+        put_u32(&mut ret, (lines.len() / LINE_SIZE) as u32);
+        put_u32(&mut ret, COFF_HEADER_SIZE as u32);
+        put_u32(&mut ret, first_code);
+        put_u32(&mut ret, last_code);
+        put_u32(&mut ret, first_data);
+        put_u32(&mut ret, last_data);
+        ret.extend_from_slice(&lines);
+        ret.extend_from_slice(&symbols.data);
+
+        // The string table size includes the size field itself. `DbgFile`
+        // reads that many bytes after the size field, so pad it out to keep
+        // the table inside of the COFF data.
+        put_u32(&mut ret, (4 + strings.data.len()) as u32);
+        ret.extend_from_slice(&strings.data);
+        ret.extend_from_slice(&[0; 4]);
+        ret
+    }
+
+    /// Build the miscellaneous debug information, an `IMAGE_DEBUG_MISC`
+    /// record holding the name of the image, or nothing if there's no name
+    fn misc(&self) -> Vec<u8> {
+        let Some(name) = &self.image_name else { return Vec::new(); };
+
+        // The name is null terminated and padded out to 4 bytes
+        let mut data = name.as_bytes().to_vec();
+        data.resize((data.len() + 1).next_multiple_of(4), 0);
+
+        let mut ret = Vec::new();
+        put_u32(&mut ret, 1); // IMAGE_DEBUG_MISC_EXENAME
+        put_u32(&mut ret, 12 + data.len() as u32);
+        ret.extend_from_slice(&[0; 4]); // not UTF-16, reserved
+        ret.extend_from_slice(&data);
+        ret
+    }
+
+    /// Build the exception information function table
+    fn function_table(&self) -> Vec<u8> {
+        let layout = if self.header.machine == Machine::Alpha64 {
+            FunctionEntryLayout::Entry64
+        } else {
+            FunctionEntryLayout::Entry
+        };
+
+        // Entries hold the start, end, and end of prologue, which we don't
+        // know so leave at the start
+        let mut ret = Vec::new();
+        for (&start, &end) in &self.function_table {
+            if layout == FunctionEntryLayout::Entry64 {
+                ret.extend_from_slice(&(start as u64).to_le_bytes());
+                ret.extend_from_slice(&(end as u64).to_le_bytes());
+                ret.extend_from_slice(&(start as u64).to_le_bytes());
+            } else {
+                put_u32(&mut ret, start);
+                put_u32(&mut ret, end);
+                put_u32(&mut ret, start);
+            }
+        }
+        ret
+    }
+}
+
+/// COFF symbol table being built, used internally
+#[derive(Default)]
+struct SymbolTable {
+    /// Symbol table entries
+    data: Vec<u8>,
+
+    /// Number of entries, counting aux entries
+    count: usize,
+}
+
+impl SymbolTable {
+    /// Add a symbol followed by the aux entries `aux`, each of which must be
+    /// `SYMBOL_SIZE` bytes
+    #[allow(clippy::too_many_arguments)]
+    fn add(&mut self, strings: &mut StringTable, name: &str, value: u32,
+            section: i16, typ: u16, class: u8, aux: &[Vec<u8>]) {
+        // Names of up to 8 bytes are inline, longer ones are in the string
+        // table
+        if name.len() <= 8 {
+            let mut inline = [0u8; 8];
+            inline[..name.len()].copy_from_slice(name.as_bytes());
+            self.data.extend_from_slice(&inline);
+        } else {
+            put_u32(&mut self.data, 0);
+            put_u32(&mut self.data, strings.add(name));
+        }
+
+        put_u32(&mut self.data, value);
+        self.data.extend_from_slice(&section.to_le_bytes());
+        put_u16(&mut self.data, typ);
+        self.data.push(class);
+        self.data.push(aux.len() as u8);
+        for aux in aux {
+            self.data.extend_from_slice(aux);
+        }
+        self.count += 1 + aux.len();
+    }
+}
+
+/// COFF string table being built, used internally
+#[derive(Default)]
+struct StringTable {
+    /// Null terminated strings, without the leading size field
+    data: Vec<u8>,
+
+    /// Offset of each string already in the table
+    offsets: BTreeMap<String, u32>,
+}
+
+impl StringTable {
+    /// Add `name` to the table if it's not already there, returning its
+    /// offset from the start of the table including the size field
+    fn add(&mut self, name: &str) -> u32 {
+        if let Some(&offset) = self.offsets.get(name) {
+            return offset;
+        }
+
+        let offset = 4 + self.data.len() as u32;
+        self.data.extend_from_slice(name.as_bytes());
+        self.data.push(0);
+        self.offsets.insert(name.to_string(), offset);
+        offset
+    }
+}
+
+/// Append a little endian `u16` to `out`
+pub(crate) fn put_u16(out: &mut Vec<u8>, val: u16) {
+    out.extend_from_slice(&val.to_le_bytes());
+}
+
+/// Append a little endian `u32` to `out`
+pub(crate) fn put_u32(out: &mut Vec<u8>, val: u32) {
+    out.extend_from_slice(&val.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a file with a long section name, names which don't fit in a
+    /// symbol, an alias, a function extent, lines from two source files, and
+    /// an image name
+    fn writer() -> DbgWriter {
+        let mut ret = DbgWriter::new(Header {
+            machine:       Machine::I386,
+            image_base:    0x400000,
+            size_of_image: 0x3000,
+            section_align: 0x1000,
+            ..Default::default()
+        });
+        ret.section(Section {
+            name:            ".text$mn_long".into(),
+            vsize:           0x1000,
+            vaddr:           0x1000,
+            raw_data_sz:     0x1000,
+            ptr_raw_data:    0x400,
+            characteristics: 0x60000020,
+        });
+        ret.section(Section {
+            name:            ".data".into(),
+            vsize:           0x1000,
+            vaddr:           0x2000,
+            raw_data_sz:     0x200,
+            ptr_raw_data:    0x1400,
+            characteristics: 0xc0000040,
+        });
+        ret.function(0x1000, "_KeBugCheckEx@20")
+            .function(0x1100, "_f")
+            .global(0x2000, "_KiServiceTable")
+            .function_size(0x1000, 0x80)
+            .line(0x1000, "d:\\nt\\private\\ntos\\ke\\bugcheck.c", 120)
+            .line(0x1010, "d:\\nt\\private\\ntos\\ke\\bugcheck.c", 121)
+            .line(0x1100, "d:\\nt\\private\\ntos\\ke\\f.c", 7);
+        ret.function_info.insert(0x1000, FunctionInfo {
+            start:      0x1000,
+            end:        0x1080,
+            first_line: 118,
+        });
+        ret.aliases.insert("_KeBugCheck2@24".into(),
+            ("_KeBugCheckEx@20".into(), 0x1000));
+        ret.image_name = Some("ntoskrnl.exe".into());
+        ret
+    }
+
+    #[test]
+    fn round_trip() {
+        let bytes = writer().to_bytes();
+        let dbg = DbgFile::parse(&bytes).unwrap();
+
+        let names = dbg.sections().iter().map(|x| x.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, [".text$mn_long", ".data"]);
+        assert_eq!(dbg.image_name(), Some("ntoskrnl.exe"));
+
+        let functions = dbg.functions().iter()
+            .map(|(rva, name)| (*rva, name.as_str())).collect::<Vec<_>>();
+        assert_eq!(functions, [(0x1000, "_KeBugCheckEx@20"), (0x1100, "_f")]);
+        assert_eq!(dbg.globals()[&0x2000], "_KiServiceTable");
+        assert_eq!(dbg.function_size(0x1000), Some(0x80));
+        assert_eq!(dbg.aliases()["_KeBugCheck2@24"],
+            ("_KeBugCheckEx@20".to_string(), 0x1000));
+        assert_eq!(dbg.function_info()[&0x1000], FunctionInfo {
+            start:      0x1000,
+            end:        0x1080,
+            first_line: 118,
+        });
+
+        let lines = dbg.lines().iter()
+            .map(|(rva, (source, line))| (*rva, &**source, *line))
+            .collect::<Vec<_>>();
+        assert_eq!(lines, [
+            (0x1000, "d:\\nt\\private\\ntos\\ke\\bugcheck.c", 120),
+            (0x1010, "d:\\nt\\private\\ntos\\ke\\bugcheck.c", 121),
+            (0x1100, "d:\\nt\\private\\ntos\\ke\\f.c", 7),
+        ]);
+
+        // Writing out what was parsed gives back the same file
+        assert_eq!(DbgWriter::from_dbg(&dbg).to_bytes(), bytes);
+    }
+}